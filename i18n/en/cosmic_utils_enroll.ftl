@@ -16,11 +16,16 @@ deleted = Deleted fingerprint.
 deleting = Deleting fingerprint...
 delete-all = Delete all
 delete-all-fallback = This fingerprint service does not support deleting a single fingerprint. Delete all of this user's fingerprints instead?
+delete-all-confirm = This fingerprint service can only delete all of a user's fingerprints at once. Delete all of this user's fingerprints?
 clear-device = Clear Device
 confirm-clear = Are you sure?
 clearing-device = Clearing all fingerprints from device for all known users...
 device-cleared = Device cleared for all known users.
-clear-device-confirm = Are you sure you want to clear fingerprints for ALL known users?
+clear-device-confirm = Are you sure you want to clear fingerprints for ALL known users? This cannot be undone. Type “{$phrase}” to confirm.
+clear-device-phrase = clear all fingerprints
+clear-device-authorizing = Waiting for authentication to clear the device...
+clear-device-not-authorized = Authentication failed, the device was not cleared.
+clear-device-policy-missing = The polkit action {$action} is not installed, so clearing the device cannot be authorized. Install the application's polkit policy file.
 
 about-licenses = License information
 help-fprintd = If you have any issues please first check you have fprintd correctly installed. You can find more information from their website:
@@ -29,6 +34,8 @@ help-pam = Also to be able to utilize fingerprint effectively PAM must be config
 
 settings-ui = User Interface
 alternative-ui = Alternative UI
+show-all-users = System accounts
+show-all-users-description = Show system accounts in the user list
 settings-clear-device = Remove all fingerprints
 settings-theme = Theme
 settings-device =
@@ -43,6 +50,7 @@ theme-dark = Dark
 
 register-tooltip = Registers a new fingerprint
 delete-tooltip = Deletes this fingerprint
+delete-all-tooltip = Deletes all of this user's fingerprints
 clear-tooltip = Only works for still existing users
 verify-tooltip = Verifies the fingerprint against the enrolled fingerprints
 
@@ -74,10 +82,17 @@ status-connecting = Connecting to system bus...
 status-searching-device = Searching for fingerprint reader...
 status-device-found = Choose which fingerprint to register.
 status-no-device-found = No fingerprint reader found.
+fprintd-vanished = The fingerprint service stopped, so the scan was cancelled. Try again.
+service-starting = Starting the fingerprint service…
+start-service = Start service
+start-service-tooltip = Start fprintd again, which needs an administrator to authenticate
 status-starting-enrollment = Starting enrollment...
 status-starting-verification = Starting verification...
 
 enroll-starting = Place your finger on the reader to start.
+sensor-touch = Touch the sensor
+sensor-swipe = Swipe your finger
+sensor-finger-detected = Finger detected
 enroll-stage-passed = Scan successful. Keep going.
 enroll-retry-scan = Could not read fingerprint. Please try again.
 enroll-swipe-too-short = Swipe was too short. Please swipe the full length of the sensor.
@@ -98,7 +113,136 @@ error-internal = An internal error occurred.
 error-no-enrolled-prints = No fingerprints enrolled for this finger.
 error-claim-device = Could not claim the device.
 error-device-not-found = Fingerprint device not found.
+error-service-not-installed = The fingerprint service is not installed. Install fprintd to use a fingerprint reader.
+error-service-failed = The fingerprint service failed to start.
 error-timeout = Operation timed out.
 error-prints-not-deleted = Could not delete fingerprints.
 error-connect-dbus = Failed to connect to DBus: {$err}
 error-unsupported-operation = This operation is not supported by the fingerprint service. The installed fprintd implementation may be incompatible.
+
+cli-finger-required = A finger is required, for example --finger right-index.
+cli-unknown-user = Could not determine the current user, use --user.
+cli-cleared = Deleted all fingerprints of {$user}.
+
+provision-user = Enrolling fingerprints of {$user}
+provision-prompt = {$user}: {$finger}. Press Enter to scan, s to skip or q to quit:
+provision-summary = Summary:
+provision-enrolled = enrolled
+provision-already-enrolled = already enrolled
+provision-skipped = skipped
+provision-failed = failed: {$reason}
+
+doctor = Diagnostics
+doctor-run = Run diagnostics
+doctor-running = Checking the fingerprint stack...
+doctor-pass = Pass
+doctor-fail = Fail
+doctor-skipped = Skipped
+doctor-system-bus = System bus
+doctor-system-bus-ok = Connected to the D-Bus system bus.
+doctor-system-bus-hint = Make sure the D-Bus system bus is running, for example with "systemctl status dbus".
+doctor-fprint-service = Fingerprint service
+doctor-fprint-service-activatable = net.reactivated.Fprint can be started on demand.
+doctor-fprint-service-running = net.reactivated.Fprint is running.
+doctor-fprint-service-missing = Nothing provides net.reactivated.Fprint.
+doctor-fprint-service-hint = Install fprintd, or open-fprintd for Validity readers, and make sure its D-Bus service file is installed.
+doctor-daemon = Daemon
+doctor-daemon-fprintd = fprintd
+doctor-daemon-open-fprintd = open-fprintd (deleting a single fingerprint is not supported)
+doctor-daemon-unknown = Unknown, no device to ask.
+doctor-daemon-hint = The fingerprint service did not answer. Check its log, for example with "journalctl -u fprintd".
+doctor-devices = Devices
+doctor-device = {$name} ({$stages} enroll stages)
+doctor-devices-found = {$count} found: {$devices}
+doctor-devices-none = No fingerprint reader found.
+doctor-devices-hint = Check that the reader is connected and supported by libfprint. Readers listed by "lsusb" but not by fprintd may need open-fprintd or a newer libfprint.
+doctor-accounts-daemon = Accounts service
+doctor-accounts-daemon-ok = {$count} users listed.
+doctor-accounts-daemon-hint = Install and start accounts-daemon, it is used to list users.
+doctor-pam = PAM
+doctor-pam-found = pam_fprintd is used by: {$services}
+doctor-pam-none = No PAM service uses pam_fprintd, enrolled fingerprints will not be used to log in.
+doctor-pam-hint = Add pam_fprintd.so to your PAM configuration, see the Help page.
+
+pam-login = Console login
+pam-sudo = sudo
+pam-polkit = Administrator prompts
+pam-greeter = Greeter
+pam-lock-screen = Lock screen
+pam-rule-via = {$control} via {$chain}
+pam-enabled = Fingerprint enabled
+pam-disabled = Not enabled
+pam-not-used = Does not load pam_fprintd.so.
+pam-no-service = No service file found.
+
+pam-setup = Enable fingerprint authentication
+pam-setup-authselect = authselect
+pam-setup-pam-auth-update = pam-auth-update
+pam-setup-pam-d = editing the files in /etc/pam.d
+pam-setup-description = Turns on pam_fprintd for login, sudo and administrator prompts by {$mechanism}. The changes are shown before anything is applied.
+pam-setup-no-prints = Enroll at least one finger before enabling fingerprint authentication.
+pam-setup-done = Fingerprint authentication is already enabled.
+pam-setup-preview = Preview
+pam-setup-confirm = The following changes will be applied by {$mechanism} after you authenticate as an administrator. A password keeps working as a fallback.
+pam-setup-apply = Apply
+pam-setup-applied = Fingerprint authentication enabled.
+pam-setup-not-authorized = Not authorized to change the PAM configuration.
+pam-setup-failed = Enabling fingerprint authentication failed (exit code {$code}).
+pam-setup-not-root = The PAM configuration can only be changed as root.
+
+last-print = Fingerprint required to sign in
+last-print-warning = These services cannot be used without a fingerprint: {$services}. Removing the last enrolled fingerprint can lock the user out of them until PAM is changed.
+delete-anyway = Delete anyway
+
+user-removed = User {$user} no longer exists. Select another user.
+add-user = Add user…
+add-user-description = Enter the name of a user who has not logged in on this computer yet, such as a directory user.
+add-user-placeholder = Username
+add-user-remember = Remember this user
+add-user-confirm = Add
+add-user-not-found = No user named {$user} was found.
+remembered-user = Added by name
+forget-user = Forget
+user-admin = Administrator
+user-locked = Locked
+user-not-enrolled = No prints
+user-enrolled =
+    { $count ->
+        [1] {$count} print
+        *[other] {$count} prints
+    }
+user-auth-required = Needs authentication
+user-not-permitted = Not permitted
+auth-required = Needs authentication
+auth-denied = Not permitted by the system policy
+
+overview = Enrollment overview
+overview-user = User
+overview-unknown = ?
+overview-close = Close
+overview-delete =
+    { $count ->
+        [1] Delete {$count} print
+        *[other] Delete {$count} prints
+    }
+overview-delete-title = Delete selected prints
+overview-delete-confirm =
+    { $count ->
+        [1] Delete the selected fingerprint?
+        *[other] Delete the {$count} selected fingerprints?
+    }
+overview-deleted = Selected fingerprints deleted.
+
+history = History
+history-description = Fingerprint changes recorded in the system journal
+history-empty = No fingerprint changes recorded yet.
+history-unavailable = Cannot read the journal: {$err}
+history-refresh = Refresh
+history-enroll = Enrolled
+history-delete = Deleted
+history-delete-all = Deleted all
+history-clear = Cleared device
+history-entry = {$actor} → {$target}
+history-forged = Claims to be by {$actor}
+history-success = Done
+history-failed = Failed: {$result}
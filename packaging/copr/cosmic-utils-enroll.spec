@@ -76,6 +76,7 @@ just rootdir=%{buildroot} install
 %{_datadir}/applications/%{appid}.desktop
 %{_datadir}/appdata/%{appid}.metainfo.xml
 %{_datadir}/icons/hicolor/scalable/apps/%{appid}.svg
+%{_datadir}/polkit-1/actions/%{appid}.policy
 
 %changelog
 * Tue Jul 15 2026 Joonas Tuomi <joonas@cosmic-utils.org> - 1.2.1-1
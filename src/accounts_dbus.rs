@@ -10,6 +10,12 @@ use zbus::proxy;
 pub trait Accounts {
     fn list_cached_users(&self) -> zbus::Result<Vec<zbus::zvariant::OwnedObjectPath>>;
     fn find_user_by_name(&self, name: &str) -> zbus::Result<zbus::zvariant::OwnedObjectPath>;
+
+    #[zbus(signal)]
+    fn user_added(&self, user: zbus::zvariant::ObjectPath<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn user_deleted(&self, user: zbus::zvariant::ObjectPath<'_>) -> zbus::Result<()>;
 }
 
 #[proxy(
@@ -25,4 +31,21 @@ pub trait User {
 
     #[zbus(property)]
     fn icon_file(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn uid(&self) -> zbus::Result<u64>;
+
+    /// 0 for a standard user, 1 for an administrator
+    #[zbus(property)]
+    fn account_type(&self) -> zbus::Result<i32>;
+
+    #[zbus(property)]
+    fn locked(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn system_account(&self) -> zbus::Result<bool>;
+
+    /// Emitted when any property of the user changes
+    #[zbus(signal)]
+    fn changed(&self) -> zbus::Result<()>;
 }
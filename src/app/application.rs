@@ -1,10 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 use crate::app::{
     ContextPage, MenuAction,
-    finger::*,
     message::Message,
+    state::{Confirmation, OperationState, Removal},
     subscription::*,
-    tasks::{task_connect, task_load_users},
+    tasks::{
+        task_connect, task_find_remembered_users, task_inspect_pam, task_load_history,
+        task_load_users,
+    },
+    users::{AddUserItem, UserOption},
 };
 use crate::config::{Config, read_config};
 use crate::fl;
@@ -17,7 +21,6 @@ use cosmic::{
 };
 
 use super::AppModel;
-use std::collections::HashMap;
 
 /// Turns AppModel to a COSMIC application
 impl cosmic::Application for AppModel {
@@ -52,39 +55,26 @@ impl cosmic::Application for AppModel {
         // Start with navigation closed
         core.nav_bar_toggle();
 
-        let mut app = AppModel {
-            core,
-            context_page: ContextPage::About,
-            nav: nav_bar::Model::default(),
-            key_binds: HashMap::new(),
-            config,
-            config_handler,
-            status: fl!("status-connecting"),
-            device_path: None,
-            devices: Vec::new(),
-            device_proxy: None,
-            connection: None,
-            busy: true,
-            enrolling_finger: None,
-            verifying_finger: false,
-            enroll_progress: 0,
-            enroll_total_stages: None,
-            users: Vec::new(),
-            selected_user: None,
-            selected_finger: Finger::default(),
-            enrolled_fingers: Vec::new(),
-            confirm_clear: false,
-            confirm_delete_all: false,
-        };
+        let mut app = AppModel::new(core, config_handler, config);
 
         let start_theme = cosmic::command::set_theme(app.config.app_theme.theme());
         let command = app.update_title_task();
         let connect_task = task_connect();
         let users_task = task_load_users();
+        let remembered_task = task_find_remembered_users(app.config.remembered_users.clone());
+        // Needed to warn before removing prints PAM depends on.
+        let pam_task = task_inspect_pam();
 
         (
             app,
-            Task::batch(vec![command, connect_task, users_task, start_theme]),
+            Task::batch(vec![
+                command,
+                connect_task,
+                users_task,
+                remembered_task,
+                pam_task,
+                start_theme,
+            ]),
         )
     }
 
@@ -98,6 +88,8 @@ impl cosmic::Application for AppModel {
                     menu::Item::Button(fl!("about"), None, MenuAction::About),
                     menu::Item::Button(fl!("settings"), None, MenuAction::Settings),
                     menu::Item::Button(fl!("help"), None, MenuAction::Help),
+                    menu::Item::Button(fl!("overview"), None, MenuAction::Overview),
+                    menu::Item::Button(fl!("history"), None, MenuAction::History),
                 ],
             ),
         )]);
@@ -136,47 +128,133 @@ impl cosmic::Application for AppModel {
                 Message::ToggleContextPage(ContextPage::Help),
             )
             .title(fl!("help")),
+            ContextPage::History => context_drawer::context_drawer(
+                self.history(),
+                Message::ToggleContextPage(ContextPage::History),
+            )
+            .title(fl!("history")),
         })
     }
 
     /// Display a dialog in the center of the application window when `Some`.
     fn dialog(&self) -> Option<Element<'_, Self::Message>> {
-        if self.confirm_clear {
-            Some(
+        if let Some(add_user) = &self.add_user {
+            return Some(self.add_user_dialog(add_user));
+        }
+
+        match self.state.confirmation()? {
+            Confirmation::ClearDevice => {
+                // Irreversible, so the phrase has to be typed out.
+                let clear_btn = widget::button::destructive(fl!("clear-device"));
+                let clear_btn = if self.clear_phrase_matches() {
+                    clear_btn.on_press(Message::ClearDevice)
+                } else {
+                    clear_btn
+                };
+                Some(
+                    dialog::dialog()
+                        .title(fl!("clear-device"))
+                        .body(fl!(
+                            "clear-device-confirm",
+                            phrase = fl!("clear-device-phrase")
+                        ))
+                        .control(
+                            widget::text_input(fl!("clear-device-phrase"), &self.clear_phrase)
+                                .on_input(Message::ClearPhraseInput),
+                        )
+                        .primary_action(clear_btn)
+                        .secondary_action(
+                            widget::button::standard(fl!("cancel")).on_press(Message::Cancel),
+                        )
+                        .into(),
+                )
+            }
+            Confirmation::DeleteAll => Some(
                 dialog::dialog()
-                    .title(fl!("clear-device"))
-                    .body(fl!("clear-device-confirm"))
+                    .title(fl!("delete-all"))
+                    .body(if self.capabilities.delete_finger {
+                        fl!("delete-all-fallback")
+                    } else {
+                        fl!("delete-all-confirm")
+                    })
                     .primary_action(
-                        widget::button::destructive(fl!("clear-device"))
-                            .on_press(Message::ClearDevice),
+                        widget::button::destructive(fl!("delete-all"))
+                            .on_press(Message::ConfirmDeleteAll),
                     )
                     .secondary_action(
-                        widget::button::standard(fl!("cancel")).on_press(Message::CancelClear),
+                        widget::button::standard(fl!("cancel")).on_press(Message::Cancel),
                     )
                     .into(),
-            )
-        } else if self.confirm_delete_all {
-            Some(
+            ),
+            Confirmation::DeleteSelected => Some(
                 dialog::dialog()
-                    .title(fl!("delete-all"))
-                    .body(fl!("delete-all-fallback"))
+                    .title(fl!("overview-delete-title"))
+                    .body(fl!(
+                        "overview-delete-confirm",
+                        count = self.overview_selected_count()
+                    ))
                     .primary_action(
-                        widget::button::destructive(fl!("delete-all"))
-                            .on_press(Message::ConfirmDeleteAll),
+                        widget::button::destructive(fl!("delete"))
+                            .on_press(Message::DeleteSelected),
                     )
                     .secondary_action(
-                        widget::button::standard(fl!("cancel")).on_press(Message::CancelDeleteAll),
+                        widget::button::standard(fl!("cancel")).on_press(Message::Cancel),
                     )
                     .into(),
-            )
-        } else {
-            None
+            ),
+            Confirmation::LastPrint(removal) => {
+                let confirm = match removal {
+                    Removal::Finger => Message::Delete,
+                    Removal::AllFingers => Message::ConfirmDeleteAll,
+                    Removal::Device => Message::ClearDevice,
+                    Removal::Selection => Message::DeleteSelected,
+                };
+                Some(
+                    dialog::dialog()
+                        .title(fl!("last-print"))
+                        .body(fl!(
+                            "last-print-warning",
+                            services = self.fingerprint_only_services().join(", ")
+                        ))
+                        .primary_action(
+                            widget::button::destructive(fl!("delete-anyway")).on_press(confirm),
+                        )
+                        .secondary_action(
+                            widget::button::standard(fl!("cancel")).on_press(Message::Cancel),
+                        )
+                        .into(),
+                )
+            }
+            Confirmation::EnablePam => {
+                let plan = self.pam_plan.as_ref()?;
+                Some(
+                    dialog::dialog()
+                        .title(fl!("pam-setup"))
+                        .body(fl!(
+                            "pam-setup-confirm",
+                            mechanism = plan.mechanism.localized_name()
+                        ))
+                        .control(
+                            widget::scrollable(widget::text::monospace(plan.preview())).height(300),
+                        )
+                        .primary_action(
+                            widget::button::suggested(fl!("pam-setup-apply"))
+                                .on_press(Message::EnablePam),
+                        )
+                        .secondary_action(
+                            widget::button::standard(fl!("cancel")).on_press(Message::Cancel),
+                        )
+                        .into(),
+                )
+            }
         }
     }
 
     /// Chooses which view to render based on config
     fn view(&self) -> Element<'_, Self::Message> {
-        if self.config.experimental_ui {
+        if let Some(overview) = &self.overview {
+            self.view_overview(overview)
+        } else if self.config.experimental_ui {
             self.view_old()
         } else {
             self.view_main()
@@ -211,16 +289,16 @@ impl cosmic::Application for AppModel {
         ];
 
         // Add enrollment subscription if enrolling
-        if let (Some(finger_name), Some(device_path), Some(connection), Some(user)) = (
-            &self.enrolling_finger,
+        if let (Some(finger_name), Some(device_path), Some(backend), Some(user)) = (
+            self.state.enrolling_finger(),
             &self.device_path,
-            &self.connection,
+            &self.backend,
             &self.selected_user,
         ) {
             let data = EnrollData::new(
                 finger_name.clone(),
                 device_path.clone(),
-                connection.clone(),
+                backend.clone(),
                 user.username.clone(),
             );
 
@@ -228,15 +306,17 @@ impl cosmic::Application for AppModel {
         }
 
         // Add verify subscription if verifying
-        if self.verifying_finger
-            && let (Some(device_path), Some(connection), Some(user)) =
-                (&self.device_path, &self.connection, &self.selected_user)
-        {
+        if let (Some(finger), Some(device_path), Some(backend), Some(user)) = (
+            self.state.verifying_finger(),
+            &self.device_path,
+            &self.backend,
+            &self.selected_user,
+        ) {
             let data = VerifyData::new(
                 device_path.clone(),
-                connection.clone(),
+                backend.clone(),
                 user.username.clone(),
-                self.selected_finger,
+                finger,
             );
 
             subscriptions.push(verify_subscription(data));
@@ -244,6 +324,13 @@ impl cosmic::Application for AppModel {
 
         subscriptions.push(portal_theme_subscription(self.config.app_theme));
 
+        subscriptions.push(hotplug_subscription());
+
+        if let Some(connection) = &self.connection {
+            subscriptions.push(users_subscription(connection.clone()));
+            subscriptions.push(fprintd_subscription(connection.clone()));
+        }
+
         subscriptions.push(key_subscription());
 
         Subscription::batch(subscriptions)
@@ -259,18 +346,37 @@ impl cosmic::Application for AppModel {
             Message::FingerSelected(finger) => self.on_finger_selected(finger),
             Message::UpdateDevices(devices) => self.on_devices_found(devices),
             Message::DeviceFound(path) => self.on_device_found(path),
+            Message::CapabilitiesProbed(path, capabilities) => {
+                self.on_capabilities_probed(path, capabilities)
+            }
+            Message::FprintdVanished => self.on_fprintd_vanished(),
+            Message::FprintdAppeared => self.on_fprintd_appeared(),
+            Message::UsbHotplug => self.on_usb_hotplug(),
+            Message::DevicesChanged(res) => self.on_devices_changed(res),
+            Message::ServiceChecked(state) => self.on_service_checked(state),
+            Message::StartService => self.on_start_service(),
+            Message::ServiceStarted(res) => self.on_service_started(res),
             Message::EnrolledFingers(fingers) => self.on_fingers_listed(fingers),
             Message::OperationError(err) => self.on_error(err),
             Message::EnrollStart(total) => self.on_enroll_start(total),
             Message::EnrollStatus(status, done) => self.on_enroll_status(status, done),
-            Message::EnrollStop => self.on_enroll_stop(),
+            Message::SensorChanged(sensor) => self.on_sensor_changed(sensor),
+            Message::Cancel => self.on_cancel(),
             Message::DeleteComplete(clear) => self.on_delete_complete(clear),
             Message::Delete => self.on_delete(),
+            Message::DeleteAll => self.on_delete_all(),
             Message::DeleteSingleUnsupported => self.on_delete_single_unsupported(),
             Message::ConfirmDeleteAll => self.on_confirm_delete_all(),
-            Message::CancelDeleteAll => self.on_cancel_delete_all(),
+            Message::ToggleOverview => self.on_toggle_overview(),
+            Message::ToggleOverviewCell(username, finger) => {
+                self.on_toggle_overview_cell(username, finger)
+            }
+            Message::ToggleOverviewRow(username) => self.on_toggle_overview_row(username),
+            Message::DeleteSelected => self.on_delete_selected(),
+            Message::SelectionDeleted(res) => self.on_selection_deleted(res),
             Message::ClearDevice => self.on_clear_device(),
-            Message::CancelClear => self.on_cancel_clear(),
+            Message::ClearPhraseInput(phrase) => self.on_clear_phrase_input(phrase),
+            Message::ClearAuthorized(authorization) => self.on_clear_authorized(authorization),
             Message::ClearComplete(res) => self.on_clear_completion(res),
             Message::CloseApplication => self.on_close(),
             Message::Register => self.on_register(),
@@ -280,29 +386,50 @@ impl cosmic::Application for AppModel {
             Message::LaunchUrl(url) => self.on_open_link(url),
             Message::VerifyFinger => self.on_verify_finger(),
             Message::VerifyStatus(status, done) => self.on_verify_status(status, done),
-            Message::VerifyStop => self.on_verify_stop(),
             Message::ThemeChanged(is_dark) => self.on_portal_color_scheme_changed(is_dark),
             Message::ThemeSetting(theme) => self.on_theme_setting(theme),
             Message::SelectFingerByNumber(key) => self.on_select_finger_by_number(key),
             Message::SelectDevice(index) => self.on_select_device(index),
             Message::UsersLoaded(users) => self.on_users_loaded(users),
+            Message::UserChanged(user) => self.on_user_changed(user),
+            Message::UserRemoved(user) => self.on_user_removed(user),
+            Message::UserPrints(device, username, fingers) => {
+                self.on_user_prints(device, username, fingers)
+            }
+            Message::OpenAddUser => self.on_open_add_user(),
+            Message::AddUserInput(username) => self.on_add_user_input(username),
+            Message::AddUserRemember(remember) => self.on_add_user_remember(remember),
+            Message::AddUser => self.on_add_user(),
+            Message::CloseAddUser => self.on_close_add_user(),
+            Message::UserFound(username, user) => self.on_user_found(username, user),
+            Message::RememberedUsersFound(users) => self.on_remembered_users_found(users),
+            Message::RefreshHistory => task_load_history(),
+            Message::HistoryLoaded(history) => self.on_history_loaded(history),
+            Message::PermissionsChecked(permissions) => self.on_permissions_checked(permissions),
+            Message::RunDiagnostics => self.on_run_diagnostics(),
+            Message::DiagnosticsReady(checks) => self.on_diagnostics_ready(checks),
+            Message::PamInspected(services, plan) => self.on_pam_inspected(services, plan),
+            Message::EnablePam => self.on_enable_pam(),
+            Message::PamSetupApplied(result) => self.on_pam_setup_applied(result),
         }
     }
 
     /// Called when a nav item is selected.
     fn on_nav_select(&mut self, id: nav_bar::Id) -> Task<cosmic::Action<Self::Message>> {
-        if self.busy {
+        if self.state.is_busy() {
             return Task::none();
         }
-        self.confirm_clear = false;
-        self.confirm_delete_all = false;
+        if self.state.confirmation().is_some() {
+            self.transition(OperationState::Idle);
+        }
+        // Keeps the selected user active behind the dialog.
+        if self.nav.data::<AddUserItem>(id).is_some() {
+            return self.on_open_add_user();
+        }
+        self.overview = None;
         // Activate the page in the model.
         self.nav.activate(id);
-        self.selected_user = self
-            .users
-            .iter()
-            .find(|user| self.nav.text(id).is_some_and(|f| f == user.to_string()))
-            .cloned();
+        self.selected_user = self.nav.data::<UserOption>(id).cloned();
 
         Task::batch(vec![self.update_title_task(), self.list_fingers_task()])
     }
@@ -0,0 +1,395 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::collections::HashMap;
+use std::os::unix::net::UnixDatagram;
+
+use nix::libc;
+use nix::unistd::{Uid, User};
+
+use crate::app::error::AppError;
+use crate::fl;
+
+/// Identifies the audit entries in the journal
+pub const MESSAGE_ID: &str = "1f5331cab43b479ca33776b1c92f04e5";
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+/// Number of entries shown in the history
+const HISTORY_LIMIT: &str = "200";
+/// Result of a change which went through
+pub const SUCCESS: &str = "success";
+
+/// A change to the prints on a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Enroll,
+    Delete,
+    DeleteAll,
+    Clear,
+}
+
+impl Action {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Action::Enroll => "enroll",
+            Action::Delete => "delete",
+            Action::DeleteAll => "delete-all",
+            Action::Clear => "clear",
+        }
+    }
+
+    fn from_str(action: &str) -> Option<Self> {
+        [
+            Action::Enroll,
+            Action::Delete,
+            Action::DeleteAll,
+            Action::Clear,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == action)
+    }
+
+    pub fn localized_name(&self) -> String {
+        match self {
+            Action::Enroll => fl!("history-enroll"),
+            Action::Delete => fl!("history-delete"),
+            Action::DeleteAll => fl!("history-delete-all"),
+            Action::Clear => fl!("history-clear"),
+        }
+    }
+}
+
+/// One audit record
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    /// Microseconds since the epoch, as journald received the entry
+    pub timestamp: Option<u64>,
+    pub action: Option<Action>,
+    /// User who made the change, as named by whoever wrote the entry
+    pub actor: String,
+    /// User journald saw send the entry (its trusted `_UID`), empty when
+    /// journald did not say
+    pub sender: String,
+    /// User whose prints changed
+    pub target: String,
+    pub device_name: String,
+    pub device_path: String,
+    pub finger: Option<String>,
+    /// [`SUCCESS`] or what went wrong
+    pub result: String,
+}
+
+impl Entry {
+    pub fn is_success(&self) -> bool {
+        self.result == SUCCESS
+    }
+
+    /// Any local process can write an entry naming someone else, only the
+    /// sender is vouched for by journald.
+    ///
+    /// **Returns** whether the entry names another actor than its sender
+    pub fn is_forged(&self) -> bool {
+        !self.sender.is_empty() && self.sender != self.actor
+    }
+
+    /// **Returns** the journal fields of this entry. Journald adds the
+    /// trusted `_UID` and `_PID` of the sender on its own.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let action = self
+            .action
+            .map(|action| action.as_str())
+            .unwrap_or_default();
+        let finger = self.finger.as_deref().unwrap_or_default();
+        let priority = if self.is_success() { "5" } else { "4" };
+        let message = if finger.is_empty() {
+            format!(
+                "{} {action} prints of {} on {}: {}",
+                self.actor, self.target, self.device_name, self.result
+            )
+        } else {
+            format!(
+                "{} {action} {finger} of {} on {}: {}",
+                self.actor, self.target, self.device_name, self.result
+            )
+        };
+
+        vec![
+            ("MESSAGE_ID", MESSAGE_ID.to_string()),
+            ("MESSAGE", message),
+            ("PRIORITY", priority.to_string()),
+            ("SYSLOG_IDENTIFIER", env!("CARGO_PKG_NAME").to_string()),
+            ("ENROLL_ACTION", action.to_string()),
+            ("ENROLL_ACTOR", self.actor.clone()),
+            ("ENROLL_TARGET_USER", self.target.clone()),
+            ("ENROLL_DEVICE_NAME", self.device_name.clone()),
+            ("ENROLL_DEVICE_PATH", self.device_path.clone()),
+            ("ENROLL_FINGER", finger.to_string()),
+            ("ENROLL_RESULT", self.result.clone()),
+        ]
+    }
+}
+
+/// Records changes made on one device by the current user
+#[derive(Clone, Debug, Default)]
+pub struct Auditor {
+    actor: String,
+    device_name: String,
+    device_path: String,
+}
+
+impl Auditor {
+    pub fn new(device_name: &str, device_path: &str) -> Self {
+        Self {
+            actor: user_name(Uid::current()),
+            device_name: device_name.to_string(),
+            device_path: device_path.to_string(),
+        }
+    }
+
+    /// Sends an audit entry for `action` on the prints of `target` to the
+    /// journal. Failing to do so is only logged.
+    pub fn record(&self, action: Action, target: &str, finger: Option<&str>, result: &str) {
+        let entry = Entry {
+            timestamp: None,
+            action: Some(action),
+            actor: self.actor.clone(),
+            sender: String::new(),
+            target: target.to_string(),
+            device_name: self.device_name.clone(),
+            device_path: self.device_path.clone(),
+            finger: finger.map(str::to_string),
+            result: result.to_string(),
+        };
+        tracing::info!(
+            action = action.as_str(),
+            actor = %entry.actor,
+            target,
+            finger,
+            result,
+            "prints changed"
+        );
+        if let Err(err) = send(&encode(&entry.fields())) {
+            tracing::warn!(%err, "cannot write the audit entry to the journal");
+        }
+    }
+}
+
+/// **Returns** the name of the user with `uid`, or the uid when it has none
+fn user_name(uid: Uid) -> String {
+    match User::from_uid(uid) {
+        Ok(Some(user)) => user.name,
+        _ => uid.to_string(),
+    }
+}
+
+/// **Returns** the audit result of an operation
+pub fn outcome<T>(result: &Result<T, AppError>) -> String {
+    match result {
+        Ok(_) => SUCCESS.to_string(),
+        Err(err) => failure(err),
+    }
+}
+
+/// **Returns** the audit result of an operation which failed with `err`
+pub fn failure(err: &AppError) -> String {
+    format!("{err:?}")
+}
+
+/// Serializes fields in the journal's native protocol. Values spanning
+/// lines are sent with their length instead of a `=`.
+fn encode(fields: &[(&str, String)]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    for (key, value) in fields {
+        payload.extend_from_slice(key.as_bytes());
+        if value.contains('\n') {
+            payload.push(b'\n');
+            payload.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            payload.push(b'=');
+        }
+        payload.extend_from_slice(value.as_bytes());
+        payload.push(b'\n');
+    }
+    payload
+}
+
+fn send(payload: &[u8]) -> std::io::Result<()> {
+    // Tests must not fill the journal of whoever runs them.
+    if cfg!(test) {
+        return Ok(());
+    }
+    UnixDatagram::unbound()?.send_to(payload, JOURNAL_SOCKET)?;
+    Ok(())
+}
+
+/// Reads the latest audit entries back through journalctl, which shows
+/// those of the user's own journal and, for members of the `systemd-journal`
+/// or `adm` group, those of everyone.
+///
+/// **Returns** the entries, newest first, or why they cannot be read
+pub async fn history() -> Result<Vec<Entry>, String> {
+    let output = tokio::process::Command::new("journalctl")
+        .args(["--output=json", "--reverse", "--no-pager", "--lines"])
+        .arg(HISTORY_LIMIT)
+        .arg(format!("MESSAGE_ID={MESSAGE_ID}"))
+        .output()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() && stdout.trim().is_empty() && !stderr.trim().is_empty() {
+        return Err(stderr.trim().to_string());
+    }
+    Ok(stdout.lines().filter_map(parse_entry).collect())
+}
+
+/// **Returns** the audit entry in a line of `journalctl --output=json`
+fn parse_entry(line: &str) -> Option<Entry> {
+    let fields: HashMap<String, serde_json::Value> = serde_json::from_str(line).ok()?;
+    // Binary values come as arrays of bytes, which no audit field has.
+    let field = |key: &str| {
+        fields
+            .get(key)
+            .and_then(|value| value.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    if field("MESSAGE_ID") != MESSAGE_ID {
+        return None;
+    }
+
+    let finger = field("ENROLL_FINGER");
+    Some(Entry {
+        timestamp: field("__REALTIME_TIMESTAMP").parse().ok(),
+        action: Action::from_str(&field("ENROLL_ACTION")),
+        actor: field("ENROLL_ACTOR"),
+        sender: field("_UID")
+            .parse()
+            .map(|uid| user_name(Uid::from_raw(uid)))
+            .unwrap_or_default(),
+        target: field("ENROLL_TARGET_USER"),
+        device_name: field("ENROLL_DEVICE_NAME"),
+        device_path: field("ENROLL_DEVICE_PATH"),
+        finger: (!finger.is_empty()).then_some(finger),
+        result: field("ENROLL_RESULT"),
+    })
+}
+
+/// **Returns** `timestamp` in microseconds as local date and time
+pub fn format_time(timestamp: u64) -> String {
+    let seconds = (timestamp / 1_000_000) as libc::time_t;
+    // SAFETY: localtime_r only writes to the given struct, and strftime at
+    // most the given length of the buffer.
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&seconds, &mut tm).is_null() {
+            return String::new();
+        }
+        let mut buffer = [0u8; 32];
+        let len = libc::strftime(
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+            c"%Y-%m-%d %H:%M".as_ptr(),
+            &tm,
+        );
+        String::from_utf8_lossy(&buffer[..len]).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> Entry {
+        Entry {
+            timestamp: None,
+            action: Some(Action::Delete),
+            actor: "root".to_string(),
+            sender: String::new(),
+            target: "alice".to_string(),
+            device_name: "Reader".to_string(),
+            device_path: "/net/reactivated/Fprint/Device/0".to_string(),
+            finger: Some("left-thumb".to_string()),
+            result: SUCCESS.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let payload = encode(&[
+            ("MESSAGE", "one line".to_string()),
+            ("DETAIL", "two\nlines".to_string()),
+        ]);
+
+        let mut expected = b"MESSAGE=one line\nDETAIL\n".to_vec();
+        expected.extend_from_slice(&9u64.to_le_bytes());
+        expected.extend_from_slice(b"two\nlines\n");
+        assert_eq!(payload, expected);
+    }
+
+    #[test]
+    fn test_fields_round_trip() {
+        let entry = entry();
+        let mut json: serde_json::Map<String, serde_json::Value> = entry
+            .fields()
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.into()))
+            .collect();
+        json.insert(
+            "__REALTIME_TIMESTAMP".to_string(),
+            "1700000000000000".into(),
+        );
+        json.insert("_UID".to_string(), "0".into());
+        let line = serde_json::Value::Object(json.clone()).to_string();
+
+        let parsed = parse_entry(&line).unwrap();
+        assert_eq!(
+            parsed,
+            Entry {
+                timestamp: Some(1_700_000_000_000_000),
+                sender: user_name(Uid::from_raw(0)),
+                ..entry
+            }
+        );
+        assert!(!parsed.is_forged());
+
+        // An entry written as someone else gives the sender away.
+        json.insert("ENROLL_ACTOR".to_string(), "alice".into());
+        let line = serde_json::Value::Object(json).to_string();
+        let forged = parse_entry(&line).unwrap();
+        assert_eq!(forged.actor, "alice");
+        assert_eq!(forged.sender, parsed.sender);
+        assert!(forged.is_forged());
+    }
+
+    #[test]
+    fn test_parse_entry_ignores_other_messages() {
+        assert_eq!(parse_entry(r#"{"MESSAGE":"hello"}"#), None);
+        assert_eq!(parse_entry("not json"), None);
+
+        let line = format!(
+            r#"{{"MESSAGE_ID":"{MESSAGE_ID}","ENROLL_ACTION":"clear","ENROLL_RESULT":[1,2]}}"#
+        );
+        let entry = parse_entry(&line).unwrap();
+        assert_eq!(entry.action, Some(Action::Clear));
+        assert_eq!(entry.finger, None);
+        assert_eq!(entry.result, "");
+    }
+
+    #[test]
+    fn test_format_time() {
+        // Two days after the epoch, whatever the local time zone.
+        assert_eq!(
+            format_time(2 * 86_400 * 1_000_000).len(),
+            "1970-01-03 00:00".len()
+        );
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(outcome(&Ok::<(), AppError>(())), SUCCESS);
+        assert_eq!(
+            outcome(&Err::<(), _>(AppError::PermissionDenied)),
+            "PermissionDenied"
+        );
+    }
+}
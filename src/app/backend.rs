@@ -0,0 +1,697 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use cosmic::iced::futures::channel::mpsc::Sender;
+use futures_util::future::BoxFuture;
+use zbus::zvariant::OwnedObjectPath;
+
+use crate::app::{
+    error::AppError,
+    fprint::*,
+    message::{DeviceOption, Message},
+};
+use crate::fprint_dbus::DeviceProxy;
+
+pub type BackendResult<T> = Result<T, AppError>;
+
+/// Fingerprint operations used by the application.
+///
+/// Every call returns a `'static` future so it can be handed straight to a
+/// `Task` or a `Subscription`. Enroll and verify stream their progress as
+/// `EnrollStart`/`EnrollStatus` and `VerifyStatus` messages through `output`.
+pub trait FingerprintBackend: Send + Sync {
+    /// Forgets the device proxies kept between calls, which belong to a
+    /// service instance that is gone.
+    fn reset(&self);
+
+    /// **Returns** the object path of the default device
+    fn find_device(&self) -> BoxFuture<'static, BackendResult<OwnedObjectPath>>;
+
+    /// **Returns** every device with its display name
+    fn list_devices(&self) -> BoxFuture<'static, BackendResult<Vec<DeviceOption>>>;
+
+    /// **Returns** what the daemon implements for a device
+    fn capabilities(
+        &self,
+        device: OwnedObjectPath,
+    ) -> BoxFuture<'static, BackendResult<DeviceCapabilities>>;
+
+    /// **Returns** the finger ids enrolled for a user
+    fn list_enrolled_fingers(
+        &self,
+        device: OwnedObjectPath,
+        username: String,
+    ) -> BoxFuture<'static, BackendResult<Vec<String>>>;
+
+    /// Enrolls a finger, resolving once the device reports `done`.
+    fn enroll(
+        &self,
+        device: OwnedObjectPath,
+        finger: String,
+        username: String,
+        output: Sender<Message>,
+    ) -> BoxFuture<'static, BackendResult<()>>;
+
+    /// Stops an ongoing enrollment and releases the device.
+    fn enroll_stop(&self, device: OwnedObjectPath) -> BoxFuture<'static, BackendResult<()>>;
+
+    /// Verifies a finger, resolving once the device reports `done`.
+    fn verify(
+        &self,
+        device: OwnedObjectPath,
+        finger: String,
+        username: String,
+        output: Sender<Message>,
+    ) -> BoxFuture<'static, BackendResult<()>>;
+
+    /// Stops an ongoing verification and releases the device.
+    fn verify_stop(&self, device: OwnedObjectPath) -> BoxFuture<'static, BackendResult<()>>;
+
+    /// Deletes a single print of a user, failing with `UnsupportedOperation`
+    /// when the daemon cannot.
+    fn delete_finger(
+        &self,
+        device: OwnedObjectPath,
+        username: String,
+        finger: String,
+    ) -> BoxFuture<'static, BackendResult<()>>;
+
+    /// Deletes every print of a user.
+    fn delete_all(
+        &self,
+        device: OwnedObjectPath,
+        username: String,
+    ) -> BoxFuture<'static, BackendResult<()>>;
+
+    /// Deletes every print of all given users.
+    fn clear(
+        &self,
+        device: OwnedObjectPath,
+        usernames: Vec<String>,
+    ) -> BoxFuture<'static, BackendResult<()>>;
+}
+
+/// Default backend talking to fprintd (or open-fprintd) over DBus.
+#[derive(Clone)]
+pub struct FprintdBackend {
+    connection: zbus::Connection,
+    // Device proxies reused between calls
+    proxies: Arc<Mutex<HashMap<OwnedObjectPath, DeviceProxy<'static>>>>,
+    // Capabilities of each device, probed on first use
+    capabilities: Arc<Mutex<HashMap<OwnedObjectPath, DeviceCapabilities>>>,
+}
+
+impl FprintdBackend {
+    pub fn new(connection: zbus::Connection) -> Self {
+        Self {
+            connection,
+            proxies: Arc::new(Mutex::new(HashMap::new())),
+            capabilities: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// **Returns** the cached capabilities of a device, probing it on first
+    /// use. When the probe fails fprintd is assumed, and probed again next time.
+    async fn device_capabilities(&self, path: &OwnedObjectPath) -> DeviceCapabilities {
+        let cached = self.capabilities.lock().unwrap().get(path).copied();
+        if let Some(capabilities) = cached {
+            return capabilities;
+        }
+
+        match device_capabilities(&self.connection, path.clone()).await {
+            Ok(capabilities) => {
+                self.capabilities
+                    .lock()
+                    .unwrap()
+                    .insert(path.clone(), capabilities);
+                capabilities
+            }
+            Err(err) => {
+                tracing::warn!(%err, %path, "cannot probe the device, assuming fprintd");
+                DeviceCapabilities::default()
+            }
+        }
+    }
+
+    /// **Returns** the cached proxy for a device, building it on first use
+    async fn device(&self, path: &OwnedObjectPath) -> zbus::Result<DeviceProxy<'static>> {
+        let cached = self.proxies.lock().unwrap().get(path).cloned();
+        if let Some(proxy) = cached {
+            return Ok(proxy);
+        }
+
+        let proxy = DeviceProxy::builder(&self.connection)
+            .path(path.clone())?
+            .build()
+            .await?;
+        self.proxies
+            .lock()
+            .unwrap()
+            .insert(path.clone(), proxy.clone());
+        Ok(proxy)
+    }
+}
+
+impl FingerprintBackend for FprintdBackend {
+    fn reset(&self) {
+        self.proxies.lock().unwrap().clear();
+        // The daemon may have been replaced by another implementation.
+        self.capabilities.lock().unwrap().clear();
+    }
+
+    fn find_device(&self) -> BoxFuture<'static, BackendResult<OwnedObjectPath>> {
+        let this = self.clone();
+        Box::pin(async move {
+            let (path, proxy) = find_device(&this.connection)
+                .await
+                .map_err(AppError::from_fprintd)?;
+            this.proxies.lock().unwrap().insert(path.clone(), proxy);
+            Ok(path)
+        })
+    }
+
+    fn list_devices(&self) -> BoxFuture<'static, BackendResult<Vec<DeviceOption>>> {
+        let this = self.clone();
+        Box::pin(async move {
+            let mut devices = Vec::new();
+            for path in find_all_devices(&this.connection)
+                .await
+                .map_err(AppError::from_fprintd)?
+            {
+                let (name, num_enroll_stages) = match this.device(&path).await {
+                    Ok(proxy) => (
+                        proxy.name().await.unwrap_or_else(|_| path.to_string()),
+                        match proxy.num_enroll_stages().await {
+                            Ok(n) if n > 0 => Some(n as u32),
+                            _ => None,
+                        },
+                    ),
+                    Err(_) => (path.to_string(), None),
+                };
+                devices.push(DeviceOption {
+                    path,
+                    name,
+                    num_enroll_stages,
+                });
+            }
+            Ok(devices)
+        })
+    }
+
+    fn capabilities(
+        &self,
+        device: OwnedObjectPath,
+    ) -> BoxFuture<'static, BackendResult<DeviceCapabilities>> {
+        let this = self.clone();
+        Box::pin(async move { Ok(this.device_capabilities(&device).await) })
+    }
+
+    fn list_enrolled_fingers(
+        &self,
+        device: OwnedObjectPath,
+        username: String,
+    ) -> BoxFuture<'static, BackendResult<Vec<String>>> {
+        let this = self.clone();
+        Box::pin(async move {
+            let proxy = this.device(&device).await.map_err(AppError::from_fprintd)?;
+            list_enrolled_fingers_dbus(proxy, username)
+                .await
+                .map_err(AppError::from_fprintd)
+        })
+    }
+
+    fn enroll(
+        &self,
+        device: OwnedObjectPath,
+        finger: String,
+        username: String,
+        mut output: Sender<Message>,
+    ) -> BoxFuture<'static, BackendResult<()>> {
+        let connection = self.connection.clone();
+        Box::pin(async move {
+            enroll_fingerprint_process(connection, &device, &finger, &username, &mut output)
+                .await
+                .map_err(AppError::from_fprintd)
+        })
+    }
+
+    fn enroll_stop(&self, device: OwnedObjectPath) -> BoxFuture<'static, BackendResult<()>> {
+        let this = self.clone();
+        Box::pin(async move {
+            let proxy = this.device(&device).await.map_err(AppError::from_fprintd)?;
+            let _ = proxy.enroll_stop().await;
+            proxy.release().await.map_err(AppError::from_fprintd)
+        })
+    }
+
+    fn verify(
+        &self,
+        device: OwnedObjectPath,
+        finger: String,
+        username: String,
+        mut output: Sender<Message>,
+    ) -> BoxFuture<'static, BackendResult<()>> {
+        let connection = self.connection.clone();
+        Box::pin(async move {
+            verify_finger_process(connection, &device, &finger, &username, &mut output)
+                .await
+                .map_err(AppError::from_fprintd)
+        })
+    }
+
+    fn verify_stop(&self, device: OwnedObjectPath) -> BoxFuture<'static, BackendResult<()>> {
+        let this = self.clone();
+        Box::pin(async move {
+            let proxy = this.device(&device).await.map_err(AppError::from_fprintd)?;
+            let _ = proxy.verify_stop().await;
+            proxy.release().await.map_err(AppError::from_fprintd)
+        })
+    }
+
+    fn delete_finger(
+        &self,
+        device: OwnedObjectPath,
+        username: String,
+        finger: String,
+    ) -> BoxFuture<'static, BackendResult<()>> {
+        let this = self.clone();
+        Box::pin(async move {
+            if !this.device_capabilities(&device).await.delete_finger {
+                return Err(AppError::UnsupportedOperation);
+            }
+            delete_fingerprint_dbus(&this.connection, device, finger, username)
+                .await
+                .map_err(AppError::from_fprintd)
+        })
+    }
+
+    fn delete_all(
+        &self,
+        device: OwnedObjectPath,
+        username: String,
+    ) -> BoxFuture<'static, BackendResult<()>> {
+        let this = self.clone();
+        Box::pin(async move {
+            let capabilities = this.device_capabilities(&device).await;
+            delete_fingers(&this.connection, device, &capabilities, username)
+                .await
+                .map_err(AppError::from_fprintd)
+        })
+    }
+
+    fn clear(
+        &self,
+        device: OwnedObjectPath,
+        usernames: Vec<String>,
+    ) -> BoxFuture<'static, BackendResult<()>> {
+        let this = self.clone();
+        Box::pin(async move {
+            let capabilities = this.device_capabilities(&device).await;
+            clear_all_fingers_dbus(&this.connection, device, &capabilities, usernames)
+                .await
+                .map_err(AppError::from_fprintd)
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod fake {
+    use super::*;
+    use futures_util::SinkExt;
+
+    #[derive(Debug, Default)]
+    struct FakeState {
+        enrolled: HashMap<String, Vec<String>>,
+        calls: Vec<String>,
+    }
+
+    /// In-memory backend with a single device.
+    ///
+    /// Enrollment always completes after `num_enroll_stages` passed stages
+    /// and verification always matches. With `single_delete` unset it
+    /// behaves like open-fprintd and rejects per-finger deletes.
+    #[derive(Clone)]
+    pub struct FakeBackend {
+        pub device: DeviceOption,
+        pub num_enroll_stages: u32,
+        pub single_delete: bool,
+        state: Arc<Mutex<FakeState>>,
+    }
+
+    impl Default for FakeBackend {
+        fn default() -> Self {
+            Self {
+                device: DeviceOption {
+                    path: OwnedObjectPath::try_from("/net/reactivated/Fprint/Device/0").unwrap(),
+                    name: "Fake Reader".to_string(),
+                    num_enroll_stages: Some(2),
+                },
+                num_enroll_stages: 2,
+                single_delete: true,
+                state: Arc::new(Mutex::new(FakeState::default())),
+            }
+        }
+    }
+
+    impl FakeBackend {
+        pub fn open_fprintd(mut self) -> Self {
+            self.single_delete = false;
+            self
+        }
+
+        pub fn with_enrolled(self, username: &str, fingers: &[&str]) -> Self {
+            self.state.lock().unwrap().enrolled.insert(
+                username.to_string(),
+                fingers.iter().map(|f| f.to_string()).collect(),
+            );
+            self
+        }
+
+        /// **Returns** the prints currently stored for a user
+        pub fn enrolled(&self, username: &str) -> Vec<String> {
+            self.state
+                .lock()
+                .unwrap()
+                .enrolled
+                .get(username)
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        /// **Returns** every backend call received so far, in order
+        pub fn calls(&self) -> Vec<String> {
+            self.state.lock().unwrap().calls.clone()
+        }
+
+        fn record(&self, call: String) {
+            self.state.lock().unwrap().calls.push(call);
+        }
+
+        fn check_device(&self, device: &OwnedObjectPath) -> BackendResult<()> {
+            if *device == self.device.path {
+                Ok(())
+            } else {
+                Err(AppError::DeviceNotFound)
+            }
+        }
+    }
+
+    impl FingerprintBackend for FakeBackend {
+        fn reset(&self) {
+            self.record("Reset".to_string());
+        }
+
+        fn find_device(&self) -> BoxFuture<'static, BackendResult<OwnedObjectPath>> {
+            self.record("FindDevice".to_string());
+            let path = self.device.path.clone();
+            Box::pin(async move { Ok(path) })
+        }
+
+        fn list_devices(&self) -> BoxFuture<'static, BackendResult<Vec<DeviceOption>>> {
+            self.record("ListDevices".to_string());
+            let devices = vec![self.device.clone()];
+            Box::pin(async move { Ok(devices) })
+        }
+
+        fn capabilities(
+            &self,
+            device: OwnedObjectPath,
+        ) -> BoxFuture<'static, BackendResult<DeviceCapabilities>> {
+            let single_delete = self.single_delete;
+            let res = self.check_device(&device).map(|_| DeviceCapabilities {
+                delete_finger: single_delete,
+                delete_fingers2: single_delete,
+                ..DeviceCapabilities::default()
+            });
+            Box::pin(async move { res })
+        }
+
+        fn list_enrolled_fingers(
+            &self,
+            device: OwnedObjectPath,
+            username: String,
+        ) -> BoxFuture<'static, BackendResult<Vec<String>>> {
+            self.record(format!("ListEnrolledFingers({username})"));
+            let res = self.check_device(&device).and_then(|_| {
+                let fingers = self.enrolled(&username);
+                if fingers.is_empty() {
+                    Err(AppError::NoEnrolledPrints)
+                } else {
+                    Ok(fingers)
+                }
+            });
+            Box::pin(async move { res })
+        }
+
+        fn enroll(
+            &self,
+            device: OwnedObjectPath,
+            finger: String,
+            username: String,
+            mut output: Sender<Message>,
+        ) -> BoxFuture<'static, BackendResult<()>> {
+            self.record(format!("Enroll({username}, {finger})"));
+            let this = self.clone();
+            Box::pin(async move {
+                this.check_device(&device)?;
+                let _ = output
+                    .send(Message::EnrollStart(Some(this.num_enroll_stages)))
+                    .await;
+                for _ in 0..this.num_enroll_stages {
+                    let _ = output
+                        .send(Message::EnrollStatus(
+                            "enroll-stage-passed".to_string(),
+                            false,
+                        ))
+                        .await;
+                }
+                {
+                    let mut state = this.state.lock().unwrap();
+                    let fingers = state.enrolled.entry(username).or_default();
+                    if !fingers.contains(&finger) {
+                        fingers.push(finger);
+                    }
+                }
+                let _ = output
+                    .send(Message::EnrollStatus("enroll-completed".to_string(), true))
+                    .await;
+                Ok(())
+            })
+        }
+
+        fn enroll_stop(&self, device: OwnedObjectPath) -> BoxFuture<'static, BackendResult<()>> {
+            self.record("EnrollStop".to_string());
+            let res = self.check_device(&device);
+            Box::pin(async move { res })
+        }
+
+        fn verify(
+            &self,
+            device: OwnedObjectPath,
+            finger: String,
+            username: String,
+            mut output: Sender<Message>,
+        ) -> BoxFuture<'static, BackendResult<()>> {
+            self.record(format!("Verify({username}, {finger})"));
+            let res = self.check_device(&device).and_then(|_| {
+                let enrolled = self.enrolled(&username);
+                if enrolled.contains(&finger) || (finger == "any" && !enrolled.is_empty()) {
+                    Ok(())
+                } else {
+                    Err(AppError::NoEnrolledPrints)
+                }
+            });
+            Box::pin(async move {
+                res?;
+                let _ = output
+                    .send(Message::VerifyStatus("verify-match".to_string(), true))
+                    .await;
+                Ok(())
+            })
+        }
+
+        fn verify_stop(&self, device: OwnedObjectPath) -> BoxFuture<'static, BackendResult<()>> {
+            self.record("VerifyStop".to_string());
+            let res = self.check_device(&device);
+            Box::pin(async move { res })
+        }
+
+        fn delete_finger(
+            &self,
+            device: OwnedObjectPath,
+            username: String,
+            finger: String,
+        ) -> BoxFuture<'static, BackendResult<()>> {
+            self.record(format!("DeleteFinger({username}, {finger})"));
+            let res = self.check_device(&device).and_then(|_| {
+                if !self.single_delete {
+                    return Err(AppError::UnsupportedOperation);
+                }
+                let mut state = self.state.lock().unwrap();
+                let fingers = state.enrolled.entry(username).or_default();
+                let before = fingers.len();
+                fingers.retain(|f| *f != finger);
+                if fingers.len() == before {
+                    Err(AppError::NoEnrolledPrints)
+                } else {
+                    Ok(())
+                }
+            });
+            Box::pin(async move { res })
+        }
+
+        fn delete_all(
+            &self,
+            device: OwnedObjectPath,
+            username: String,
+        ) -> BoxFuture<'static, BackendResult<()>> {
+            self.record(format!("DeleteAll({username})"));
+            let res = self.check_device(&device).map(|_| {
+                self.state.lock().unwrap().enrolled.remove(&username);
+            });
+            Box::pin(async move { res })
+        }
+
+        fn clear(
+            &self,
+            device: OwnedObjectPath,
+            usernames: Vec<String>,
+        ) -> BoxFuture<'static, BackendResult<()>> {
+            self.record(format!("Clear({})", usernames.join(", ")));
+            let res = self.check_device(&device).map(|_| {
+                let mut state = self.state.lock().unwrap();
+                for username in &usernames {
+                    state.enrolled.remove(username);
+                }
+            });
+            Box::pin(async move { res })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fprint_mock::{Flavor, MockConfig, MockFprintd};
+    use cosmic::iced::futures::channel::mpsc;
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_fprintd_backend_lists_devices() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        let backend = FprintdBackend::new(mock.connection.clone());
+
+        assert_eq!(backend.find_device().await.unwrap(), mock.device_path);
+        let devices = backend.list_devices().await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].path, mock.device_path);
+        assert_eq!(devices[0].name, "Mock Fingerprint Reader");
+        assert_eq!(devices[0].num_enroll_stages, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_fprintd_backend_enroll_and_delete() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        let backend = FprintdBackend::new(mock.connection.clone());
+        let (output, receiver) = mpsc::channel(100);
+
+        backend
+            .enroll(
+                mock.device_path.clone(),
+                "left-thumb".to_string(),
+                "alice".to_string(),
+                output,
+            )
+            .await
+            .unwrap();
+        let messages: Vec<Message> = receiver.collect().await;
+        assert!(matches!(
+            messages.last(),
+            Some(Message::EnrollStatus(result, true)) if result == "enroll-completed"
+        ));
+
+        let fingers = backend
+            .list_enrolled_fingers(mock.device_path.clone(), "alice".to_string())
+            .await
+            .unwrap();
+        assert_eq!(fingers, vec!["left-thumb"]);
+
+        backend
+            .delete_finger(
+                mock.device_path.clone(),
+                "alice".to_string(),
+                "left-thumb".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            backend
+                .list_enrolled_fingers(mock.device_path.clone(), "alice".to_string())
+                .await,
+            Err(AppError::NoEnrolledPrints)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fprintd_backend_maps_unsupported() {
+        let config = MockConfig::default()
+            .flavor(Flavor::OpenFprintd)
+            .enrolled("alice", &["left-thumb"]);
+        let mock = MockFprintd::start(config).await.unwrap();
+        let backend = FprintdBackend::new(mock.connection.clone());
+
+        assert_eq!(
+            backend
+                .delete_finger(
+                    mock.device_path.clone(),
+                    "alice".to_string(),
+                    "left-thumb".to_string(),
+                )
+                .await,
+            Err(AppError::UnsupportedOperation)
+        );
+        // Rejected from the probed capabilities, without claiming the device.
+        assert!(mock.calls().is_empty());
+        assert_eq!(mock.enrolled("alice"), vec!["left-thumb"]);
+    }
+
+    #[tokio::test]
+    async fn test_fprintd_backend_assumes_fprintd_when_probe_fails() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        let backend = FprintdBackend::new(mock.connection.clone());
+        let missing = OwnedObjectPath::try_from("/net/reactivated/Fprint/Device/9").unwrap();
+
+        assert_eq!(
+            backend.capabilities(missing).await,
+            Ok(DeviceCapabilities::default())
+        );
+        // Nothing is cached, so the device is probed again later.
+        assert!(backend.capabilities.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fprintd_backend_reset_drops_proxies() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        let backend = FprintdBackend::new(mock.connection.clone());
+
+        backend.find_device().await.unwrap();
+        backend
+            .capabilities(mock.device_path.clone())
+            .await
+            .unwrap();
+        assert_eq!(backend.proxies.lock().unwrap().len(), 1);
+        assert_eq!(backend.capabilities.lock().unwrap().len(), 1);
+        backend.reset();
+        assert!(backend.proxies.lock().unwrap().is_empty());
+        assert!(backend.capabilities.lock().unwrap().is_empty());
+
+        // Proxies are built anew on the next call.
+        backend
+            .list_enrolled_fingers(mock.device_path.clone(), "alice".to_string())
+            .await
+            .ok();
+        assert_eq!(backend.proxies.lock().unwrap().len(), 1);
+    }
+}
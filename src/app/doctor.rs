@@ -0,0 +1,338 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::path::Path;
+
+use zbus::fdo::DBusProxy;
+use zbus::zvariant::OwnedObjectPath;
+
+use crate::app::fprint::{FPRINT_SERVICE, device_capabilities, find_all_devices};
+use crate::app::pam::{PAM_DIRS, PamService, inspect};
+use crate::app::users::AccountsProxy;
+use crate::fl;
+use crate::fprint_dbus::DeviceProxy;
+
+/// A part of the fingerprint stack, in the order it is checked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckKind {
+    SystemBus,
+    FprintService,
+    Daemon,
+    Devices,
+    AccountsDaemon,
+    Pam,
+}
+
+impl CheckKind {
+    pub fn localized_name(&self) -> String {
+        match self {
+            CheckKind::SystemBus => fl!("doctor-system-bus"),
+            CheckKind::FprintService => fl!("doctor-fprint-service"),
+            CheckKind::Daemon => fl!("doctor-daemon"),
+            CheckKind::Devices => fl!("doctor-devices"),
+            CheckKind::AccountsDaemon => fl!("doctor-accounts-daemon"),
+            CheckKind::Pam => fl!("doctor-pam"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Not run because an earlier check failed
+    Skipped,
+}
+
+impl CheckStatus {
+    pub fn localized_name(&self) -> String {
+        match self {
+            CheckStatus::Pass => fl!("doctor-pass"),
+            CheckStatus::Fail => fl!("doctor-fail"),
+            CheckStatus::Skipped => fl!("doctor-skipped"),
+        }
+    }
+}
+
+/// Result of a single diagnostic check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    pub kind: CheckKind,
+    pub status: CheckStatus,
+    /// What was found
+    pub detail: String,
+    /// How to fix a failure
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(kind: CheckKind, detail: String) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Pass,
+            detail,
+            hint: None,
+        }
+    }
+
+    fn fail(kind: CheckKind, detail: String, hint: String) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Fail,
+            detail,
+            hint: Some(hint),
+        }
+    }
+
+    fn skipped(kind: CheckKind) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Skipped,
+            detail: String::new(),
+            hint: None,
+        }
+    }
+}
+
+/// Checks the whole fingerprint stack on a fresh system bus connection,
+/// so no device claimed by the application is touched.
+///
+/// **Returns** one check per [`CheckKind`], in order
+pub async fn diagnose() -> Vec<Check> {
+    let connection = match zbus::Connection::system().await {
+        Ok(connection) => connection,
+        Err(err) => {
+            return vec![
+                Check::fail(
+                    CheckKind::SystemBus,
+                    err.to_string(),
+                    fl!("doctor-system-bus-hint"),
+                ),
+                Check::skipped(CheckKind::FprintService),
+                Check::skipped(CheckKind::Daemon),
+                Check::skipped(CheckKind::Devices),
+                Check::skipped(CheckKind::AccountsDaemon),
+                check_pam(&PAM_DIRS),
+            ];
+        }
+    };
+
+    let mut checks = vec![
+        Check::pass(CheckKind::SystemBus, fl!("doctor-system-bus-ok")),
+        check_fprint_service(&connection).await,
+    ];
+    if checks[1].status == CheckStatus::Pass {
+        checks.extend(check_fprintd(&connection).await);
+    } else {
+        checks.push(Check::skipped(CheckKind::Daemon));
+        checks.push(Check::skipped(CheckKind::Devices));
+    }
+    checks.push(check_accounts_daemon(&connection).await);
+    checks.push(check_pam(&PAM_DIRS));
+    checks
+}
+
+/// Whether the bus can start fprintd on demand, or it is already running
+async fn check_fprint_service(connection: &zbus::Connection) -> Check {
+    let kind = CheckKind::FprintService;
+    let dbus = match DBusProxy::new(connection).await {
+        Ok(dbus) => dbus,
+        Err(err) => return Check::fail(kind, err.to_string(), fl!("doctor-fprint-service-hint")),
+    };
+
+    let is_fprint = |name: &zbus::names::OwnedBusName| name.as_str() == FPRINT_SERVICE;
+    if dbus
+        .list_activatable_names()
+        .await
+        .is_ok_and(|names| names.iter().any(is_fprint))
+    {
+        Check::pass(kind, fl!("doctor-fprint-service-activatable"))
+    } else if dbus
+        .list_names()
+        .await
+        .is_ok_and(|names| names.iter().any(is_fprint))
+    {
+        Check::pass(kind, fl!("doctor-fprint-service-running"))
+    } else {
+        Check::fail(
+            kind,
+            fl!("doctor-fprint-service-missing"),
+            fl!("doctor-fprint-service-hint"),
+        )
+    }
+}
+
+/// Detects the daemon implementation and lists its devices.
+///
+/// open-fprintd is told apart from fprintd by the missing
+/// `DeleteEnrolledFingers2` in the capabilities of its first device.
+///
+/// **Returns** the [`CheckKind::Daemon`] and [`CheckKind::Devices`] checks
+pub(crate) async fn check_fprintd(connection: &zbus::Connection) -> [Check; 2] {
+    let paths = match find_all_devices(connection).await {
+        Ok(paths) => paths,
+        Err(err) => {
+            return [
+                Check::fail(
+                    CheckKind::Daemon,
+                    err.to_string(),
+                    fl!("doctor-daemon-hint"),
+                ),
+                Check::skipped(CheckKind::Devices),
+            ];
+        }
+    };
+
+    let mut devices = Vec::with_capacity(paths.len());
+    for path in &paths {
+        devices.push(device_proxy(connection, path).await);
+    }
+
+    let capabilities = match paths.first() {
+        Some(path) => device_capabilities(connection, path.clone()).await.ok(),
+        None => None,
+    };
+    let daemon = match capabilities {
+        Some(capabilities) if !capabilities.delete_fingers2 => {
+            Check::pass(CheckKind::Daemon, fl!("doctor-daemon-open-fprintd"))
+        }
+        Some(_) => Check::pass(CheckKind::Daemon, fl!("doctor-daemon-fprintd")),
+        None => Check::pass(CheckKind::Daemon, fl!("doctor-daemon-unknown")),
+    };
+
+    if paths.is_empty() {
+        return [
+            daemon,
+            Check::fail(
+                CheckKind::Devices,
+                fl!("doctor-devices-none"),
+                fl!("doctor-devices-hint"),
+            ),
+        ];
+    }
+
+    let mut found = Vec::with_capacity(paths.len());
+    for (path, device) in paths.iter().zip(&devices) {
+        let (name, stages) = match device {
+            Ok(device) => (
+                device.name().await.unwrap_or_else(|_| path.to_string()),
+                device.num_enroll_stages().await.unwrap_or_default(),
+            ),
+            Err(_) => (path.to_string(), 0),
+        };
+        found.push(fl!("doctor-device", name = name, stages = stages));
+    }
+
+    [
+        daemon,
+        Check::pass(
+            CheckKind::Devices,
+            fl!(
+                "doctor-devices-found",
+                count = paths.len(),
+                devices = found.join(", ")
+            ),
+        ),
+    ]
+}
+
+async fn device_proxy(
+    connection: &zbus::Connection,
+    path: &OwnedObjectPath,
+) -> zbus::Result<DeviceProxy<'static>> {
+    DeviceProxy::builder(connection)
+        .path(path.clone())?
+        .build()
+        .await
+}
+
+async fn check_accounts_daemon(connection: &zbus::Connection) -> Check {
+    let kind = CheckKind::AccountsDaemon;
+    let users = match AccountsProxy::new(connection).await {
+        Ok(accounts) => accounts.list_cached_users().await,
+        Err(err) => Err(err),
+    };
+    match users {
+        Ok(users) => Check::pass(kind, fl!("doctor-accounts-daemon-ok", count = users.len())),
+        Err(err) => Check::fail(kind, err.to_string(), fl!("doctor-accounts-daemon-hint")),
+    }
+}
+
+/// Looks for known PAM services in `dirs` which use `pam_fprintd`
+pub(crate) fn check_pam<P: AsRef<Path>>(dirs: &[P]) -> Check {
+    let services: Vec<String> = inspect(dirs)
+        .iter()
+        .filter_map(PamService::summary)
+        .collect();
+
+    if services.is_empty() {
+        Check::fail(
+            CheckKind::Pam,
+            fl!("doctor-pam-none"),
+            fl!("doctor-pam-hint"),
+        )
+    } else {
+        Check::pass(
+            CheckKind::Pam,
+            fl!("doctor-pam-found", services = services.join(", ")),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fprint_mock::{Flavor, MockConfig, MockFprintd};
+
+    #[tokio::test]
+    async fn test_check_fprintd() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+
+        let [daemon, devices] = check_fprintd(&mock.connection).await;
+        assert_eq!(
+            daemon,
+            Check::pass(CheckKind::Daemon, fl!("doctor-daemon-fprintd"))
+        );
+        assert_eq!(devices.status, CheckStatus::Pass);
+        assert!(devices.detail.contains("Mock Fingerprint Reader"));
+        // Detection introspects the device without calling it.
+        assert!(!mock.is_claimed());
+        assert!(mock.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_open_fprintd() {
+        let mock = MockFprintd::start(MockConfig::default().flavor(Flavor::OpenFprintd))
+            .await
+            .unwrap();
+
+        let [daemon, _] = check_fprintd(&mock.connection).await;
+        assert_eq!(
+            daemon,
+            Check::pass(CheckKind::Daemon, fl!("doctor-daemon-open-fprintd"))
+        );
+    }
+
+    #[test]
+    fn test_check_pam() {
+        let dir = std::env::temp_dir().join(format!("enroll-doctor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("login"), "auth include system-auth\n").unwrap();
+        std::fs::write(dir.join("sudo"), "# auth sufficient pam_fprintd.so\n").unwrap();
+
+        assert_eq!(check_pam(&[&dir]).status, CheckStatus::Fail);
+
+        std::fs::write(
+            dir.join("system-auth"),
+            "auth sufficient pam_fprintd.so\nauth required pam_unix.so\n",
+        )
+        .unwrap();
+        let check = check_pam(&[&dir]);
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(check.detail.contains("login"));
+        assert!(check.detail.contains("system-auth"));
+        assert!(!check.detail.contains("sudo"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(check_pam(&[&dir]).status, CheckStatus::Fail);
+    }
+}
@@ -13,6 +13,8 @@ pub enum AppError {
     Timeout,
     DeviceNotFound,
     UnsupportedOperation,
+    ServiceNotInstalled,
+    ServiceFailed,
     ConnectDbus(String),
     Unknown(String),
 }
@@ -31,11 +33,33 @@ impl AppError {
             AppError::Timeout => fl!("error-timeout"),
             AppError::DeviceNotFound => fl!("error-device-not-found"),
             AppError::UnsupportedOperation => fl!("error-unsupported-operation"),
+            AppError::ServiceNotInstalled => fl!("error-service-not-installed"),
+            AppError::ServiceFailed => fl!("error-service-failed"),
             AppError::ConnectDbus(msg) => fl!("error-connect-dbus", err = msg),
             AppError::Unknown(msg) => msg.clone(),
         }
     }
 
+    /// Converts the error of a call to fprintd, where a service which is
+    /// missing from the bus or cannot be activated is fprintd itself
+    pub fn from_fprintd(err: zbus::Error) -> Self {
+        let name = match &err {
+            zbus::Error::MethodError(name, _, _) => name.as_str(),
+            _ => "",
+        };
+        match name {
+            // Nothing on the bus provides the name.
+            "org.freedesktop.DBus.Error.ServiceUnknown" => AppError::ServiceNotInstalled,
+            // Activating the service failed.
+            name if name.starts_with("org.freedesktop.DBus.Error.Spawn.")
+                || name.starts_with("org.freedesktop.systemd1.") =>
+            {
+                AppError::ServiceFailed
+            }
+            _ => AppError::from(err),
+        }
+    }
+
     /// Creates a new instance with added context before message
     pub fn with_context(self, context: &str) -> Self {
         match self {
@@ -56,11 +80,16 @@ impl From<zbus::Error> for AppError {
                 "net.reactivated.Fprint.Error.ClaimDevice" => AppError::ClaimDevice,
                 "net.reactivated.Fprint.Error.PrintsNotDeleted" => AppError::PrintsNotDeleted,
                 "net.reactivated.Fprint.Error.Timeout" => AppError::Timeout,
-                "net.reactivated.Fprint.Error.DeviceNotFound" => AppError::DeviceNotFound,
+                "net.reactivated.Fprint.Error.DeviceNotFound"
+                | "net.reactivated.Fprint.Error.NoSuchDevice" => AppError::DeviceNotFound,
                 "org.freedesktop.DBus.Error.UnknownMethod"
                 | "org.freedesktop.DBus.Error.UnknownInterface"
                 | "org.freedesktop.DBus.Error.UnknownObject"
                 | "org.freedesktop.DBus.Error.UnknownProperty" => AppError::UnsupportedOperation,
+                "org.freedesktop.DBus.Error.AccessDenied"
+                | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired" => {
+                    AppError::PermissionDenied
+                }
                 _ => AppError::Unknown(err.to_string()),
             }
         } else {
@@ -124,6 +153,14 @@ mod tests {
                 "org.freedesktop.DBus.Error.UnknownInterface",
                 AppError::UnsupportedOperation,
             ),
+            (
+                "net.reactivated.Fprint.Error.NoSuchDevice",
+                AppError::DeviceNotFound,
+            ),
+            (
+                "org.freedesktop.DBus.Error.AccessDenied",
+                AppError::PermissionDenied,
+            ),
         ];
 
         for (error_str, expected) in test_cases {
@@ -133,6 +170,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_fprintd_service_errors() {
+        let test_cases = [
+            (
+                "org.freedesktop.DBus.Error.ServiceUnknown",
+                AppError::ServiceNotInstalled,
+            ),
+            (
+                "org.freedesktop.DBus.Error.Spawn.ChildExited",
+                AppError::ServiceFailed,
+            ),
+            (
+                "org.freedesktop.systemd1.UnitMasked",
+                AppError::ServiceFailed,
+            ),
+            (
+                "net.reactivated.Fprint.Error.ClaimDevice",
+                AppError::ClaimDevice,
+            ),
+        ];
+
+        for (error_str, expected) in test_cases {
+            let app_err = AppError::from_fprintd(create_method_error(error_str));
+            assert_eq!(app_err, expected, "Failed for error: {}", error_str);
+        }
+
+        // Other services missing from the bus are not fprintd.
+        let app_err = AppError::from(create_method_error(
+            "org.freedesktop.DBus.Error.ServiceUnknown",
+        ));
+        assert!(matches!(app_err, AppError::Unknown(_)));
+    }
+
     #[test]
     fn test_unknown_zbus_error() {
         let error_str = "net.reactivated.Fprint.Error.UnknownOne";
@@ -85,6 +85,15 @@ impl Finger {
             Finger::LeftPinky => "left-little-finger",
         }
     }
+
+    /// Maps a fprintd API name to a finger, also accepting it without the
+    /// `-finger` suffix (e.g. `right-index`)
+    pub fn from_finger_id(id: &str) -> Option<Finger> {
+        Self::all().iter().copied().find(|finger| {
+            let finger_id = finger.as_finger_id();
+            finger_id == id || finger_id.strip_suffix("-finger") == Some(id)
+        })
+    }
 }
 
 impl std::fmt::Display for Finger {
@@ -157,4 +166,20 @@ mod tests {
         assert_eq!(Finger::LeftRing.as_finger_id(), "left-ring-finger");
         assert_eq!(Finger::LeftPinky.as_finger_id(), "left-little-finger");
     }
+    #[test]
+    fn test_finger_from_finger_id() {
+        for finger in Finger::all() {
+            assert_eq!(Finger::from_finger_id(finger.as_finger_id()), Some(*finger));
+        }
+        assert_eq!(
+            Finger::from_finger_id("right-index"),
+            Some(Finger::RightIndex)
+        );
+        assert_eq!(
+            Finger::from_finger_id("left-little"),
+            Some(Finger::LeftPinky)
+        );
+        assert_eq!(Finger::from_finger_id("left-thumb-finger"), None);
+        assert_eq!(Finger::from_finger_id("any"), None);
+    }
 }
@@ -2,9 +2,99 @@
 
 use crate::app::error::AppError;
 use crate::app::message::Message;
+use crate::app::status::{ScanType, Sensor};
 use crate::fprint_dbus::{DeviceProxy, ManagerProxy};
 use futures_util::sink::Sink;
+use futures_util::stream::Stream;
 use futures_util::{SinkExt, StreamExt};
+use zbus::fdo::{PropertiesChangedStream, PropertiesProxy};
+use zbus_xml::Node;
+
+/// Well-known bus name of fprintd and open-fprintd
+pub const FPRINT_SERVICE: &str = "net.reactivated.Fprint";
+/// Interface of the fingerprint reader objects
+const DEVICE_INTERFACE: &str = "net.reactivated.Fprint.Device";
+
+/// What the daemon behind a device object implements. fprintd provides
+/// everything, open-fprintd lacks per-finger deletes, `DeleteEnrolledFingers2`
+/// and the sensor properties.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    /// `DeleteEnrolledFinger`, deleting a single print
+    pub delete_finger: bool,
+    /// `DeleteEnrolledFingers2`, deleting the prints of the claiming user
+    pub delete_fingers2: bool,
+    /// The `num-enroll-stages` property
+    pub num_enroll_stages: bool,
+    /// The `scan-type` property, telling press sensors from swipe sensors
+    pub scan_type: bool,
+    /// The `finger-present` property
+    pub finger_present: bool,
+}
+
+impl Default for DeviceCapabilities {
+    /// Everything fprintd provides, assumed until the device is probed
+    fn default() -> Self {
+        Self {
+            delete_finger: true,
+            delete_fingers2: true,
+            num_enroll_stages: true,
+            scan_type: true,
+            finger_present: true,
+        }
+    }
+}
+
+impl DeviceCapabilities {
+    /// **Returns** the capabilities listed in the introspection data of a
+    /// device object
+    pub fn from_introspection(xml: &str) -> zbus_xml::Result<Self> {
+        let node = Node::try_from(xml)?;
+        let interface = node
+            .interfaces()
+            .iter()
+            .find(|interface| interface.name() == DEVICE_INTERFACE);
+        let has_method = |name: &str| {
+            interface.is_some_and(|interface| {
+                interface
+                    .methods()
+                    .iter()
+                    .any(|method| method.name() == name)
+            })
+        };
+        let has_property = |name: &str| {
+            interface.is_some_and(|interface| {
+                interface
+                    .properties()
+                    .iter()
+                    .any(|property| property.name() == name)
+            })
+        };
+        Ok(Self {
+            delete_finger: has_method("DeleteEnrolledFinger"),
+            delete_fingers2: has_method("DeleteEnrolledFingers2"),
+            num_enroll_stages: has_property("num-enroll-stages"),
+            scan_type: has_property("scan-type"),
+            finger_present: has_property("finger-present"),
+        })
+    }
+}
+
+/// Introspects a device object once to learn what the daemon implements,
+/// instead of trying each call and catching `UnknownMethod`.
+pub async fn device_capabilities(
+    connection: &zbus::Connection,
+    path: zbus::zvariant::OwnedObjectPath,
+) -> zbus::Result<DeviceCapabilities> {
+    let introspectable = zbus::fdo::IntrospectableProxy::builder(connection)
+        .destination(FPRINT_SERVICE)?
+        .path(path)?
+        .build()
+        .await?;
+    let xml = introspectable.introspect().await?;
+    DeviceCapabilities::from_introspection(&xml)
+        .map_err(|err| zbus::Error::Failure(err.to_string()))
+}
 
 /// **Returns** the default fingerprint reader device.
 /// *device:*
@@ -24,6 +114,32 @@ pub async fn find_device(
     Ok((path, device))
 }
 
+/// Follows the owner of [`FPRINT_SERVICE`], sending `FprintdVanished` when
+/// the service exits or crashes and `FprintdAppeared` when it is started or
+/// activated again. fprintd exits on its own once idle, so either is routine.
+pub async fn watch_service<S>(connection: &zbus::Connection, output: &mut S) -> zbus::Result<()>
+where
+    S: Sink<Message> + Unpin + Send,
+{
+    let dbus = zbus::fdo::DBusProxy::new(connection).await?;
+    let mut changes = dbus
+        .receive_name_owner_changed_with_args(&[(0, FPRINT_SERVICE)])
+        .await?;
+
+    while let Some(change) = changes.next().await {
+        let Ok(args) = change.args() else {
+            continue;
+        };
+        if args.old_owner().is_some() && output.send(Message::FprintdVanished).await.is_err() {
+            return Ok(());
+        }
+        if args.new_owner().is_some() && output.send(Message::FprintdAppeared).await.is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
 pub async fn find_all_devices(
     connection: &zbus::Connection,
 ) -> zbus::Result<Vec<zbus::zvariant::OwnedObjectPath>> {
@@ -54,18 +170,25 @@ pub async fn list_enrolled_fingers_dbus(
 /// Returns true when the error means the running fprintd implementation does
 /// not provide the requested method (e.g. open-fprintd only implements the
 /// legacy `DeleteEnrolledFingers`).
-fn is_unsupported(err: &zbus::Error) -> bool {
+pub(crate) fn is_unsupported(err: &zbus::Error) -> bool {
     AppError::from(err.clone()) == AppError::UnsupportedOperation
 }
 
 /// Deletes every enrolled fingerprint for a user, using the modern
-/// `DeleteEnrolledFingers2` when available and falling back to the legacy
-/// `DeleteEnrolledFingers(username)` for daemons like open-fprintd.
-async fn delete_all_fingers(device: &DeviceProxy<'_>, username: &str) -> zbus::Result<()> {
+/// `DeleteEnrolledFingers2` when available and the legacy
+/// `DeleteEnrolledFingers(username)` on daemons like open-fprintd. The
+/// legacy call is also tried when the capabilities were only assumed.
+async fn delete_all_fingers(
+    device: &DeviceProxy<'_>,
+    capabilities: &DeviceCapabilities,
+    username: &str,
+) -> zbus::Result<()> {
+    if !capabilities.delete_fingers2 {
+        return device.delete_enrolled_fingers(username).await;
+    }
     match device.delete_enrolled_fingers2().await {
-        Ok(()) => Ok(()),
         Err(e) if is_unsupported(&e) => device.delete_enrolled_fingers(username).await,
-        Err(e) => Err(e),
+        res => res,
     }
 }
 
@@ -111,13 +234,14 @@ pub async fn delete_fingerprint_dbus(
 pub async fn delete_fingers(
     connection: &zbus::Connection,
     path: zbus::zvariant::OwnedObjectPath,
+    capabilities: &DeviceCapabilities,
     username: String,
 ) -> zbus::Result<()> {
     validate_username(&username)?;
     let device = DeviceProxy::builder(connection).path(path)?.build().await?;
 
     device.claim(&username).await?;
-    let res = delete_all_fingers(&device, &username).await;
+    let res = delete_all_fingers(&device, capabilities, &username).await;
     let rel_res = device.release().await;
     res.and(rel_res)
 }
@@ -133,6 +257,7 @@ pub async fn delete_fingers(
 pub async fn clear_all_fingers_dbus(
     connection: &zbus::Connection,
     path: zbus::zvariant::OwnedObjectPath,
+    capabilities: &DeviceCapabilities,
     usernames: Vec<String>,
 ) -> zbus::Result<()> {
     let device = DeviceProxy::builder(connection).path(path)?.build().await?;
@@ -149,24 +274,33 @@ pub async fn clear_all_fingers_dbus(
             continue;
         }
 
-        match device.list_enrolled_fingers(&username).await {
-            Ok(fingers) => {
-                for finger in fingers {
-                    match device.delete_enrolled_finger(&finger).await {
-                        Ok(()) => {}
-                        Err(e) if is_unsupported(&e) => {
-                            // Legacy daemon has no per-finger delete; remove all at once.
-                            if let Err(e) = device.delete_enrolled_fingers(&username).await {
-                                last_error = Some(e);
+        if !capabilities.delete_finger {
+            // Legacy daemon has no per-finger delete; remove all at once.
+            if let Err(e) = delete_all_fingers(&device, capabilities, &username).await {
+                last_error = Some(e);
+            }
+        } else {
+            match device.list_enrolled_fingers(&username).await {
+                Ok(fingers) => {
+                    for finger in fingers {
+                        match device.delete_enrolled_finger(&finger).await {
+                            Ok(()) => {}
+                            Err(e) if is_unsupported(&e) => {
+                                // The capabilities were assumed, not probed.
+                                if let Err(e) =
+                                    delete_all_fingers(&device, capabilities, &username).await
+                                {
+                                    last_error = Some(e);
+                                }
+                                break;
                             }
-                            break;
+                            Err(e) => last_error = Some(e),
                         }
-                        Err(e) => last_error = Some(e),
                     }
                 }
-            }
-            Err(e) => {
-                last_error = Some(e);
+                Err(e) => {
+                    last_error = Some(e);
+                }
             }
         }
 
@@ -226,14 +360,9 @@ where
     };
     let _ = output.send(Message::EnrollStart(total_stages)).await;
 
-    // Start enrollment
-    if let Err(e) = device.enroll_start(finger_name).await {
-        let _ = device.release().await;
-        return Err(e);
-    }
-
-    // Listen for signals
-    let mut stream = match device.receive_enroll_status().await {
+    // Listen for signals before starting so no early status is missed
+    let sensor = watch_sensor(&connection, path, &device).await;
+    let stream = match device.receive_enroll_status().await {
         Ok(s) => s,
         Err(e) => {
             let _ = device.release().await;
@@ -241,31 +370,15 @@ where
         }
     };
 
-    while let Some(signal) = stream.next().await {
-        let args = signal.args();
-        match args {
-            Ok(args) => {
-                let result: String = args.result;
-                let done: bool = args.done;
-
-                // Map result string to user friendly message if needed, or pass through
-                let _ = output.send(Message::EnrollStatus(result, done)).await;
-
-                if done {
-                    break;
-                }
-            }
-            Err(_) => {
-                let _ = output
-                    .send(Message::OperationError(AppError::Unknown(
-                        "Failed to parse signal".to_string(),
-                    )))
-                    .await;
-                break;
-            }
-        }
+    // Start enrollment
+    if let Err(e) = device.enroll_start(finger_name).await {
+        let _ = device.release().await;
+        return Err(e);
     }
 
+    let statuses = stream.map(|signal| signal.args().map(|args| (args.result, args.done)));
+    follow_scan(statuses, sensor, output, Message::EnrollStatus).await;
+
     // Release device
     let _ = device.release().await;
 
@@ -306,12 +419,8 @@ where
 
     device.claim(username).await?;
 
-    if let Err(e) = device.verify_start(finger).await {
-        let _ = device.release().await;
-        return Err(e);
-    }
-
-    let mut status_stream = match device.receive_verify_status().await {
+    let sensor = watch_sensor(&connection, path, &device).await;
+    let status_stream = match device.receive_verify_status().await {
         Ok(s) => s,
         Err(e) => {
             let _ = device.release().await;
@@ -319,30 +428,111 @@ where
         }
     };
 
-    while let Some(signal) = status_stream.next().await {
-        match signal.args() {
-            Ok(args) => {
-                let result: String = args.result;
-                let done: bool = args.done;
+    if let Err(e) = device.verify_start(finger).await {
+        let _ = device.release().await;
+        return Err(e);
+    }
 
-                let _ = output.send(Message::VerifyStatus(result, done)).await;
+    let statuses = status_stream.map(|signal| signal.args().map(|args| (args.result, args.done)));
+    follow_scan(statuses, sensor, output, Message::VerifyStatus).await;
 
-                if done {
-                    break;
-                }
+    device.release().await
+}
+
+/// Reads the sensor state of a claimed device and subscribes to its changes.
+///
+/// **Returns** `None` when the daemon does not publish the sensor state,
+/// like open-fprintd
+async fn watch_sensor(
+    connection: &zbus::Connection,
+    path: &zbus::zvariant::OwnedObjectPath,
+    device: &DeviceProxy<'_>,
+) -> Option<(Sensor, PropertiesChangedStream)> {
+    let scan_type = device.scan_type().await.ok()?;
+    let sensor = Sensor {
+        scan_type: ScanType::from_property(&scan_type),
+        finger_present: device.finger_present().await.unwrap_or_default(),
+        finger_needed: device.finger_needed().await.unwrap_or_default(),
+    };
+    let properties = PropertiesProxy::builder(connection)
+        .destination(FPRINT_SERVICE)
+        .ok()?
+        .path(path)
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+    let changes = properties.receive_properties_changed().await.ok()?;
+    Some((sensor, changes))
+}
+
+/// Sends a message made by `status` for each status signal of a running scan
+/// until one is done. With `sensor`, its state is sent first and again
+/// whenever `finger-present` or `finger-needed` changes, ahead of any status
+/// which came after the change.
+async fn follow_scan<S>(
+    mut statuses: impl Stream<Item = zbus::Result<(String, bool)>> + Unpin,
+    sensor: Option<(Sensor, PropertiesChangedStream)>,
+    output: &mut S,
+    status: fn(String, bool) -> Message,
+) where
+    S: Sink<Message> + Unpin + Send,
+{
+    let (mut state, mut changes) = match sensor {
+        Some((state, changes)) => {
+            let _ = output.send(Message::SensorChanged(state)).await;
+            (state, Some(changes))
+        }
+        None => (Sensor::default(), None),
+    };
+
+    loop {
+        let change = async {
+            match &mut changes {
+                Some(changes) => changes.next().await,
+                None => std::future::pending().await,
             }
-            Err(_e) => {
-                let _ = output
-                    .send(Message::OperationError(AppError::Unknown(
-                        "Failed to parse signal".to_string(),
-                    )))
-                    .await;
-                break;
+        };
+        tokio::select! {
+            biased;
+            Some(signal) = change => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                if args.interface_name().as_str() != DEVICE_INTERFACE {
+                    continue;
+                }
+                let previous = state;
+                for (name, value) in args.changed_properties() {
+                    match (*name, value.downcast_ref::<bool>()) {
+                        ("finger-present", Ok(present)) => state.finger_present = present,
+                        ("finger-needed", Ok(needed)) => state.finger_needed = needed,
+                        _ => {}
+                    }
+                }
+                if state != previous {
+                    let _ = output.send(Message::SensorChanged(state)).await;
+                }
             }
+            next = statuses.next() => match next {
+                Some(Ok((result, done))) => {
+                    let _ = output.send(status(result, done)).await;
+                    if done {
+                        break;
+                    }
+                }
+                Some(Err(_)) => {
+                    let _ = output
+                        .send(Message::OperationError(AppError::Unknown(
+                            "Failed to parse signal".to_string(),
+                        )))
+                        .await;
+                    break;
+                }
+                None => break,
+            },
         }
     }
-
-    device.release().await
 }
 
 fn validate_username(username: &str) -> zbus::Result<()> {
@@ -420,4 +610,541 @@ mod tests {
             "net.reactivated.Fprint.Error.PermissionDenied"
         )));
     }
+
+    use crate::fprint_mock::{Flavor, MockConfig, MockFprintd};
+    use cosmic::iced::futures::channel::mpsc;
+
+    const FINGER: &str = "right-index-finger";
+
+    /// Closes the sender and returns every message sent through it.
+    async fn drain(
+        output: mpsc::Sender<Message>,
+        receiver: mpsc::Receiver<Message>,
+    ) -> Vec<Message> {
+        drop(output);
+        receiver.collect().await
+    }
+
+    #[tokio::test]
+    async fn test_find_device() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+
+        let (path, device) = find_device(&mock.connection).await.unwrap();
+        assert_eq!(path, mock.device_path);
+        assert_eq!(device.name().await.unwrap(), "Mock Fingerprint Reader");
+        assert_eq!(device.num_enroll_stages().await.unwrap(), 3);
+        assert_eq!(
+            find_all_devices(&mock.connection).await.unwrap(),
+            vec![mock.device_path.clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enroll_claims_and_releases() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        let (mut output, receiver) = mpsc::channel(100);
+
+        enroll_fingerprint_process(
+            mock.connection.clone(),
+            &mock.device_path,
+            FINGER,
+            "alice",
+            &mut output,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mock.calls(),
+            vec!["Claim(alice)", "EnrollStart(right-index-finger)", "Release"]
+        );
+        assert!(!mock.is_claimed());
+        assert_eq!(mock.enrolled("alice"), vec![FINGER]);
+
+        let messages = drain(output, receiver).await;
+        assert!(matches!(messages[0], Message::EnrollStart(Some(3))));
+        let statuses: Vec<(String, bool)> = messages
+            .iter()
+            .filter_map(|m| match m {
+                Message::EnrollStatus(result, done) => Some((result.clone(), *done)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("enroll-stage-passed".to_string(), false),
+                ("enroll-stage-passed".to_string(), false),
+                ("enroll-completed".to_string(), true),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enroll_follows_sensor() {
+        let mut config = MockConfig::default()
+            .enroll_script(&[("enroll-stage-passed", false), ("enroll-completed", true)]);
+        config.scan_type = "swipe".to_string();
+        let mock = MockFprintd::start(config).await.unwrap();
+        let (mut output, receiver) = mpsc::channel(100);
+
+        enroll_fingerprint_process(
+            mock.connection.clone(),
+            &mock.device_path,
+            FINGER,
+            "alice",
+            &mut output,
+        )
+        .await
+        .unwrap();
+
+        let sensor = |finger_present, finger_needed| Sensor {
+            scan_type: ScanType::Swipe,
+            finger_present,
+            finger_needed,
+        };
+        let messages = drain(output, receiver).await;
+        let states: Vec<Sensor> = messages
+            .iter()
+            .filter_map(|m| match m {
+                Message::SensorChanged(sensor) => Some(*sensor),
+                _ => None,
+            })
+            .collect();
+        // The state read after claiming comes first, then every change which
+        // came before the final status.
+        assert_eq!(states.first(), Some(&sensor(false, false)));
+        assert!(states.contains(&sensor(false, true)));
+        assert!(states.contains(&sensor(true, true)));
+        assert!(states.windows(2).all(|pair| pair[0] != pair[1]));
+        assert!(matches!(
+            messages.last(),
+            Some(Message::EnrollStatus(result, true)) if result == "enroll-completed"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_without_sensor_on_open_fprintd() {
+        let config = MockConfig::default()
+            .flavor(Flavor::OpenFprintd)
+            .enrolled("alice", &[FINGER]);
+        let mock = MockFprintd::start(config).await.unwrap();
+        let (mut output, receiver) = mpsc::channel(100);
+
+        verify_finger_process(
+            mock.connection.clone(),
+            &mock.device_path,
+            FINGER,
+            "alice",
+            &mut output,
+        )
+        .await
+        .unwrap();
+
+        let messages = drain(output, receiver).await;
+        assert!(
+            !messages
+                .iter()
+                .any(|m| matches!(m, Message::SensorChanged(_)))
+        );
+        assert!(matches!(
+            messages.last(),
+            Some(Message::VerifyStatus(result, true)) if result == "verify-match"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_enroll_failed_is_not_stored() {
+        let config = MockConfig::default()
+            .enroll_script(&[("enroll-retry-scan", false), ("enroll-failed", true)]);
+        let mock = MockFprintd::start(config).await.unwrap();
+        let (mut output, receiver) = mpsc::channel(100);
+
+        enroll_fingerprint_process(
+            mock.connection.clone(),
+            &mock.device_path,
+            FINGER,
+            "alice",
+            &mut output,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(mock.calls().last().map(String::as_str), Some("Release"));
+        assert!(mock.enrolled("alice").is_empty());
+        let messages = drain(output, receiver).await;
+        assert!(matches!(
+            messages.last(),
+            Some(Message::EnrollStatus(result, true)) if result == "enroll-failed"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_enroll_releases_after_failed_start() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        let (mut output, _receiver) = mpsc::channel(100);
+
+        enroll_fingerprint_process(
+            mock.connection.clone(),
+            &mock.device_path,
+            "sixth-finger",
+            "alice",
+            &mut output,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            mock.calls(),
+            vec!["Claim(alice)", "EnrollStart(sixth-finger)", "Release"]
+        );
+        assert!(!mock.is_claimed());
+        assert!(mock.enrolled("alice").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_enroll_does_not_release_foreign_claim() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        mock.claim_elsewhere("bob");
+        let (mut output, receiver) = mpsc::channel(100);
+
+        let err = enroll_fingerprint_process(
+            mock.connection.clone(),
+            &mock.device_path,
+            FINGER,
+            "alice",
+            &mut output,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(AppError::from(err), AppError::AlreadyInUse);
+        assert_eq!(mock.calls(), vec!["Claim(alice)"]);
+        assert!(mock.is_claimed());
+        assert!(drain(output, receiver).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_verify_claims_and_releases() {
+        let config = MockConfig::default()
+            .enrolled("alice", &[FINGER])
+            .verify_script(&[("verify-no-match", false), ("verify-match", true)]);
+        let mock = MockFprintd::start(config).await.unwrap();
+        let (mut output, receiver) = mpsc::channel(100);
+
+        verify_finger_process(
+            mock.connection.clone(),
+            &mock.device_path,
+            FINGER,
+            "alice",
+            &mut output,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mock.calls(),
+            vec!["Claim(alice)", "VerifyStart(right-index-finger)", "Release"]
+        );
+        let messages = drain(output, receiver).await;
+        assert!(matches!(
+            messages.last(),
+            Some(Message::VerifyStatus(result, true)) if result == "verify-match"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_without_prints_releases() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        let (mut output, _receiver) = mpsc::channel(100);
+
+        let err = verify_finger_process(
+            mock.connection.clone(),
+            &mock.device_path,
+            FINGER,
+            "alice",
+            &mut output,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(AppError::from(err), AppError::NoEnrolledPrints);
+        assert_eq!(
+            mock.calls(),
+            vec!["Claim(alice)", "VerifyStart(right-index-finger)", "Release"]
+        );
+        assert!(!mock.is_claimed());
+    }
+
+    #[tokio::test]
+    async fn test_list_enrolled_fingers() {
+        let config = MockConfig::default().enrolled("alice", &[FINGER, "left-thumb"]);
+        let mock = MockFprintd::start(config).await.unwrap();
+        let (_, device) = find_device(&mock.connection).await.unwrap();
+
+        let fingers = list_enrolled_fingers_dbus(device.clone(), "alice".to_string())
+            .await
+            .unwrap();
+        assert_eq!(fingers, vec![FINGER, "left-thumb"]);
+
+        let err = list_enrolled_fingers_dbus(device, "bob".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(AppError::from(err), AppError::NoEnrolledPrints);
+    }
+
+    #[test]
+    fn test_capabilities_from_introspection() {
+        let xml = r#"
+            <node>
+              <interface name="org.freedesktop.DBus.Properties">
+                <method name="Get"/>
+              </interface>
+              <interface name = 'net.reactivated.Fprint.Device'>
+                <method name='DeleteEnrolledFinger'>
+                  <arg name='finger_name' type='s' direction='in'/>
+                </method>
+                <property type="i" name="num-enroll-stages" access="read"/>
+                <property type="s" name="scan-type" access="read">
+                  <annotation name="org.example.Note" value="&lt;press&gt; &amp; swipe"/>
+                </property>
+                <signal name="EnrollStatus"><arg name="result" type="s"/></signal>
+              </interface>
+            </node>"#;
+
+        assert_eq!(
+            DeviceCapabilities::from_introspection(xml).unwrap(),
+            DeviceCapabilities {
+                delete_finger: true,
+                delete_fingers2: false,
+                num_enroll_stages: true,
+                scan_type: true,
+                finger_present: false,
+            }
+        );
+        assert!(DeviceCapabilities::from_introspection("<node><interface").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_device_capabilities() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        let capabilities = device_capabilities(&mock.connection, mock.device_path.clone())
+            .await
+            .unwrap();
+        assert_eq!(capabilities, DeviceCapabilities::default());
+
+        let config = MockConfig::default().flavor(Flavor::OpenFprintd);
+        let mock = MockFprintd::start(config).await.unwrap();
+        let capabilities = device_capabilities(&mock.connection, mock.device_path.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            capabilities,
+            DeviceCapabilities {
+                delete_finger: false,
+                delete_fingers2: false,
+                num_enroll_stages: true,
+                scan_type: false,
+                finger_present: false,
+            }
+        );
+        // Introspection is not a device call.
+        assert!(mock.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_fingerprint() {
+        let config = MockConfig::default().enrolled("alice", &[FINGER, "left-thumb"]);
+        let mock = MockFprintd::start(config).await.unwrap();
+
+        delete_fingerprint_dbus(
+            &mock.connection,
+            mock.device_path.clone(),
+            FINGER.to_string(),
+            "alice".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mock.calls(),
+            vec![
+                "Claim(alice)",
+                "DeleteEnrolledFinger(right-index-finger)",
+                "Release"
+            ]
+        );
+        assert_eq!(mock.enrolled("alice"), vec!["left-thumb"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_fingerprint_unsupported_on_open_fprintd() {
+        let config = MockConfig::default()
+            .flavor(Flavor::OpenFprintd)
+            .enrolled("alice", &[FINGER]);
+        let mock = MockFprintd::start(config).await.unwrap();
+
+        let err = delete_fingerprint_dbus(
+            &mock.connection,
+            mock.device_path.clone(),
+            FINGER.to_string(),
+            "alice".to_string(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(is_unsupported(&err));
+        assert_eq!(mock.calls(), vec!["Claim(alice)", "Release"]);
+        assert_eq!(mock.enrolled("alice"), vec![FINGER]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_fingers() {
+        let config = MockConfig::default().enrolled("alice", &[FINGER]);
+        let mock = MockFprintd::start(config).await.unwrap();
+
+        delete_fingers(
+            &mock.connection,
+            mock.device_path.clone(),
+            &DeviceCapabilities::default(),
+            "alice".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mock.calls(),
+            vec!["Claim(alice)", "DeleteEnrolledFingers2", "Release"]
+        );
+        assert!(mock.enrolled("alice").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_fingers_on_open_fprintd() {
+        let config = MockConfig::default()
+            .flavor(Flavor::OpenFprintd)
+            .enrolled("alice", &[FINGER]);
+        let mock = MockFprintd::start(config).await.unwrap();
+        let capabilities = device_capabilities(&mock.connection, mock.device_path.clone())
+            .await
+            .unwrap();
+
+        delete_fingers(
+            &mock.connection,
+            mock.device_path.clone(),
+            &capabilities,
+            "alice".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mock.calls(),
+            vec!["Claim(alice)", "DeleteEnrolledFingers(alice)", "Release"]
+        );
+        assert!(mock.enrolled("alice").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_open_fprintd_without_probed_capabilities() {
+        let config = MockConfig::default()
+            .flavor(Flavor::OpenFprintd)
+            .enrolled("alice", &[FINGER])
+            .enrolled("bob", &[FINGER, "left-thumb"]);
+        let mock = MockFprintd::start(config).await.unwrap();
+        // What the backend assumes when the probe failed
+        let assumed = DeviceCapabilities::default();
+
+        delete_fingers(
+            &mock.connection,
+            mock.device_path.clone(),
+            &assumed,
+            "alice".to_string(),
+        )
+        .await
+        .unwrap();
+        assert!(mock.enrolled("alice").is_empty());
+
+        clear_all_fingers_dbus(
+            &mock.connection,
+            mock.device_path.clone(),
+            &assumed,
+            vec!["bob".to_string()],
+        )
+        .await
+        .unwrap();
+        assert!(mock.enrolled("bob").is_empty());
+        assert!(!mock.is_claimed());
+    }
+
+    #[tokio::test]
+    async fn test_clear_all_fingers() {
+        for flavor in [Flavor::Fprintd, Flavor::OpenFprintd] {
+            let config = MockConfig::default()
+                .flavor(flavor)
+                .enrolled("alice", &[FINGER, "left-thumb"])
+                .enrolled("bob", &["left-thumb"]);
+            let mock = MockFprintd::start(config).await.unwrap();
+            let capabilities = device_capabilities(&mock.connection, mock.device_path.clone())
+                .await
+                .unwrap();
+
+            clear_all_fingers_dbus(
+                &mock.connection,
+                mock.device_path.clone(),
+                &capabilities,
+                vec!["alice".to_string(), "bob".to_string()],
+            )
+            .await
+            .unwrap();
+
+            assert!(mock.enrolled("alice").is_empty(), "{flavor:?}");
+            assert!(mock.enrolled("bob").is_empty(), "{flavor:?}");
+            assert!(!mock.is_claimed(), "{flavor:?}");
+
+            // Every claim must be paired with a release before the next user.
+            let calls = mock.calls();
+            let claims: Vec<usize> = calls
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.starts_with("Claim("))
+                .map(|(i, _)| i)
+                .collect();
+            let releases: Vec<usize> = calls
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| *c == "Release")
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(claims.len(), 2, "{flavor:?}: {calls:?}");
+            assert_eq!(releases.len(), 2, "{flavor:?}: {calls:?}");
+            assert!(
+                claims[0] < releases[0] && releases[0] < claims[1],
+                "{calls:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clear_all_fingers_continues_after_denied_user() {
+        let config = MockConfig::default()
+            .enrolled("alice", &[FINGER])
+            .enrolled("bob", &[FINGER])
+            .deny("alice");
+        let mock = MockFprintd::start(config).await.unwrap();
+
+        let err = clear_all_fingers_dbus(
+            &mock.connection,
+            mock.device_path.clone(),
+            &DeviceCapabilities::default(),
+            vec!["alice".to_string(), "bob".to_string()],
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(AppError::from(err), AppError::PermissionDenied);
+        assert_eq!(mock.enrolled("alice"), vec![FINGER]);
+        assert!(mock.enrolled("bob").is_empty());
+        assert!(!mock.is_claimed());
+    }
 }
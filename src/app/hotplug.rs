@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::collections::BTreeSet;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+use std::time::Duration;
+
+use futures_util::SinkExt;
+use futures_util::sink::Sink;
+use nix::libc;
+use tokio::io::unix::AsyncFd;
+
+use crate::app::message::Message;
+
+/// Netlink multicast group of the uevents sent by the kernel
+const KERNEL_EVENTS: u32 = 1;
+/// Where USB devices are listed, polled when netlink is not available
+const USB_DEVICES: &str = "/sys/bus/usb/devices";
+/// Time for a burst of events to settle, and for fprintd to pick up a new
+/// reader, before the devices are listed again
+const DEBOUNCE: Duration = Duration::from_secs(1);
+/// How often sysfs is polled
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Sends `UsbHotplug` shortly after a USB device is plugged in or removed.
+/// Kernel uevents are used when a netlink socket can be opened, which a
+/// sandbox may forbid, otherwise sysfs is polled.
+pub async fn watch_devices<S>(output: &mut S) -> io::Result<()>
+where
+    S: Sink<Message> + Unpin + Send,
+{
+    match open_uevents() {
+        Ok(socket) => watch_uevents(&socket, output).await,
+        Err(err) => {
+            tracing::info!(%err, "uevents not available, polling {USB_DEVICES}");
+            poll_sysfs(Path::new(USB_DEVICES), output).await
+        }
+    }
+}
+
+async fn watch_uevents<S>(socket: &AsyncFd<OwnedFd>, output: &mut S) -> io::Result<()>
+where
+    S: Sink<Message> + Unpin + Send,
+{
+    let mut buffer = vec![0u8; 8192];
+    loop {
+        let len = receive(socket, &mut buffer).await?;
+        if !is_usb_hotplug(&buffer[..len]) {
+            continue;
+        }
+
+        tokio::time::sleep(DEBOUNCE).await;
+        // Whatever came meanwhile is covered by listing the devices once.
+        while recv(socket.get_ref(), &mut buffer).is_ok() {}
+        if output.send(Message::UsbHotplug).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+async fn poll_sysfs<S>(dir: &Path, output: &mut S) -> io::Result<()>
+where
+    S: Sink<Message> + Unpin + Send,
+{
+    let mut known = usb_devices(dir)?;
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let current = usb_devices(dir)?;
+        if current == known {
+            continue;
+        }
+
+        known = current;
+        tokio::time::sleep(DEBOUNCE).await;
+        if output.send(Message::UsbHotplug).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// **Returns** a non-blocking socket receiving the kernel's uevents
+fn open_uevents() -> io::Result<AsyncFd<OwnedFd>> {
+    // SAFETY: the descriptor is owned as soon as it is created, and bind
+    // reads a zeroed sockaddr_nl of the given size.
+    let fd = unsafe {
+        let fd = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            libc::NETLINK_KOBJECT_UEVENT,
+        );
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+
+        let mut addr: libc::sockaddr_nl = std::mem::zeroed();
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = KERNEL_EVENTS;
+        if libc::bind(
+            fd.as_raw_fd(),
+            (&raw const addr).cast(),
+            size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        ) < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        fd
+    };
+    AsyncFd::new(fd)
+}
+
+/// Waits for the next uevent
+///
+/// **Returns** its length in `buffer`
+async fn receive(socket: &AsyncFd<OwnedFd>, buffer: &mut [u8]) -> io::Result<usize> {
+    loop {
+        let mut guard = socket.readable().await?;
+        if let Ok(res) = guard.try_io(|fd| recv(fd.get_ref(), buffer)) {
+            return res;
+        }
+    }
+}
+
+fn recv(fd: &OwnedFd, buffer: &mut [u8]) -> io::Result<usize> {
+    // SAFETY: recv writes at most `buffer.len()` bytes to the buffer.
+    let len = unsafe { libc::recv(fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len(), 0) };
+    if len < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(len as usize)
+    }
+}
+
+/// Whether a kernel uevent reports a USB device being added or removed.
+/// The event is `action@devpath` followed by `KEY=value` fields, each
+/// ending with a NUL byte. Events for the interfaces of a device are left out.
+fn is_usb_hotplug(event: &[u8]) -> bool {
+    let (mut action, mut subsystem, mut devtype) = (None, None, None);
+    for field in event.split(|byte| *byte == 0) {
+        match std::str::from_utf8(field)
+            .ok()
+            .and_then(|f| f.split_once('='))
+        {
+            Some(("ACTION", value)) => action = Some(value),
+            Some(("SUBSYSTEM", value)) => subsystem = Some(value),
+            Some(("DEVTYPE", value)) => devtype = Some(value),
+            _ => {}
+        }
+    }
+    matches!(action, Some("add" | "remove"))
+        && subsystem == Some("usb")
+        && devtype == Some("usb_device")
+}
+
+/// **Returns** the USB devices listed in `dir`, without their interfaces
+/// (named like `1-2:1.0`)
+fn usb_devices(dir: &Path) -> io::Result<BTreeSet<String>> {
+    Ok(std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.contains(':'))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uevent(fields: &[&str]) -> Vec<u8> {
+        let mut event = Vec::new();
+        for field in fields {
+            event.extend_from_slice(field.as_bytes());
+            event.push(0);
+        }
+        event
+    }
+
+    #[test]
+    fn test_is_usb_hotplug() {
+        let device = |action: &str| {
+            uevent(&[
+                &format!("{action}@/devices/pci0000:00/0000:00:14.0/usb1/1-3"),
+                &format!("ACTION={action}"),
+                "DEVPATH=/devices/pci0000:00/0000:00:14.0/usb1/1-3",
+                "SUBSYSTEM=usb",
+                "DEVTYPE=usb_device",
+                "SEQNUM=4242",
+            ])
+        };
+        assert!(is_usb_hotplug(&device("add")));
+        assert!(is_usb_hotplug(&device("remove")));
+        assert!(!is_usb_hotplug(&device("bind")));
+
+        let interface = uevent(&[
+            "add@/devices/pci0000:00/0000:00:14.0/usb1/1-3/1-3:1.0",
+            "ACTION=add",
+            "SUBSYSTEM=usb",
+            "DEVTYPE=usb_interface",
+        ]);
+        assert!(!is_usb_hotplug(&interface));
+
+        let battery = uevent(&["change@/class/power_supply/BAT0", "ACTION=change"]);
+        assert!(!is_usb_hotplug(&battery));
+        assert!(!is_usb_hotplug(b"\xff\xfe"));
+    }
+
+    #[test]
+    fn test_usb_devices() {
+        let dir = std::env::temp_dir().join(format!("enroll-hotplug-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["usb1", "1-3", "1-3:1.0"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+        }
+
+        let devices = usb_devices(&dir).unwrap();
+        assert_eq!(
+            devices.into_iter().collect::<Vec<_>>(),
+            vec!["1-3".to_string(), "usb1".to_string()]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
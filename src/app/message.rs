@@ -1,15 +1,25 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use crate::app::AppModel;
+use crate::app::audit::{Action, Auditor, Entry, SUCCESS, failure};
+use crate::app::backend::{FingerprintBackend, FprintdBackend};
+use crate::app::doctor::Check;
 use crate::app::error::AppError;
+use crate::app::fprint::DeviceCapabilities;
+use crate::app::overview::{Deletion, Overview};
+use crate::app::pam::PamService;
+use crate::app::pam_setup::Plan;
+use crate::app::polkit::{Access, Authorization, Permissions};
+use crate::app::service::ServiceState;
+use crate::app::state::{Confirmation, OperationState, Removal};
+use crate::app::status::{Sensor, enroll_status_text, verify_status_text};
 use crate::app::tasks::*;
 use crate::app::{
     ContextPage, Finger,
-    users::{UserOption, build_nav},
+    users::{AddUserDialog, UserOption, build_nav},
 };
 use crate::config::{AppTheme, Config};
 use crate::fl;
-use crate::fprint_dbus::DeviceProxy;
 use cosmic::cosmic_config::CosmicConfigEntry;
 use cosmic::{Task, command};
 use std::sync::Arc;
@@ -23,6 +33,7 @@ pub const REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
 pub struct DeviceOption {
     pub path: zbus::zvariant::OwnedObjectPath,
     pub name: String,
+    pub num_enroll_stages: Option<u32>,
 }
 
 #[derive(Debug, Clone)]
@@ -32,36 +43,99 @@ pub enum Message {
     UpdateConfig(Config),
     LaunchUrl(String),
     Delete,
+    DeleteAll,
     Register,
     ConnectionReady(zbus::Connection),
-    DeviceFound(Option<(zbus::zvariant::OwnedObjectPath, DeviceProxy<'static>)>),
+    DeviceFound(Option<zbus::zvariant::OwnedObjectPath>),
+    CapabilitiesProbed(zbus::zvariant::OwnedObjectPath, DeviceCapabilities),
     UpdateDevices(Vec<DeviceOption>),
+    FprintdVanished,
+    FprintdAppeared,
+    UsbHotplug,
+    DevicesChanged(Result<Vec<DeviceOption>, AppError>),
+    ServiceChecked(ServiceState),
+    StartService,
+    ServiceStarted(Result<(), AppError>),
     OperationError(AppError),
     EnrollStart(Option<u32>),
     EnrollStatus(String, bool),
-    EnrollStop,
+    SensorChanged(Sensor),
+    Cancel,
     DeleteComplete(bool),
     DeleteSingleUnsupported,
     ConfirmDeleteAll,
-    CancelDeleteAll,
     ClearDevice,
-    CancelClear,
+    ClearPhraseInput(String),
+    ClearAuthorized(Authorization),
     ClearComplete(Result<(), AppError>),
     CloseApplication,
     EnrolledFingers(Vec<String>),
     FingerSelected(Finger),
     VerifyFinger,
     VerifyStatus(String, bool),
-    VerifyStop,
     ThemeChanged(bool),
     ThemeSetting(AppTheme),
     SelectFingerByNumber(u8),
     SelectDevice(usize),
     UsersLoaded(Vec<UserOption>),
+    UserChanged(UserOption),
+    UserRemoved(UserOption),
+    UserPrints(zbus::zvariant::OwnedObjectPath, String, Option<Vec<String>>),
+    ToggleOverview,
+    ToggleOverviewCell(String, Finger),
+    ToggleOverviewRow(String),
+    DeleteSelected,
+    SelectionDeleted(Result<(), AppError>),
+    OpenAddUser,
+    AddUserInput(String),
+    AddUserRemember(bool),
+    AddUser,
+    CloseAddUser,
+    UserFound(String, Option<UserOption>),
+    RememberedUsersFound(Vec<UserOption>),
+    RefreshHistory,
+    HistoryLoaded(Result<Vec<Entry>, String>),
+    PermissionsChecked(Permissions),
+    RunDiagnostics,
+    DiagnosticsReady(Vec<Check>),
+    PamInspected(Vec<PamService>, Plan),
+    EnablePam,
+    PamSetupApplied(Result<(), String>),
 }
 
 // Section for handling of Messages
 impl AppModel {
+    /// Moves to the next operation state, ignoring transitions the current
+    /// state does not allow.
+    ///
+    /// **Returns** whether the state changed
+    pub(crate) fn transition(&mut self, next: OperationState) -> bool {
+        match self.state.transition(next) {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::warn!(from = ?err.from, to = ?err.to, "ignoring invalid state transition");
+                false
+            }
+        }
+    }
+
+    /// Ends the current operation, becoming idle if a device is available
+    pub(crate) fn finish_operation(&mut self) {
+        let next = if self.device_path.is_some() {
+            OperationState::Idle
+        } else {
+            OperationState::Unavailable
+        };
+        self.transition(next);
+    }
+
+    /// Drops a pending confirmation dialog, if any
+    fn dismiss_confirmation(&mut self) {
+        if self.state.confirmation().is_some() {
+            self.transition(OperationState::Idle);
+        }
+    }
+
     /// Closes the application
     ///
     /// **Return** ***Task***::*done*()
@@ -69,12 +143,20 @@ impl AppModel {
         Task::done(cosmic::app::Action::Close).map(cosmic::Action::Cosmic)
     }
 
-    /// Resets clear state
+    /// Cancels whatever the current state allows: a running enroll or verify,
+    /// or an open confirmation dialog
     ///
-    /// **Returns** ***Task***()
-    pub(crate) fn on_cancel_clear(&mut self) -> Task<cosmic::Action<Message>> {
-        self.confirm_clear = false;
-        Task::none()
+    /// **Returns** ***task_enroll_stop***(), ***task_verify_stop***() or ***Task***()
+    pub(crate) fn on_cancel(&mut self) -> Task<cosmic::Action<Message>> {
+        match self.state {
+            OperationState::Enrolling { .. } => self.on_enroll_stop(),
+            OperationState::Verifying { .. } => self.on_verify_stop(),
+            OperationState::AwaitingConfirmation(_) => {
+                self.transition(OperationState::Idle);
+                Task::none()
+            }
+            _ => Task::none(),
+        }
     }
 
     /// After succesfully removal of all prints set status, empties enrolled_fingers
@@ -90,12 +172,17 @@ impl AppModel {
             Ok(_) => {
                 self.status = fl!("device-cleared");
                 self.enrolled_fingers.clear();
+                // Clearing the device removes the prints of every user.
+                let usernames: Vec<String> = self.user_prints.keys().cloned().collect();
+                for username in usernames {
+                    self.set_user_prints(&username, Some(Vec::new()));
+                }
             }
             Err(e) => {
                 self.status = e.localized_message();
             }
         }
-        self.busy = false;
+        self.finish_operation();
         Task::none()
     }
 
@@ -109,22 +196,83 @@ impl AppModel {
 
     /// After DBus connection is established searches queries it for fprintd default device
     ///
-    /// **Returns** ***task_find_device***(*Connection*)
+    /// **Returns** ***task_find_device***(), ***get_devices_task***() and
+    /// ***task_check_permissions***()
     pub fn on_connection_ready(&mut self, conn: zbus::Connection) -> Task<cosmic::Action<Message>> {
+        let backend: Arc<dyn FingerprintBackend> = Arc::new(FprintdBackend::new(conn.clone()));
+        self.backend = Some(backend.clone());
         self.connection = Some(conn.clone());
         self.status = fl!("status-searching-device");
 
-        let find_conn = conn.clone();
-        let devices_conn = conn.clone();
         Task::batch(vec![
-            task_find_device(find_conn),
-            get_devices_task(devices_conn),
+            task_find_device(backend.clone()),
+            get_devices_task(backend),
+            task_check_permissions(conn),
         ])
     }
 
-    /// Toggles the context page
+    /// Stores what polkit lets us do and updates the lock badges of users,
+    /// rebuilding the nav bar when only the current user may be shown now
+    /// or no longer
     ///
-    /// **Returns** ***Task***()
+    /// **Returns** ***Task***(), or the tasks of ***set_users***()
+    pub(crate) fn on_permissions_checked(
+        &mut self,
+        permissions: Permissions,
+    ) -> Task<cosmic::Action<Message>> {
+        let was_restricted = self.is_restricted();
+        self.permissions = permissions;
+        if self.is_restricted() != was_restricted {
+            return self.set_users(self.users.clone());
+        }
+        let usernames: Vec<String> = self
+            .nav
+            .iter()
+            .filter_map(|id| self.nav.data::<UserOption>(id))
+            .map(|user| (*user.username).clone())
+            .collect();
+        for username in usernames {
+            self.update_nav_label(&username);
+        }
+        Task::none()
+    }
+
+    /// Whether only the current user is shown, as polkit or their account
+    /// type keeps them from managing others
+    pub(crate) fn is_restricted(&self) -> bool {
+        self.restricts(&self.users)
+    }
+
+    /// **Returns** whether only the current user is shown given `users`
+    fn restricts(&self, users: &[UserOption]) -> bool {
+        let is_admin = users
+            .iter()
+            .find(|user| user.is_current_user())
+            .is_some_and(|user| user.admin);
+        self.permissions.restricts(is_admin)
+    }
+
+    /// **Returns** polkit's access to enroll or delete prints of `user`
+    pub(crate) fn manage_access(&self, user: &UserOption) -> Access {
+        self.permissions.manage(user.is_current_user())
+    }
+
+    /// **Returns** polkit's access to verify prints of `user`
+    pub(crate) fn verify_access(&self, user: &UserOption) -> Access {
+        self.permissions.verify(user.is_current_user())
+    }
+
+    /// **Returns** polkit's access to clearing the device, which deletes
+    /// the prints of every user
+    pub(crate) fn clear_access(&self) -> Access {
+        self.permissions.manage(false)
+    }
+
+    /// Toggles the context page, reading the PAM configuration again
+    /// whenever help is opened and the journal whenever history is
+    ///
+    /// **Returns** either ***Task***(), ***task_inspect_pam***() or
+    /// ***task_load_history***()
     pub(crate) fn on_context_page_toggle(
         &mut self,
         context_page: ContextPage,
@@ -137,25 +285,151 @@ impl AppModel {
             self.context_page = context_page;
             self.core.window.show_context = true;
         }
-        Task::none()
+
+        if !self.core.window.show_context {
+            return Task::none();
+        }
+        match self.context_page {
+            ContextPage::Help => task_inspect_pam(),
+            ContextPage::History => task_load_history(),
+            _ => Task::none(),
+        }
     }
 
-    /// Localizes the error and stores it on status resetting everything
+    /// Stores the audit entries read back from the journal
     ///
     /// **Returns** ***Task***()
+    pub(crate) fn on_history_loaded(
+        &mut self,
+        history: Result<Vec<Entry>, String>,
+    ) -> Task<cosmic::Action<Message>> {
+        self.history = history;
+        Task::none()
+    }
+
+    /// **Returns** an auditor for changes on the selected device
+    pub(crate) fn auditor(&self) -> Auditor {
+        let path = self
+            .device_path
+            .as_ref()
+            .map(|path| path.as_str())
+            .unwrap_or_default();
+        let name = self
+            .devices
+            .iter()
+            .find(|device| device.path.as_str() == path)
+            .map(|device| device.name.as_str())
+            .unwrap_or_default();
+        Auditor::new(name, path)
+    }
+
+    /// Records the end of an enrollment of the selected user
+    fn audit_enroll(&self, result: &str) {
+        if let (Some(finger), Some(user)) = (self.state.enrolling_finger(), &self.selected_user) {
+            self.auditor().record(
+                Action::Enroll,
+                &user.username,
+                Some(finger.as_str()),
+                result,
+            );
+        }
+    }
+
+    /// Localizes the error and stores it on status resetting everything.
+    /// When the service could not be reached for a device, systemd is asked
+    /// what became of it.
+    ///
+    /// **Returns** ***Task***() or ***task_check_service***()
     pub(crate) fn on_error(&mut self, err: AppError) -> Task<cosmic::Action<Message>> {
+        self.audit_enroll(&failure(&err));
         if err == AppError::NoEnrolledPrints {
             self.enrolled_fingers.clear();
+            self.update_selected_prints();
             self.status = fl!("success");
         } else {
             self.status = err.localized_message();
         }
-        self.busy = false;
-        self.verifying_finger = false;
-        self.enrolling_finger = None;
+        self.finish_operation();
+
+        let service_error = matches!(
+            err,
+            AppError::ServiceNotInstalled
+                | AppError::ServiceFailed
+                | AppError::DeviceNotFound
+                | AppError::Timeout
+                | AppError::Unknown(_)
+        );
+        match &self.connection {
+            Some(conn) if service_error && self.device_path.is_none() => {
+                task_check_service(conn.clone())
+            }
+            _ => Task::none(),
+        }
+    }
+
+    /// Explains a missing device with the state of the service, unless a
+    /// device turned up meanwhile
+    ///
+    /// **Returns** ***Task***()
+    pub(crate) fn on_service_checked(
+        &mut self,
+        state: ServiceState,
+    ) -> Task<cosmic::Action<Message>> {
+        if self.device_path.is_some() {
+            return Task::none();
+        }
+        if let Some(message) = state.localized_message() {
+            self.status = message;
+        }
+        self.service = state;
         Task::none()
     }
 
+    /// Asks systemd to start the failed service
+    ///
+    /// **Returns** ***task_start_service***() or ***Task***()
+    pub(crate) fn on_start_service(&mut self) -> Task<cosmic::Action<Message>> {
+        let (ServiceState::Failed(unit), Some(conn)) = (self.service.clone(), &self.connection)
+        else {
+            return Task::none();
+        };
+        let task = task_start_service(conn.clone(), unit.clone());
+        self.service = ServiceState::Starting(unit);
+        self.status = self.service.localized_message().unwrap_or_default();
+        task
+    }
+
+    /// Looks for the device again once the service started, otherwise
+    /// offers to start it again
+    ///
+    /// **Returns** ***task_find_device***() and ***get_devices_task***(), or ***Task***()
+    pub(crate) fn on_service_started(
+        &mut self,
+        res: Result<(), AppError>,
+    ) -> Task<cosmic::Action<Message>> {
+        let ServiceState::Starting(unit) = self.service.clone() else {
+            return Task::none();
+        };
+        match res {
+            Ok(()) => {
+                self.service = ServiceState::Available;
+                self.status = fl!("status-searching-device");
+                match &self.backend {
+                    Some(backend) => Task::batch(vec![
+                        task_find_device(backend.clone()),
+                        get_devices_task(backend.clone()),
+                    ]),
+                    None => Task::none(),
+                }
+            }
+            Err(err) => {
+                self.service = ServiceState::Failed(unit);
+                self.status = err.localized_message();
+                Task::none()
+            }
+        }
+    }
+
     /// Stores the results of list_fingers_task
     ///
     /// **Returns** ***Task***()
@@ -164,6 +438,7 @@ impl AppModel {
         fingers: Vec<String>,
     ) -> Task<cosmic::Action<Message>> {
         self.enrolled_fingers = fingers;
+        self.update_selected_prints();
         Task::none()
     }
 
@@ -172,10 +447,10 @@ impl AppModel {
     ///
     /// **Returns** ***Task***()
     pub(crate) fn on_finger_selected(&mut self, finger: Finger) -> Task<cosmic::Action<Message>> {
-        if self.busy {
+        if self.state.is_busy() {
             return Task::none();
         }
-        self.confirm_clear = false;
+        self.dismiss_confirmation();
         self.selected_finger = finger;
         Task::none()
     }
@@ -191,48 +466,158 @@ impl AppModel {
         Task::none()
     }
 
+    /// Lists the devices again after a USB device was plugged in or removed
+    ///
+    /// **Returns** ***task_list_changed_devices***() or ***Task***()
+    pub(crate) fn on_usb_hotplug(&mut self) -> Task<cosmic::Action<Message>> {
+        match &self.backend {
+            Some(backend) => task_list_changed_devices(backend.clone()),
+            None => Task::none(),
+        }
+    }
+
+    /// Stores the devices listed after a hotplug, falling back to the default
+    /// device when the selected one is gone. A running operation is left to
+    /// learn about losing its device from fprintd.
+    ///
+    /// **Returns** ***task_find_device***(), the tasks of ***on_device_found***() or ***Task***()
+    pub(crate) fn on_devices_changed(
+        &mut self,
+        res: Result<Vec<DeviceOption>, AppError>,
+    ) -> Task<cosmic::Action<Message>> {
+        self.devices = match res {
+            Ok(devices) => devices,
+            Err(err) => {
+                tracing::warn!(?err, "cannot list devices after a hotplug");
+                return Task::none();
+            }
+        };
+        if self.state.is_busy() && self.state != OperationState::Unavailable {
+            return Task::none();
+        }
+
+        let selected = self
+            .device_path
+            .as_deref()
+            .is_some_and(|path| self.devices.iter().any(|device| device.path == *path));
+        if selected {
+            Task::none()
+        } else if self.devices.is_empty() {
+            self.on_device_found(None)
+        } else if let Some(backend) = &self.backend {
+            task_find_device(backend.clone())
+        } else {
+            Task::none()
+        }
+    }
+
     /// Requests users enrolled prints
     ///
     /// **Returns** either ***Task***::**none**() or ***list_fingers_task***()
     pub(crate) fn on_device_found(
         &mut self,
-        device_info: Option<(zbus::zvariant::OwnedObjectPath, DeviceProxy<'static>)>,
+        device_path: Option<zbus::zvariant::OwnedObjectPath>,
     ) -> Task<cosmic::Action<Message>> {
-        if let Some((path, proxy)) = device_info {
+        if let Some(path) = device_path {
+            let probe = match &self.backend {
+                Some(backend) => task_probe_capabilities(backend.clone(), path.clone()),
+                None => Task::none(),
+            };
             self.device_path = Some(Arc::new(path));
-            self.device_proxy = Some(proxy);
+            self.capabilities = DeviceCapabilities::default();
+            self.service = ServiceState::Available;
             self.status = fl!("status-device-found");
-            self.busy = false;
+            self.transition(OperationState::Idle);
+            self.clear_user_prints();
 
             if self.selected_user.is_some() {
-                self.list_fingers_task()
+                Task::batch(vec![
+                    self.list_fingers_task(),
+                    self.list_user_prints_task(),
+                    probe,
+                ])
             } else {
-                Task::none()
+                Task::batch(vec![self.list_user_prints_task(), probe])
             }
         } else {
             self.device_path = None;
-            self.device_proxy = None;
+            self.capabilities = DeviceCapabilities::default();
             self.status = fl!("status-no-device-found");
-            self.busy = true;
+            self.transition(OperationState::Unavailable);
             Task::none()
         }
     }
 
+    /// Keeps what the daemon implements for the selected device, so that
+    /// unsupported actions are not offered. Probes of a device selected
+    /// before are dropped.
+    ///
+    /// **Returns** ***Task***()
+    pub(crate) fn on_capabilities_probed(
+        &mut self,
+        path: zbus::zvariant::OwnedObjectPath,
+        capabilities: DeviceCapabilities,
+    ) -> Task<cosmic::Action<Message>> {
+        if self.device_path.as_deref() == Some(&path) {
+            self.capabilities = capabilities;
+        }
+        Task::none()
+    }
+
+    /// Drops the device proxies of the fprintd instance which left the bus.
+    /// Enroll and verify cannot outlive it and end with a message, otherwise
+    /// the service merely exited while idle.
+    ///
+    /// **Returns** ***Task***()
+    pub(crate) fn on_fprintd_vanished(&mut self) -> Task<cosmic::Action<Message>> {
+        if let Some(backend) = &self.backend {
+            backend.reset();
+        }
+        if self.state.enrolling_finger().is_some() || self.state.verifying_finger().is_some() {
+            info!("fprintd left the bus during an operation");
+            self.audit_enroll("fprintd-vanished");
+            self.status = fl!("fprintd-vanished");
+            self.finish_operation();
+        }
+        Task::none()
+    }
+
+    /// Looks for the devices again once fprintd is (re)started, keeping the
+    /// selected device if it is still there. A running operation started the
+    /// service itself and is left alone.
+    ///
+    /// **Returns** ***get_devices_task***() and ***task_restore_device***(), or ***Task***()
+    pub(crate) fn on_fprintd_appeared(&mut self) -> Task<cosmic::Action<Message>> {
+        let Some(backend) = self.backend.clone() else {
+            return Task::none();
+        };
+        if self.state.is_busy() && self.state != OperationState::Unavailable {
+            return Task::none();
+        }
+        let previous = self.device_path.as_deref().cloned();
+        Task::batch(vec![
+            get_devices_task(backend.clone()),
+            task_restore_device(backend, previous),
+        ])
+    }
+
     /// Called to request verification of the selected print
     ///
     /// **Returns** ***Task***()
     pub(crate) fn on_verify_finger(&mut self) -> Task<cosmic::Action<Message>> {
-        if self.busy {
+        if self.state.is_busy() {
             return Task::none();
         }
         if self
             .enrolled_fingers
             .iter()
             .any(|ef| ef == self.selected_finger.as_finger_id())
+            && self.transition(OperationState::Verifying {
+                finger: self.selected_finger,
+            })
         {
-            self.busy = true;
-            self.verifying_finger = true;
             self.status = fl!("status-starting-verification");
+            self.sensor = None;
         }
         Task::none()
     }
@@ -245,35 +630,32 @@ impl AppModel {
         status: String,
         done: bool,
     ) -> Task<cosmic::Action<Message>> {
-        // Here you could map verify-* strings to localized messages
-        // Currently we'll fallback to showing the string directly or success message if done
-        let status_msg = match status.as_str() {
-            "verify-match" => fl!("verify-match"),
-            "verify-no-match" => fl!("verify-no-match"),
-            "verify-retry-scan" => fl!("verify-retry-scan"),
-            "verify-swipe-too-short" => fl!("verify-swipe-too-short"),
-            "verify-finger-not-centered" => fl!("verify-finger-not-centered"),
-            "verify-remove-and-retry" => fl!("verify-remove-and-retry"),
-            "verify-too-fast" => fl!("verify-too-fast"),
-            "verify-disconnected" => fl!("verify-disconnected"),
-            "verify-unknown-error" => fl!("verify-unknown-error"),
-            "verify-cancelled" => fl!("verify-cancelled"),
-            _ => status.clone(),
-        };
-        self.status = status_msg;
+        self.status = verify_status_text(&status);
 
         if done {
-            self.busy = false;
-            self.verifying_finger = false;
+            self.finish_operation();
+        }
+        Task::none()
+    }
+
+    /// Keeps the sensor state of the running enroll or verify for the finger
+    /// indicator
+    ///
+    /// **Returns** ***Task***()
+    pub(crate) fn on_sensor_changed(&mut self, sensor: Sensor) -> Task<cosmic::Action<Message>> {
+        if self.state.enrolling_finger().is_some() || self.state.verifying_finger().is_some() {
+            self.sensor = Some(sensor);
         }
         Task::none()
     }
 
     /// Stops any ongoing verification
     pub(crate) fn on_verify_stop(&mut self) -> Task<cosmic::Action<Message>> {
-        if let (Some(path), Some(conn)) = (self.device_path.clone(), self.connection.clone()) {
-            let path = (*path).clone();
-            task_verify_stop(path, conn)
+        if self.state.verifying_finger().is_none() {
+            return Task::none();
+        }
+        if let (Some(path), Some(backend)) = (&self.device_path, &self.backend) {
+            task_verify_stop(backend.clone(), path.as_ref().clone())
         } else {
             Task::none()
         }
@@ -284,7 +666,9 @@ impl AppModel {
     /// **Returns** ***Task***()
     pub(crate) fn on_enroll_start(&mut self, total: Option<u32>) -> Task<cosmic::Action<Message>> {
         self.enroll_total_stages = total;
-        self.enroll_progress = 0;
+        if let Some(finger) = self.state.enrolling_finger().cloned() {
+            self.transition(OperationState::Enrolling { finger, stage: 0 });
+        }
         self.status = fl!("enroll-starting");
         Task::none()
     }
@@ -299,30 +683,25 @@ impl AppModel {
         status: String,
         done: bool,
     ) -> Task<cosmic::Action<Message>> {
-        let status_msg = match status.as_str() {
-            "enroll-stage-passed" => {
-                self.enroll_progress += 1;
-                fl!("enroll-stage-passed")
-            }
-            "enroll-retry-scan" => fl!("enroll-retry-scan"),
-            "enroll-swipe-too-short" => fl!("enroll-swipe-too-short"),
-            "enroll-finger-not-centered" => fl!("enroll-finger-not-centered"),
-            "enroll-remove-and-retry" => fl!("enroll-remove-and-retry"),
-            "enroll-unknown-error" => fl!("enroll-unknown-error"),
-            "enroll-completed" => fl!("enroll-completed"),
-            "enroll-failed" => fl!("enroll-failed"),
-            "enroll-disconnected" => fl!("enroll-disconnected"),
-            "enroll-data-full" => fl!("enroll-data-full"),
-            "enroll-too-fast" => fl!("enroll-too-fast"),
-            "enroll-duplicate" => fl!("enroll-duplicate"),
-            "enroll-cancelled" => fl!("enroll-cancelled"),
-            _ => status.clone(),
-        };
-        self.status = status_msg;
+        if status == "enroll-stage-passed"
+            && let OperationState::Enrolling { finger, stage } = &self.state
+        {
+            let next = OperationState::Enrolling {
+                finger: finger.clone(),
+                stage: stage + 1,
+            };
+            self.transition(next);
+        }
+        self.status = enroll_status_text(&status);
 
         if done {
-            self.busy = false;
-            self.enrolling_finger = None;
+            let result = if status == "enroll-completed" {
+                SUCCESS
+            } else {
+                status.as_str()
+            };
+            self.audit_enroll(result);
+            self.finish_operation();
 
             if status == "enroll-completed" {
                 let cycle = self.on_cycle_finger(1);
@@ -336,59 +715,185 @@ impl AppModel {
     ///
     /// **Returns** either ***Task***() or ***task_enroll_stop***()
     pub(crate) fn on_enroll_stop(&self) -> Task<cosmic::Action<Message>> {
-        if self.enrolling_finger.is_none() {
+        if self.state.enrolling_finger().is_none() {
             return Task::none();
         }
-        if let (Some(path), Some(conn)) = (self.device_path.clone(), self.connection.clone()) {
-            let path = (*path).clone();
-            return task_enroll_stop(path, conn);
+        if let (Some(path), Some(backend)) = (&self.device_path, &self.backend) {
+            return task_enroll_stop(backend.clone(), path.as_ref().clone());
         }
         Task::none()
     }
 
+    /// Names the PAM services a user without prints cannot get into
+    pub(crate) fn fingerprint_only_services(&self) -> Vec<String> {
+        self.pam_services
+            .iter()
+            .filter(|service| service.fingerprint_only())
+            .filter_map(|service| service.name.clone())
+            .collect()
+    }
+
+    /// Whether `removal` takes away a user's last print while PAM depends on
+    /// fingerprints, and the user has not been warned about it yet
+    fn needs_last_print_warning(&self, removal: Removal) -> bool {
+        let last_print = match removal {
+            Removal::Finger => {
+                self.enrolled_fingers.len() == 1
+                    && self.enrolled_fingers[0] == self.selected_finger.as_finger_id()
+            }
+            Removal::AllFingers => !self.enrolled_fingers.is_empty(),
+            // Users whose prints were not listed yet may have some.
+            Removal::Device => self.with_added_users(&self.users).iter().any(|user| {
+                self.user_prints
+                    .get(user.username.as_str())
+                    .is_none_or(|prints| !prints.is_empty())
+            }),
+            Removal::Selection => self.overview_deletions().iter().any(|d| d.all),
+        };
+        last_print
+            && self.state.confirmation() != Some(Confirmation::LastPrint(removal))
+            && !self.fingerprint_only_services().is_empty()
+    }
+
     /// Clears all prints for all users
     ///
     /// **Returns** either ***Task***() or ***task_clear_device***()
     pub(crate) fn on_clear_device(&mut self) -> Task<cosmic::Action<Message>> {
-        if self.busy {
+        if self.is_restricted() {
             return Task::none();
         }
+        match self.state.confirmation() {
+            Some(Confirmation::ClearDevice) if !self.clear_phrase_matches() => {
+                return Task::none();
+            }
+            Some(Confirmation::ClearDevice) if self.needs_last_print_warning(Removal::Device) => {
+                self.transition(OperationState::AwaitingConfirmation(
+                    Confirmation::LastPrint(Removal::Device),
+                ));
+                return Task::none();
+            }
+            Some(Confirmation::ClearDevice | Confirmation::LastPrint(Removal::Device)) => {}
+            _ => {
+                self.clear_phrase.clear();
+                self.transition(OperationState::AwaitingConfirmation(
+                    Confirmation::ClearDevice,
+                ));
+                return Task::none();
+            }
+        }
+
+        if self.transition(OperationState::AuthorizingClear) {
+            self.status = fl!("clear-device-authorizing");
+            return match self.connection.clone() {
+                Some(conn) => task_authorize_clear_device(conn),
+                // Polkit cannot be asked without the system bus.
+                None => Task::done(cosmic::Action::App(Message::ClearAuthorized(
+                    Authorization::Refused,
+                ))),
+            };
+        }
+        Task::none()
+    }
+
+    pub(crate) fn on_clear_phrase_input(
+        &mut self,
+        phrase: String,
+    ) -> Task<cosmic::Action<Message>> {
+        self.clear_phrase = phrase;
+        Task::none()
+    }
 
-        if !self.confirm_clear {
-            self.confirm_clear = true;
+    /// **Returns** whether the phrase confirming to clear the device was typed
+    pub(crate) fn clear_phrase_matches(&self) -> bool {
+        self.clear_phrase.trim() == fl!("clear-device-phrase")
+    }
+
+    /// Clears the device once the user authenticated, otherwise leaves it
+    ///
+    /// **Returns** either ***Task***() or ***task_clear_device***()
+    pub(crate) fn on_clear_authorized(
+        &mut self,
+        authorization: Authorization,
+    ) -> Task<cosmic::Action<Message>> {
+        if self.state != OperationState::AuthorizingClear {
+            return Task::none();
+        }
+        if let Some(error) = authorization.localized_clear_error() {
+            self.status = error;
+            self.finish_operation();
             return Task::none();
         }
 
-        if let (Some(path), Some(conn)) = (&self.device_path, &self.connection) {
+        if let (Some(path), Some(backend)) = (self.device_path.clone(), self.backend.clone())
+            && self.transition(OperationState::Clearing)
+        {
             self.status = fl!("clearing-device");
-            self.busy = true;
-            self.confirm_clear = false;
-            let usernames: Vec<String> = self.users.iter().map(|u| (*u.username).clone()).collect();
-            return task_clear_device(path.as_ref().to_owned(), usernames, conn.clone());
+            return task_clear_device(
+                backend,
+                path.as_ref().to_owned(),
+                self.clear_usernames(),
+                self.auditor(),
+            );
         }
         Task::none()
     }
 
-    /// Deletes the selected finger's print for the current user.
+    /// **Returns** the users whose prints clearing the device deletes,
+    /// including those added by name
+    pub(crate) fn clear_usernames(&self) -> Vec<String> {
+        self.with_added_users(&self.users)
+            .iter()
+            .map(|user| (*user.username).clone())
+            .collect()
+    }
+
+    /// Deletes the selected finger's print for the current user, or asks to
+    /// delete all of their prints when the daemon cannot delete just one.
     ///
     /// **Returns** either ***Task***() or ***task_delete_print***()
     pub(crate) fn on_delete(&mut self) -> Task<cosmic::Action<Message>> {
-        if self.busy {
+        // Without per-finger deletes the view only offers *Delete all*.
+        if self.state.is_busy()
+            || !self.capabilities.delete_finger
+            || !self
+                .enrolled_fingers
+                .iter()
+                .any(|ef| ef == self.selected_finger.as_finger_id())
+        {
+            return Task::none();
+        }
+        if self.needs_last_print_warning(Removal::Finger) {
+            self.transition(OperationState::AwaitingConfirmation(
+                Confirmation::LastPrint(Removal::Finger),
+            ));
             return Task::none();
         }
 
-        if let (Some(path), Some(conn), Some(user)) = (
+        if let (Some(path), Some(backend), Some(user)) = (
             self.device_path.clone(),
-            self.connection.clone(),
+            self.backend.clone(),
             self.selected_user.clone(),
-        ) {
+        ) && self.transition(OperationState::Deleting)
+        {
             self.status = fl!("deleting");
-            self.busy = true;
             let path = (*path).clone();
             let username = (*user.username).clone();
 
             let finger_name = self.selected_finger.as_finger_id().to_string();
-            return task_delete_print(path, username, finger_name, conn);
+            return task_delete_print(backend, path, username, finger_name, self.auditor());
+        }
+        Task::none()
+    }
+
+    /// Called to request deletion of all of the selected user's prints when
+    /// the device cannot delete single prints; asks for confirmation first.
+    ///
+    /// **Returns** ***Task***()
+    pub(crate) fn on_delete_all(&mut self) -> Task<cosmic::Action<Message>> {
+        if !self.state.is_busy() && !self.enrolled_fingers.is_empty() {
+            self.transition(OperationState::AwaitingConfirmation(
+                Confirmation::DeleteAll,
+            ));
         }
         Task::none()
     }
@@ -398,7 +903,7 @@ impl AppModel {
     /// **Returns** ***Task***()
     pub(crate) fn on_delete_complete(&mut self, clear: bool) -> Task<cosmic::Action<Message>> {
         self.status = fl!("deleted");
-        self.busy = false;
+        self.finish_operation();
 
         if clear {
             self.enrolled_fingers.clear();
@@ -406,6 +911,7 @@ impl AppModel {
             self.enrolled_fingers
                 .retain(|f| f.as_str() != self.selected_finger.as_finger_id());
         }
+        self.update_selected_prints();
 
         Task::none()
     }
@@ -415,9 +921,11 @@ impl AppModel {
     ///
     /// **Returns** ***Task***()
     pub(crate) fn on_delete_single_unsupported(&mut self) -> Task<cosmic::Action<Message>> {
-        self.busy = false;
-        self.confirm_delete_all = true;
-        self.status = fl!("delete-all-fallback");
+        if self.transition(OperationState::AwaitingConfirmation(
+            Confirmation::DeleteAll,
+        )) {
+            self.status = fl!("delete-all-fallback");
+        }
         Task::none()
     }
 
@@ -426,26 +934,30 @@ impl AppModel {
     ///
     /// **Returns** ***task_delete_prints***() or ***Task***::**none**()
     pub(crate) fn on_confirm_delete_all(&mut self) -> Task<cosmic::Action<Message>> {
-        self.confirm_delete_all = false;
-        if let (Some(path), Some(conn), Some(user)) = (
+        if !matches!(
+            self.state.confirmation(),
+            Some(Confirmation::DeleteAll | Confirmation::LastPrint(Removal::AllFingers))
+        ) {
+            return Task::none();
+        }
+        if self.needs_last_print_warning(Removal::AllFingers) {
+            self.transition(OperationState::AwaitingConfirmation(
+                Confirmation::LastPrint(Removal::AllFingers),
+            ));
+            return Task::none();
+        }
+        if let (Some(path), Some(backend), Some(user)) = (
             self.device_path.clone(),
-            self.connection.clone(),
+            self.backend.clone(),
             self.selected_user.clone(),
-        ) {
+        ) && self.transition(OperationState::Deleting)
+        {
             self.status = fl!("deleting");
-            self.busy = true;
             let path = (*path).clone();
             let username = (*user.username).clone();
-            return task_delete_prints(path, username, conn);
+            return task_delete_prints(backend, path, username, self.auditor());
         }
-        Task::none()
-    }
-
-    /// User cancelled the single-finger delete fallback dialog.
-    ///
-    /// **Returns** ***Task***()
-    pub(crate) fn on_cancel_delete_all(&mut self) -> Task<cosmic::Action<Message>> {
-        self.confirm_delete_all = false;
+        self.transition(OperationState::Idle);
         Task::none()
     }
 
@@ -462,22 +974,28 @@ impl AppModel {
         }
     }
 
-    /// Sets state as busy and sets which finger is being registered for subscription
+    /// Moves to enrolling the selected finger, which starts the enroll subscription
     ///
     /// **Returns** ***Task***()
     pub(crate) fn on_register(&mut self) -> Task<cosmic::Action<Message>> {
-        if !self.busy && self.device_path.is_some() && self.enrolling_finger.is_none() {
-            self.busy = true;
-            self.enrolling_finger = Some(Arc::new(self.selected_finger.as_finger_id().to_string()));
+        if self.device_path.is_some()
+            && self.transition(OperationState::Enrolling {
+                finger: Arc::new(self.selected_finger.as_finger_id().to_string()),
+                stage: 0,
+            })
+        {
             self.status = fl!("status-starting-enrollment");
+            self.sensor = None;
         }
         Task::none()
     }
 
-    /// Sets the config state as the given on and writes it to disk
+    /// Sets the config state as the given on and writes it to disk. The nav
+    /// bar is rebuilt when system accounts are shown or hidden.
     ///
-    /// **Returns** ***Task***()
+    /// **Returns** ***Task***() or the tasks of ***set_users***()
     pub(crate) fn on_update_config(&mut self, config: Config) -> Task<cosmic::Action<Message>> {
+        let show_all_changed = self.config.show_all_users != config.show_all_users;
         self.config = config.clone();
 
         if let Some(handler) = &self.config_handler
@@ -486,6 +1004,9 @@ impl AppModel {
             tracing::error!("failed to write config: {}", err);
         }
 
+        if show_all_changed {
+            return self.set_users(self.users.clone());
+        }
         Task::none()
     }
 
@@ -529,25 +1050,20 @@ impl AppModel {
     /// **Returns** ***Task***()
     pub(crate) fn on_select_finger_by_number(&mut self, key: u8) -> Task<cosmic::Action<Message>> {
         if let Some(finger) = Finger::from_key(key)
-            && !self.busy
+            && !self.state.is_busy()
         {
-            self.confirm_clear = false;
+            self.dismiss_confirmation();
             self.selected_finger = finger;
         }
         Task::none()
     }
 
     pub(crate) fn on_select_device(&mut self, index: usize) -> Task<cosmic::Action<Message>> {
-        if self.busy {
-            return Task::none();
-        }
-
-        if let Some(device) = self.devices.get(index)
-            && let Some(conn) = &self.connection
+        if let Some(path) = self.devices.get(index).map(|d| d.path.clone())
+            && self.transition(OperationState::SelectingDevice)
         {
             self.status = fl!("status-searching-device");
-            self.busy = true;
-            return task_select_device(conn.clone(), device.path.clone());
+            return task_select_device(path);
         }
         Task::none()
     }
@@ -556,14 +1072,14 @@ impl AppModel {
     ///
     /// **Returns** ***Task***()
     pub(crate) fn on_cycle_finger(&mut self, direction: i8) -> Task<cosmic::Action<Message>> {
-        if self.busy {
+        if self.state.is_busy() {
             return Task::none();
         }
         let fingers = Finger::all();
         if let Some(pos) = fingers.iter().position(|f| *f == self.selected_finger) {
             let len = fingers.len() as i8;
             let next = ((pos as i8 + direction) % len + len) % len;
-            self.confirm_clear = false;
+            self.dismiss_confirmation();
             self.selected_finger = fingers[next as usize];
         }
         Task::none()
@@ -576,11 +1092,1448 @@ impl AppModel {
         &mut self,
         users: Vec<UserOption>,
     ) -> Task<cosmic::Action<Message>> {
-        let (nav, selected_user) = build_nav(&users);
+        let (nav, selected_user) = build_nav(
+            &self.with_added_users(&users),
+            None,
+            self.config.show_all_users,
+            self.restricts(&users),
+            &self.user_prints,
+            &self.permissions,
+        );
         self.nav = nav;
         self.users = users;
         self.selected_user = selected_user;
 
-        Task::batch(vec![self.update_title_task(), self.list_fingers_task()])
+        Task::batch(vec![
+            self.update_title_task(),
+            self.list_fingers_task(),
+            self.list_user_prints_task(),
+        ])
+    }
+
+    /// Accounts-daemon added or changed `user`, who replaces their entry in
+    /// the user list or is appended to it. Everyone else is kept, including
+    /// users found through NSS only.
+    ///
+    /// **Returns** ***set_users***()
+    pub(crate) fn on_user_changed(&mut self, user: UserOption) -> Task<cosmic::Action<Message>> {
+        let mut users = self.users.clone();
+        match users
+            .iter_mut()
+            .find(|listed| listed.is_same_account(&user))
+        {
+            Some(listed) => *listed = user,
+            None => users.push(user),
+        }
+        self.set_users(users)
+    }
+
+    /// Accounts-daemon deleted `user`, who is dropped from the user list.
+    ///
+    /// **Returns** ***set_users***()
+    pub(crate) fn on_user_removed(&mut self, user: UserOption) -> Task<cosmic::Action<Message>> {
+        let mut users = self.users.clone();
+        users.retain(|listed| !listed.is_same_account(&user));
+        self.set_users(users)
+    }
+
+    /// Replaces the user list, keeping the selected user. When that user is
+    /// gone, whatever runs for them is stopped and nothing stays selected.
+    /// A user who is only hidden now falls back to the current user.
+    ///
+    /// **Returns** ***Task***() batched with a stop task when needed
+    pub(crate) fn set_users(&mut self, users: Vec<UserOption>) -> Task<cosmic::Action<Message>> {
+        let Some(previous) = self.selected_user.clone() else {
+            return self.on_users_loaded(users);
+        };
+
+        let all_users = self.with_added_users(&users);
+        let (nav, selected_user) = build_nav(
+            &all_users,
+            Some(&previous.username),
+            self.config.show_all_users,
+            self.restricts(&users),
+            &self.user_prints,
+            &self.permissions,
+        );
+
+        if selected_user.is_some() {
+            self.nav = nav;
+            self.users = users;
+            self.selected_user = selected_user;
+            return Task::batch(vec![self.update_title_task(), self.list_user_prints_task()]);
+        }
+
+        let stop = self.on_cancel();
+        self.dismiss_confirmation();
+        self.enrolled_fingers.clear();
+        if all_users
+            .iter()
+            .any(|user| user.username == previous.username)
+        {
+            return Task::batch(vec![stop, self.on_users_loaded(users)]);
+        }
+
+        self.nav = nav;
+        self.users = users;
+        self.selected_user = None;
+        self.status = fl!("user-removed", user = previous.username.as_str());
+        Task::batch(vec![
+            stop,
+            self.update_title_task(),
+            self.list_user_prints_task(),
+        ])
+    }
+
+    /// Stores the prints a user has on `device` and shows their number on
+    /// the user's nav bar item, updating the selected user's fingers while
+    /// nothing runs. Prints on another device are dropped.
+    ///
+    /// **Returns** ***Task***()
+    pub(crate) fn on_user_prints(
+        &mut self,
+        device: zbus::zvariant::OwnedObjectPath,
+        username: String,
+        fingers: Option<Vec<String>>,
+    ) -> Task<cosmic::Action<Message>> {
+        if self.device_path.as_deref() != Some(&device) {
+            return Task::none();
+        }
+        if let (Some(fingers), Some(user)) = (&fingers, &self.selected_user)
+            && *user.username == username
+            && !self.state.is_busy()
+        {
+            self.enrolled_fingers = fingers.clone();
+        }
+        self.set_user_prints(&username, fingers);
+        Task::none()
+    }
+
+    /// Shows or hides the users × fingers overview, listing everyone's prints
+    /// again when it opens
+    ///
+    /// **Returns** ***list_user_prints_task***() or ***Task***()
+    pub(crate) fn on_toggle_overview(&mut self) -> Task<cosmic::Action<Message>> {
+        if self.overview.take().is_some() {
+            return Task::none();
+        }
+        self.overview = Some(Overview::default());
+        self.list_user_prints_task()
+    }
+
+    pub(crate) fn on_toggle_overview_cell(
+        &mut self,
+        username: String,
+        finger: Finger,
+    ) -> Task<cosmic::Action<Message>> {
+        if !self.state.is_busy()
+            && self.capabilities.delete_finger
+            && let Some(overview) = &mut self.overview
+        {
+            overview.toggle_cell(&username, finger);
+        }
+        Task::none()
+    }
+
+    pub(crate) fn on_toggle_overview_row(
+        &mut self,
+        username: String,
+    ) -> Task<cosmic::Action<Message>> {
+        if !self.state.is_busy()
+            && let Some(overview) = &mut self.overview
+            && let Some(enrolled) = self.user_prints.get(&username)
+        {
+            overview.toggle_row(&username, enrolled);
+        }
+        Task::none()
+    }
+
+    /// **Returns** the prints selected in the overview, in nav bar order
+    fn overview_deletions(&self) -> Vec<Deletion> {
+        let Some(overview) = &self.overview else {
+            return Vec::new();
+        };
+        let usernames: Vec<String> = self
+            .nav
+            .iter()
+            .filter_map(|id| self.nav.data::<UserOption>(id))
+            .map(|user| (*user.username).clone())
+            .collect();
+        overview.deletions(&usernames, &self.user_prints)
+    }
+
+    /// **Returns** polkit's access to deleting the overview selection, the
+    /// strictest among the users it touches
+    pub(crate) fn overview_access(&self) -> Access {
+        let deletions = self.overview_deletions();
+        self.nav
+            .iter()
+            .filter_map(|id| self.nav.data::<UserOption>(id))
+            .filter(|user| deletions.iter().any(|d| d.username == *user.username))
+            .fold(Access::Allowed, |access, user| {
+                access.and(self.manage_access(user))
+            })
+    }
+
+    /// **Returns** the number of prints which the overview selection deletes
+    pub(crate) fn overview_selected_count(&self) -> usize {
+        self.overview_deletions()
+            .iter()
+            .map(|deletion| deletion.fingers.len())
+            .sum()
+    }
+
+    /// Deletes the prints selected in the overview once confirmed, warning
+    /// first when a user loses all prints while PAM depends on them
+    ///
+    /// **Returns** either ***Task***() or ***task_delete_selection***()
+    pub(crate) fn on_delete_selected(&mut self) -> Task<cosmic::Action<Message>> {
+        let deletions = self.overview_deletions();
+        if deletions.is_empty() {
+            return Task::none();
+        }
+        match self.state.confirmation() {
+            Some(Confirmation::DeleteSelected)
+                if self.needs_last_print_warning(Removal::Selection) =>
+            {
+                self.transition(OperationState::AwaitingConfirmation(
+                    Confirmation::LastPrint(Removal::Selection),
+                ));
+                return Task::none();
+            }
+            Some(Confirmation::DeleteSelected | Confirmation::LastPrint(Removal::Selection)) => {}
+            _ => {
+                self.transition(OperationState::AwaitingConfirmation(
+                    Confirmation::DeleteSelected,
+                ));
+                return Task::none();
+            }
+        }
+
+        if let (Some(path), Some(backend)) = (self.device_path.clone(), self.backend.clone())
+            && self.transition(OperationState::Deleting)
+        {
+            self.status = fl!("deleting");
+            return task_delete_selection(
+                backend,
+                path.as_ref().clone(),
+                deletions,
+                self.auditor(),
+            );
+        }
+        Task::none()
+    }
+
+    /// Ends a bulk deletion from the overview and lists the prints again,
+    /// as a failure may leave some of them deleted
+    ///
+    /// **Returns** ***list_user_prints_task***()
+    pub(crate) fn on_selection_deleted(
+        &mut self,
+        res: Result<(), AppError>,
+    ) -> Task<cosmic::Action<Message>> {
+        match res {
+            Ok(()) => {
+                self.status = fl!("overview-deleted");
+                if let Some(overview) = &mut self.overview {
+                    overview.selected.clear();
+                }
+            }
+            Err(e) => self.status = e.localized_message(),
+        }
+        self.finish_operation();
+        self.list_user_prints_task()
+    }
+
+    /// Keeps the prints of the selected user in line with `enrolled_fingers`
+    fn update_selected_prints(&mut self) {
+        if let Some(user) = self.selected_user.clone() {
+            self.set_user_prints(&user.username, Some(self.enrolled_fingers.clone()));
+        }
+    }
+
+    fn set_user_prints(&mut self, username: &str, fingers: Option<Vec<String>>) {
+        match fingers {
+            Some(fingers) => self.user_prints.insert(username.to_string(), fingers),
+            None => self.user_prints.remove(username),
+        };
+        self.update_nav_label(username);
+    }
+
+    /// Drops all badges, as the prints belong to the previous device
+    fn clear_user_prints(&mut self) {
+        let usernames: Vec<String> = self.user_prints.drain().map(|(name, _)| name).collect();
+        for username in usernames {
+            self.update_nav_label(&username);
+        }
+    }
+
+    fn update_nav_label(&mut self, username: &str) {
+        let item = self.nav.iter().find_map(|id| {
+            self.nav
+                .data::<UserOption>(id)
+                .filter(|user| *user.username == username)
+                .map(|user| {
+                    (
+                        id,
+                        user.nav_label(
+                            self.user_prints.get(username).map(Vec::len),
+                            self.manage_access(user),
+                        ),
+                    )
+                })
+        });
+        if let Some((id, label)) = item {
+            self.nav.text_set(id, label);
+        }
+    }
+
+    /// **Returns** `users` followed by the users added by name which are
+    /// not among them. Users added by name are never hidden as system
+    /// accounts.
+    fn with_added_users(&self, users: &[UserOption]) -> Vec<UserOption> {
+        let is_added = |user: &UserOption| {
+            self.added_users
+                .iter()
+                .any(|added| added.username == user.username)
+        };
+        let mut all_users: Vec<UserOption> = users
+            .iter()
+            .map(|user| UserOption {
+                system: user.system && !is_added(user),
+                ..user.clone()
+            })
+            .collect();
+        for added in &self.added_users {
+            if !users.iter().any(|user| user.username == added.username) {
+                all_users.push(UserOption {
+                    system: false,
+                    ..added.clone()
+                });
+            }
+        }
+        all_users
+    }
+
+    /// Opens the dialog to add a user by name
+    ///
+    /// **Returns** ***Task***()
+    pub(crate) fn on_open_add_user(&mut self) -> Task<cosmic::Action<Message>> {
+        if !self.state.is_busy() && !self.is_restricted() {
+            self.add_user = Some(AddUserDialog::default());
+        }
+        Task::none()
+    }
+
+    pub(crate) fn on_add_user_input(&mut self, username: String) -> Task<cosmic::Action<Message>> {
+        if let Some(dialog) = &mut self.add_user {
+            dialog.username = username;
+            dialog.error = None;
+        }
+        Task::none()
+    }
+
+    pub(crate) fn on_add_user_remember(&mut self, remember: bool) -> Task<cosmic::Action<Message>> {
+        if let Some(dialog) = &mut self.add_user {
+            dialog.remember = remember;
+        }
+        Task::none()
+    }
+
+    pub(crate) fn on_close_add_user(&mut self) -> Task<cosmic::Action<Message>> {
+        self.add_user = None;
+        Task::none()
+    }
+
+    /// Looks up the name typed into the add user dialog
+    ///
+    /// **Returns** ***task_find_user***() or ***Task***()
+    pub(crate) fn on_add_user(&mut self) -> Task<cosmic::Action<Message>> {
+        let Some(dialog) = &mut self.add_user else {
+            return Task::none();
+        };
+        let username = dialog.username.trim().to_string();
+        if username.is_empty() || dialog.searching {
+            return Task::none();
+        }
+        dialog.searching = true;
+        dialog.error = None;
+        task_find_user(username)
+    }
+
+    /// Adds the user found for the add user dialog to the nav bar and selects
+    /// them, remembering them in config when asked to. An unknown name is
+    /// reported in the dialog.
+    ///
+    /// **Returns** ***update_title_task***() and ***list_fingers_task***()
+    pub(crate) fn on_user_found(
+        &mut self,
+        username: String,
+        user: Option<UserOption>,
+    ) -> Task<cosmic::Action<Message>> {
+        let Some(dialog) = &mut self.add_user else {
+            return Task::none();
+        };
+        let Some(user) = user else {
+            dialog.searching = false;
+            dialog.error = Some(fl!("add-user-not-found", user = username.as_str()));
+            return Task::none();
+        };
+        let remember = dialog.remember;
+        self.add_user = None;
+
+        if remember
+            && !self
+                .config
+                .remembered_users
+                .iter()
+                .any(|name| *name == *user.username)
+        {
+            let mut config = self.config.clone();
+            config.remembered_users.push((*user.username).clone());
+            let _ = self.on_update_config(config);
+        }
+
+        self.added_users
+            .retain(|added| added.username != user.username);
+        self.added_users.push(user.clone());
+
+        if self.state.is_busy() {
+            return self.set_users(self.users.clone());
+        }
+        self.dismiss_confirmation();
+        let (nav, selected_user) = build_nav(
+            &self.with_added_users(&self.users),
+            Some(&user.username),
+            self.config.show_all_users,
+            self.is_restricted(),
+            &self.user_prints,
+            &self.permissions,
+        );
+        self.nav = nav;
+        self.selected_user = selected_user;
+        self.enrolled_fingers.clear();
+        Task::batch(vec![
+            self.update_title_task(),
+            self.list_fingers_task(),
+            self.list_user_prints_task(),
+        ])
+    }
+
+    /// Adds the users remembered in config to the nav bar
+    ///
+    /// **Returns** the tasks of ***set_users***()
+    pub(crate) fn on_remembered_users_found(
+        &mut self,
+        users: Vec<UserOption>,
+    ) -> Task<cosmic::Action<Message>> {
+        for user in users {
+            if !self
+                .added_users
+                .iter()
+                .any(|added| added.username == user.username)
+            {
+                self.added_users.push(user);
+            }
+        }
+        self.set_users(self.users.clone())
+    }
+
+    /// Starts checking the fingerprint stack unless a check is running
+    ///
+    /// **Returns** either ***Task***() or ***task_run_diagnostics***()
+    pub(crate) fn on_run_diagnostics(&mut self) -> Task<cosmic::Action<Message>> {
+        if self.diagnosing {
+            return Task::none();
+        }
+        self.diagnosing = true;
+        task_run_diagnostics()
+    }
+
+    /// Stores the diagnostics report shown in settings
+    ///
+    /// **Returns** ***Task***()
+    pub(crate) fn on_diagnostics_ready(
+        &mut self,
+        checks: Vec<Check>,
+    ) -> Task<cosmic::Action<Message>> {
+        self.diagnosing = false;
+        self.diagnostics = checks;
+        Task::none()
+    }
+
+    /// Stores how PAM services use pam_fprintd and what enabling it would change
+    ///
+    /// **Returns** ***Task***()
+    pub(crate) fn on_pam_inspected(
+        &mut self,
+        services: Vec<PamService>,
+        plan: Plan,
+    ) -> Task<cosmic::Action<Message>> {
+        self.pam_services = services;
+        self.pam_plan = Some(plan);
+        Task::none()
+    }
+
+    /// **Returns** whether the user running the app has prints on the
+    /// selected device, which the PAM helper requires of whoever ran it
+    pub(crate) fn session_has_prints(&self) -> bool {
+        self.with_added_users(&self.users)
+            .iter()
+            .find(|user| user.is_current_user())
+            .and_then(|user| self.user_prints.get(user.username.as_str()))
+            .is_some_and(|prints| !prints.is_empty())
+    }
+
+    /// First shows the PAM changes for confirmation, then applies them
+    /// through the privileged helper. Refused until the user running the
+    /// app enrolled a finger, so nobody enables a login method they cannot use.
+    ///
+    /// **Returns** either ***Task***() or ***task_apply_pam_setup***()
+    pub(crate) fn on_enable_pam(&mut self) -> Task<cosmic::Action<Message>> {
+        let Some(device) = self.device_path.clone() else {
+            return Task::none();
+        };
+        if !self.session_has_prints() {
+            self.status = fl!("pam-setup-no-prints");
+            return Task::none();
+        }
+        if self.pam_plan.as_ref().is_none_or(Plan::is_empty) {
+            return Task::none();
+        }
+
+        if self.state.confirmation() != Some(Confirmation::EnablePam) {
+            self.transition(OperationState::AwaitingConfirmation(
+                Confirmation::EnablePam,
+            ));
+            return Task::none();
+        }
+
+        if self.transition(OperationState::ConfiguringPam) {
+            return task_apply_pam_setup((*device).clone());
+        }
+        Task::none()
+    }
+
+    /// Reports the helper's result and reads the PAM configuration again
+    ///
+    /// **Returns** ***task_inspect_pam***()
+    pub(crate) fn on_pam_setup_applied(
+        &mut self,
+        result: Result<(), String>,
+    ) -> Task<cosmic::Action<Message>> {
+        self.finish_operation();
+        self.status = match result {
+            Ok(()) => fl!("pam-setup-applied"),
+            Err(err) => err,
+        };
+        task_inspect_pam()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::backend::fake::FakeBackend;
+    use crate::app::pam::{FprintdRule, ServiceKind};
+    use crate::app::pam_setup::{FileChange, Mechanism};
+    use crate::fprint_mock::{MockConfig, MockFprintd};
+    use cosmic::Application;
+    use cosmic::iced::futures::channel::mpsc;
+    use futures_util::StreamExt;
+
+    fn user(name: &str) -> UserOption {
+        UserOption {
+            username: Arc::new(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// **Returns** a model with a found device and "alice" selected
+    async fn model(fake: &FakeBackend) -> AppModel {
+        let mut app = AppModel::new(cosmic::Core::default(), None, Config::default());
+        app.backend = Some(Arc::new(fake.clone()));
+        app.users = vec![user("alice"), user("bob")];
+        app.selected_user = Some(user("alice"));
+        let _ = app.update(Message::DeviceFound(Some(fake.device.path.clone())));
+        let _ = app.update(list_fingers(backend(&app), path(&app), "alice".to_string()).await);
+        app
+    }
+
+    /// A sudo which only accepts fingerprints
+    fn fingerprint_only_sudo() -> PamService {
+        PamService {
+            kind: ServiceKind::Sudo,
+            name: Some("sudo".to_string()),
+            rules: vec![FprintdRule {
+                control: "sufficient".to_string(),
+                chain: vec!["sudo".to_string()],
+            }],
+            password_fallback: false,
+        }
+    }
+
+    fn backend(app: &AppModel) -> Arc<dyn FingerprintBackend> {
+        app.backend.clone().unwrap()
+    }
+
+    fn path(app: &AppModel) -> zbus::zvariant::OwnedObjectPath {
+        app.device_path.as_deref().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_device_found_lists_fingers() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let app = model(&fake).await;
+
+        assert_eq!(app.state, OperationState::Idle);
+        assert_eq!(app.status, fl!("status-device-found"));
+        assert_eq!(app.enrolled_fingers, vec!["left-thumb"]);
+    }
+
+    #[tokio::test]
+    async fn test_fprintd_restart_cancels_and_restores_device() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut app = model(&fake).await;
+        app.selected_finger = Finger::LeftIndex;
+        let _ = app.update(Message::Register);
+        assert!(app.state.enrolling_finger().is_some());
+
+        // A running enroll started the service itself.
+        let _ = app.update(Message::FprintdAppeared);
+        assert!(app.state.enrolling_finger().is_some());
+
+        let _ = app.update(Message::FprintdVanished);
+        assert_eq!(app.state, OperationState::Idle);
+        assert_eq!(app.status, fl!("fprintd-vanished"));
+        assert!(fake.calls().contains(&"Reset".to_string()));
+
+        // Exiting while idle is routine and says nothing.
+        app.status = fl!("status-device-found");
+        let _ = app.update(Message::FprintdVanished);
+        assert_eq!(app.status, fl!("status-device-found"));
+
+        let _ = app.update(Message::FprintdAppeared);
+        let message = restore_device(backend(&app), Some(path(&app))).await;
+        assert!(matches!(&message, Message::DeviceFound(Some(p)) if *p == fake.device.path));
+        let gone =
+            zbus::zvariant::OwnedObjectPath::try_from("/net/reactivated/Fprint/Device/7").unwrap();
+        let message = restore_device(backend(&app), Some(gone)).await;
+        assert!(matches!(&message, Message::DeviceFound(Some(p)) if *p == fake.device.path));
+        let _ = app.update(message);
+        assert_eq!(app.state, OperationState::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_hotplug_falls_back_to_default_device() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut app = model(&fake).await;
+        let docked = DeviceOption {
+            path: zbus::zvariant::OwnedObjectPath::try_from("/net/reactivated/Fprint/Device/1")
+                .unwrap(),
+            name: "Dock Reader".to_string(),
+            num_enroll_stages: None,
+        };
+
+        // A reader coming along keeps the selected one.
+        let _ = app.update(Message::DevicesChanged(Ok(vec![
+            fake.device.clone(),
+            docked.clone(),
+        ])));
+        assert_eq!(app.devices.len(), 2);
+        assert_eq!(path(&app), fake.device.path);
+
+        // A running enroll hears about its device from fprintd.
+        app.selected_finger = Finger::LeftIndex;
+        let _ = app.update(Message::Register);
+        let _ = app.update(Message::DevicesChanged(Ok(vec![docked.clone()])));
+        assert!(app.state.enrolling_finger().is_some());
+        assert_eq!(path(&app), fake.device.path);
+        app.finish_operation();
+
+        // Without it, the default device is looked up again.
+        let _ = app.update(Message::DevicesChanged(Ok(vec![docked.clone()])));
+        assert_eq!(app.devices.len(), 1);
+        assert_eq!(path(&app), fake.device.path);
+        let _ = app.update(Message::DeviceFound(Some(docked.path.clone())));
+        assert_eq!(path(&app), docked.path);
+
+        let _ = app.update(Message::DevicesChanged(Ok(Vec::new())));
+        assert!(app.device_path.is_none());
+        assert_eq!(app.state, OperationState::Unavailable);
+        assert_eq!(app.status, fl!("status-no-device-found"));
+
+        // Listing failures keep what is known.
+        let _ = app.update(Message::DevicesChanged(Err(AppError::Timeout)));
+        assert_eq!(app.status, fl!("status-no-device-found"));
+    }
+
+    #[tokio::test]
+    async fn test_start_failed_service() {
+        let mock = MockFprintd::start(MockConfig::default()).await.unwrap();
+        let fake = FakeBackend::default();
+        let mut app = AppModel::new(cosmic::Core::default(), None, Config::default());
+        app.backend = Some(Arc::new(fake.clone()));
+        app.connection = Some(mock.connection.clone());
+
+        let _ = app.update(Message::OperationError(AppError::ServiceNotInstalled));
+        assert_eq!(
+            app.status,
+            AppError::ServiceNotInstalled.localized_message()
+        );
+        let _ = app.update(Message::ServiceChecked(ServiceState::NotInstalled));
+        // Only a failed unit can be started.
+        let _ = app.update(Message::StartService);
+        assert_eq!(app.service, ServiceState::NotInstalled);
+
+        let unit = "fprintd.service".to_string();
+        let _ = app.update(Message::ServiceChecked(ServiceState::Failed(unit.clone())));
+        assert_eq!(app.status, AppError::ServiceFailed.localized_message());
+        let _ = app.update(Message::StartService);
+        assert_eq!(app.service, ServiceState::Starting(unit.clone()));
+        assert_eq!(app.status, fl!("service-starting"));
+
+        // Dismissing the authentication offers to start it again.
+        let _ = app.update(Message::ServiceStarted(Err(AppError::PermissionDenied)));
+        assert_eq!(app.service, ServiceState::Failed(unit.clone()));
+        assert_eq!(app.status, AppError::PermissionDenied.localized_message());
+
+        let _ = app.update(Message::StartService);
+        let _ = app.update(Message::ServiceStarted(Ok(())));
+        assert_eq!(app.service, ServiceState::Available);
+        assert_eq!(app.status, fl!("status-searching-device"));
+
+        // A device found meanwhile wins over a late answer from systemd.
+        let _ = app.update(Message::DeviceFound(Some(fake.device.path.clone())));
+        let _ = app.update(Message::ServiceChecked(ServiceState::Failed(unit)));
+        assert_eq!(app.service, ServiceState::Available);
+        assert_eq!(app.status, fl!("status-device-found"));
+    }
+
+    #[tokio::test]
+    async fn test_no_enrolled_prints_is_not_an_error() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut app = model(&fake).await;
+
+        let _ = app.update(Message::OperationError(AppError::NoEnrolledPrints));
+        assert!(app.enrolled_fingers.is_empty());
+        assert_eq!(app.status, fl!("success"));
+    }
+
+    #[tokio::test]
+    async fn test_enroll_updates_enrolled_fingers() {
+        let fake = FakeBackend::default();
+        let mut app = model(&fake).await;
+        app.selected_finger = Finger::LeftThumb;
+
+        let _ = app.update(Message::Register);
+        assert!(app.state.is_busy());
+        let finger = app.state.enrolling_finger().cloned().unwrap();
+        assert_eq!(*finger, "left-thumb");
+
+        // A second register while enrolling is ignored.
+        app.selected_finger = Finger::LeftIndex;
+        let _ = app.update(Message::Register);
+        assert_eq!(app.state.enrolling_finger(), Some(&finger));
+        // So is verifying.
+        let _ = app.update(Message::VerifyFinger);
+        assert!(app.state.verifying_finger().is_none());
+        app.selected_finger = Finger::LeftThumb;
+
+        let (output, receiver) = mpsc::channel(100);
+        backend(&app)
+            .enroll(path(&app), (*finger).clone(), "alice".to_string(), output)
+            .await
+            .unwrap();
+        let mut messages: Vec<Message> = receiver.collect().await;
+        let completed = messages.pop().unwrap();
+        for message in messages {
+            let _ = app.update(message);
+        }
+        assert!(matches!(
+            app.state,
+            OperationState::Enrolling { stage: 2, .. }
+        ));
+
+        let _ = app.update(completed);
+        assert_eq!(app.state, OperationState::Idle);
+        assert_eq!(app.status, fl!("enroll-completed"));
+        // Completing an enrollment moves on to the next finger.
+        assert_eq!(app.selected_finger, Finger::LeftIndex);
+
+        let _ = app.update(list_fingers(backend(&app), path(&app), "alice".to_string()).await);
+        assert_eq!(app.enrolled_fingers, vec!["left-thumb"]);
+    }
+
+    #[tokio::test]
+    async fn test_sensor_kept_while_scanning() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["right-index-finger"]);
+        let mut app = model(&fake).await;
+        let detected = Sensor {
+            finger_present: true,
+            finger_needed: true,
+            ..Sensor::default()
+        };
+
+        // Changes arriving after a scan are dropped.
+        let _ = app.update(Message::SensorChanged(detected));
+        assert_eq!(app.sensor, None);
+
+        app.selected_finger = Finger::RightIndex;
+        let _ = app.update(Message::VerifyFinger);
+        let _ = app.update(Message::SensorChanged(detected));
+        assert_eq!(app.sensor, Some(detected));
+        let _ = app.update(Message::VerifyStatus("verify-match".to_string(), true));
+
+        // The next scan starts without the previous sensor state.
+        let _ = app.update(Message::Register);
+        assert_eq!(app.sensor, None);
+    }
+
+    #[tokio::test]
+    async fn test_delete_single_finger() {
+        let fake =
+            FakeBackend::default().with_enrolled("alice", &["right-index-finger", "left-thumb"]);
+        let mut app = model(&fake).await;
+        app.selected_finger = Finger::RightIndex;
+
+        let _ = app.update(Message::Delete);
+        assert_eq!(app.state, OperationState::Deleting);
+        assert_eq!(app.status, fl!("deleting"));
+
+        // Busy state blocks selecting another finger.
+        let _ = app.update(Message::FingerSelected(Finger::LeftThumb));
+        assert_eq!(app.selected_finger, Finger::RightIndex);
+
+        let message = delete_print(
+            backend(&app),
+            path(&app),
+            "alice".to_string(),
+            "right-index-finger".to_string(),
+            app.auditor(),
+        )
+        .await;
+        let _ = app.update(message);
+
+        assert_eq!(app.state, OperationState::Idle);
+        assert_eq!(app.status, fl!("deleted"));
+        assert_eq!(app.enrolled_fingers, vec!["left-thumb"]);
+        assert_eq!(fake.enrolled("alice"), vec!["left-thumb"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_falls_back_to_delete_all() {
+        let fake = FakeBackend::default()
+            .open_fprintd()
+            .with_enrolled("alice", &["right-index-finger", "left-thumb"]);
+        let mut app = model(&fake).await;
+        app.selected_finger = Finger::RightIndex;
+
+        let _ = app.update(Message::Delete);
+        let message = delete_print(
+            backend(&app),
+            path(&app),
+            "alice".to_string(),
+            "right-index-finger".to_string(),
+            app.auditor(),
+        )
+        .await;
+        assert!(matches!(message, Message::DeleteSingleUnsupported));
+        let _ = app.update(message);
+
+        assert_eq!(app.state.confirmation(), Some(Confirmation::DeleteAll));
+        assert_eq!(app.enrolled_fingers.len(), 2);
+
+        let _ = app.update(Message::ConfirmDeleteAll);
+        assert_eq!(app.state, OperationState::Deleting);
+
+        let message = delete_prints(
+            backend(&app),
+            path(&app),
+            "alice".to_string(),
+            app.auditor(),
+        )
+        .await;
+        let _ = app.update(message);
+
+        assert_eq!(app.state, OperationState::Idle);
+        assert!(app.enrolled_fingers.is_empty());
+        assert!(fake.enrolled("alice").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_all_offered_without_single_delete() {
+        let fake = FakeBackend::default()
+            .open_fprintd()
+            .with_enrolled("alice", &["right-index-finger", "left-thumb"]);
+        let mut app = model(&fake).await;
+        assert!(app.capabilities.delete_finger);
+
+        // A probe of a device selected before is dropped.
+        let other =
+            zbus::zvariant::OwnedObjectPath::try_from("/net/reactivated/Fprint/Device/1").unwrap();
+        let _ = app.update(Message::CapabilitiesProbed(
+            other,
+            DeviceCapabilities::default(),
+        ));
+        let _ = app.update(probe_capabilities(backend(&app), path(&app)).await);
+        assert!(!app.capabilities.delete_finger);
+
+        // Single prints cannot be deleted, only everything after a confirmation.
+        app.selected_finger = Finger::RightIndex;
+        let _ = app.update(Message::Delete);
+        assert_eq!(app.state, OperationState::Idle);
+        let _ = app.update(Message::DeleteAll);
+        assert_eq!(app.state.confirmation(), Some(Confirmation::DeleteAll));
+        assert!(
+            !fake
+                .calls()
+                .iter()
+                .any(|call| call.starts_with("DeleteFinger"))
+        );
+
+        let _ = app.update(Message::ConfirmDeleteAll);
+        assert_eq!(app.state, OperationState::Deleting);
+        let message = delete_prints(
+            backend(&app),
+            path(&app),
+            "alice".to_string(),
+            app.auditor(),
+        )
+        .await;
+        let _ = app.update(message);
+        assert!(fake.enrolled("alice").is_empty());
+
+        // Nothing left to delete.
+        let _ = app.update(Message::DeleteAll);
+        assert_eq!(app.state, OperationState::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_delete_ignores_unenrolled_finger() {
+        let fake = FakeBackend::default()
+            .open_fprintd()
+            .with_enrolled("alice", &["right-index-finger"]);
+        let mut app = model(&fake).await;
+        app.selected_finger = Finger::LeftThumb;
+
+        let _ = app.update(Message::Delete);
+        assert_eq!(app.state, OperationState::Idle);
+
+        let _ = app.update(probe_capabilities(backend(&app), path(&app)).await);
+        let _ = app.update(Message::Delete);
+        assert_eq!(app.state, OperationState::Idle);
+        assert!(fake.calls().iter().all(|call| !call.starts_with("Delete")));
+        assert_eq!(fake.enrolled("alice"), vec!["right-index-finger"]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_delete_all_keeps_prints() {
+        let fake = FakeBackend::default()
+            .open_fprintd()
+            .with_enrolled("alice", &["right-index-finger"]);
+        let mut app = model(&fake).await;
+
+        let _ = app.update(Message::Delete);
+        let _ = app.update(Message::DeleteSingleUnsupported);
+        let _ = app.update(Message::Cancel);
+
+        assert_eq!(app.state, OperationState::Idle);
+        assert_eq!(app.enrolled_fingers, vec!["right-index-finger"]);
+        assert_eq!(fake.enrolled("alice"), vec!["right-index-finger"]);
+    }
+
+    #[tokio::test]
+    async fn test_clear_device_requires_confirmation() {
+        let fake = FakeBackend::default()
+            .with_enrolled("alice", &["left-thumb"])
+            .with_enrolled("bob", &["left-thumb"]);
+        let mut app = model(&fake).await;
+
+        let _ = app.update(Message::ClearDevice);
+        assert_eq!(app.state.confirmation(), Some(Confirmation::ClearDevice));
+
+        // Selecting another finger dismisses the dialog.
+        let _ = app.update(Message::FingerSelected(Finger::RightThumb));
+        assert_eq!(app.state, OperationState::Idle);
+
+        // The phrase has to be typed before confirming.
+        let _ = app.update(Message::ClearDevice);
+        let _ = app.update(Message::ClearDevice);
+        assert_eq!(app.state.confirmation(), Some(Confirmation::ClearDevice));
+        let _ = app.update(Message::ClearPhraseInput(format!(
+            " {} ",
+            fl!("clear-device-phrase")
+        )));
+        let _ = app.update(Message::ClearDevice);
+        assert_eq!(app.state, OperationState::AuthorizingClear);
+
+        // Failed authentication leaves the prints.
+        let _ = app.update(Message::ClearAuthorized(Authorization::Refused));
+        assert_eq!(app.state, OperationState::Idle);
+        assert_eq!(app.status, fl!("clear-device-not-authorized"));
+        assert_eq!(fake.enrolled("bob"), vec!["left-thumb"]);
+
+        // A missing policy file is named instead.
+        let _ = app.update(Message::ClearDevice);
+        let _ = app.update(Message::ClearPhraseInput(fl!("clear-device-phrase")));
+        let _ = app.update(Message::ClearDevice);
+        let _ = app.update(Message::ClearAuthorized(Authorization::NotRegistered));
+        assert_eq!(app.state, OperationState::Idle);
+        assert_eq!(
+            Some(app.status.clone()),
+            Authorization::NotRegistered.localized_clear_error()
+        );
+
+        // Reopening the dialog asks for the phrase again.
+        let _ = app.update(Message::ClearDevice);
+        assert!(!app.clear_phrase_matches());
+        let _ = app.update(Message::ClearPhraseInput(fl!("clear-device-phrase")));
+        let _ = app.update(Message::ClearDevice);
+        let _ = app.update(Message::ClearAuthorized(Authorization::Granted));
+        assert_eq!(app.state, OperationState::Clearing);
+
+        let usernames = app.clear_usernames();
+        let _ = app.update(clear_device(backend(&app), path(&app), usernames, app.auditor()).await);
+
+        assert_eq!(app.state, OperationState::Idle);
+        assert!(app.enrolled_fingers.is_empty());
+        assert!(fake.enrolled("alice").is_empty());
+        assert!(fake.enrolled("bob").is_empty());
+        assert_eq!(fake.calls().last().unwrap(), "Clear(alice, bob)");
+    }
+
+    #[tokio::test]
+    async fn test_clear_device_includes_added_users() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut app = model(&fake).await;
+
+        let _ = app.update(Message::OpenAddUser);
+        let _ = app.update(Message::AddUserInput("carol".to_string()));
+        let _ = app.update(Message::AddUser);
+        let _ = app.update(Message::UserFound("carol".to_string(), Some(user("carol"))));
+        app.selected_finger = Finger::RightThumb;
+        let _ = app.update(Message::Register);
+        let finger = app.state.enrolling_finger().cloned().unwrap();
+        let (output, receiver) = mpsc::channel(100);
+        backend(&app)
+            .enroll(path(&app), (*finger).clone(), "carol".to_string(), output)
+            .await
+            .unwrap();
+        for message in receiver.collect::<Vec<Message>>().await {
+            let _ = app.update(message);
+        }
+        assert_eq!(fake.enrolled("carol"), vec!["right-thumb"]);
+
+        let _ = app.update(Message::ClearDevice);
+        let _ = app.update(Message::ClearPhraseInput(fl!("clear-device-phrase")));
+        let _ = app.update(Message::ClearDevice);
+        let _ = app.update(Message::ClearAuthorized(Authorization::Granted));
+        let usernames = app.clear_usernames();
+        assert_eq!(usernames, vec!["alice", "bob", "carol"]);
+        let _ = app.update(clear_device(backend(&app), path(&app), usernames, app.auditor()).await);
+
+        assert_eq!(app.state, OperationState::Idle);
+        assert!(fake.enrolled("alice").is_empty());
+        assert!(fake.enrolled("carol").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_last_print_warning() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["right-index-finger"]);
+        let mut app = model(&fake).await;
+        app.selected_finger = Finger::RightIndex;
+        app.pam_services = vec![fingerprint_only_sudo()];
+        assert_eq!(app.fingerprint_only_services(), vec!["sudo"]);
+
+        let _ = app.update(Message::Delete);
+        assert_eq!(
+            app.state.confirmation(),
+            Some(Confirmation::LastPrint(Removal::Finger))
+        );
+        let _ = app.update(Message::Cancel);
+        assert_eq!(app.state, OperationState::Idle);
+        assert_eq!(fake.enrolled("alice"), vec!["right-index-finger"]);
+
+        let _ = app.update(Message::Delete);
+        let _ = app.update(Message::Delete);
+        assert_eq!(app.state, OperationState::Deleting);
+        let _ = app.update(Message::DeleteComplete(false));
+
+        // Clearing the device warns after its own confirmation.
+        let _ = app.update(Message::ClearDevice);
+        let _ = app.update(Message::ClearPhraseInput(fl!("clear-device-phrase")));
+        let _ = app.update(Message::ClearDevice);
+        assert_eq!(
+            app.state.confirmation(),
+            Some(Confirmation::LastPrint(Removal::Device))
+        );
+        let _ = app.update(Message::ClearDevice);
+        assert_eq!(app.state, OperationState::AuthorizingClear);
+    }
+
+    #[tokio::test]
+    async fn test_no_warning_with_password_fallback() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["right-index-finger"]);
+        let mut app = model(&fake).await;
+        app.selected_finger = Finger::RightIndex;
+        app.pam_services = vec![PamService {
+            password_fallback: true,
+            ..fingerprint_only_sudo()
+        }];
+
+        let _ = app.update(Message::Delete);
+        assert_eq!(app.state, OperationState::Deleting);
+    }
+
+    #[tokio::test]
+    async fn test_users_changed_keeps_selection() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut app = model(&fake).await;
+        let renamed = UserOption {
+            realname: Arc::new("Alice".to_string()),
+            ..user("alice")
+        };
+
+        let _ = app.update(Message::UserChanged(renamed.clone()));
+        assert_eq!(app.selected_user, Some(renamed));
+        assert_eq!(app.users.len(), 2);
+        assert_eq!(app.enrolled_fingers, vec!["left-thumb"]);
+
+        let _ = app.update(Message::UserRemoved(user("alice")));
+        assert_eq!(app.users, vec![user("bob")]);
+        assert_eq!(app.selected_user, None);
+        assert!(app.enrolled_fingers.is_empty());
+        assert_eq!(app.status, fl!("user-removed", user = "alice"));
+
+        // Nothing is started for a user who no longer exists.
+        let _ = app.update(Message::Delete);
+        assert_eq!(app.state, OperationState::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_user_changes_keep_fallback_list() {
+        let fake = FakeBackend::default();
+        let mut app = model(&fake).await;
+        // Listed through NSS, unknown to accounts-daemon.
+        let nss = vec![
+            UserOption {
+                uid: Some(1000),
+                ..user("alice")
+            },
+            UserOption {
+                uid: Some(1001),
+                ..user("bob")
+            },
+        ];
+        let _ = app.set_users(nss.clone());
+
+        let carol = UserOption {
+            uid: Some(1002),
+            ..user("carol")
+        };
+        let _ = app.update(Message::UserChanged(carol.clone()));
+        assert_eq!(
+            app.users,
+            vec![nss[0].clone(), nss[1].clone(), carol.clone()]
+        );
+
+        // A renamed account replaces its entry.
+        let robert = UserOption {
+            uid: Some(1001),
+            ..user("robert")
+        };
+        let _ = app.update(Message::UserChanged(robert.clone()));
+        assert_eq!(app.users, vec![nss[0].clone(), robert, carol.clone()]);
+
+        let _ = app.update(Message::UserRemoved(carol));
+        assert_eq!(app.users.len(), 2);
+        assert_eq!(
+            app.selected_user
+                .as_ref()
+                .map(|user| user.username.as_str()),
+            Some("alice")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_system_accounts_hidden() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut app = model(&fake).await;
+        let system = UserOption {
+            system: true,
+            ..user("alice")
+        };
+
+        let _ = app.set_users(vec![system.clone()]);
+        assert_ne!(app.selected_user, Some(system.clone()));
+        assert_ne!(app.status, fl!("user-removed", user = "alice"));
+        assert_eq!(app.users, vec![system.clone()]);
+
+        let config = Config {
+            show_all_users: true,
+            ..app.config.clone()
+        };
+        let _ = app.update(Message::UpdateConfig(config));
+        assert!(
+            app.nav
+                .iter()
+                .any(|id| app.nav.data::<UserOption>(id) == Some(&system))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_user_by_name() {
+        let fake = FakeBackend::default().with_enrolled("carol", &["right-thumb"]);
+        let mut app = model(&fake).await;
+        let carol = UserOption {
+            system: true,
+            ..user("carol")
+        };
+
+        let _ = app.update(Message::OpenAddUser);
+        let _ = app.update(Message::AddUserInput(" carol ".to_string()));
+        let _ = app.update(Message::AddUserRemember(true));
+        let _ = app.update(Message::AddUser);
+        assert!(app.add_user.as_ref().is_some_and(|dialog| dialog.searching));
+
+        let _ = app.update(Message::UserFound("carol".to_string(), None));
+        assert_eq!(
+            app.add_user
+                .as_ref()
+                .and_then(|dialog| dialog.error.clone()),
+            Some(fl!("add-user-not-found", user = "carol"))
+        );
+
+        let _ = app.update(Message::UserFound("carol".to_string(), Some(carol.clone())));
+        assert_eq!(app.add_user, None);
+        assert_eq!(
+            app.selected_user
+                .as_ref()
+                .map(|user| user.username.as_str()),
+            Some("carol")
+        );
+        assert_eq!(app.config.remembered_users, vec!["carol"]);
+
+        // Users added by name survive accounts-daemon reloading its list.
+        let _ = app.set_users(vec![user("alice")]);
+        assert_eq!(
+            app.selected_user
+                .as_ref()
+                .map(|user| user.username.as_str()),
+            Some("carol")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enrolled_count_badges() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb", "right-thumb"]);
+        let mut app = model(&fake).await;
+        let _ = app.set_users(vec![user("alice"), user("bob")]);
+        let label = |app: &AppModel, name: &str| {
+            app.nav
+                .iter()
+                .find(|id| {
+                    app.nav
+                        .data::<UserOption>(*id)
+                        .is_some_and(|user| *user.username == name)
+                })
+                .and_then(|id| app.nav.text(id))
+                .map(str::to_string)
+        };
+
+        // The selected user's badge follows their listed prints.
+        assert_eq!(
+            label(&app, "alice"),
+            Some(format!("alice · {}", fl!("user-enrolled", count = 2)))
+        );
+
+        let _ = app.update(list_user_prints(backend(&app), path(&app), "bob".to_string()).await);
+        assert_eq!(
+            label(&app, "bob"),
+            Some(format!("bob · {}", fl!("user-not-enrolled")))
+        );
+
+        // Counts from another device are ignored.
+        let other = zbus::zvariant::OwnedObjectPath::try_from("/other").unwrap();
+        let _ = app.update(Message::UserPrints(
+            other,
+            "bob".to_string(),
+            Some(vec!["left-thumb".to_string()]),
+        ));
+        assert_eq!(app.user_prints.get("bob"), Some(&Vec::new()));
+
+        let _ = app.update(Message::DeleteComplete(true));
+        assert_eq!(app.user_prints.get("alice"), Some(&Vec::new()));
+    }
+
+    #[tokio::test]
+    async fn test_restricted_mode() {
+        let fake = FakeBackend::default();
+        let mut app = model(&fake).await;
+        let me = nix::unistd::User::from_uid(nix::unistd::Uid::current())
+            .unwrap()
+            .unwrap()
+            .name;
+        let users = vec![user("alice"), user("bob"), user(&me)];
+        let _ = app.set_users(users.clone());
+        let shown = |app: &AppModel| {
+            app.nav
+                .iter()
+                .filter_map(|id| app.nav.data::<UserOption>(id))
+                .map(|user| (*user.username).clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shown(&app).len(), 3);
+
+        // A standard user who would have to authenticate sees only themself.
+        let _ = app.update(Message::PermissionsChecked(Permissions {
+            set_username: Access::NeedsAuth,
+            ..Default::default()
+        }));
+        assert!(app.is_restricted());
+        assert_eq!(shown(&app), vec![me.clone()]);
+        assert_eq!(
+            app.selected_user
+                .as_ref()
+                .map(|user| user.username.as_str()),
+            Some(me.as_str())
+        );
+        let _ = app.update(Message::OpenAddUser);
+        assert!(app.add_user.is_none());
+        let _ = app.update(Message::ClearDevice);
+        assert!(app.state.confirmation().is_none());
+
+        // Administrators keep everyone.
+        let mut users = users;
+        users[2].admin = true;
+        let _ = app.set_users(users);
+        assert!(!app.is_restricted());
+        assert_eq!(shown(&app).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_permission_badges() {
+        let fake = FakeBackend::default().with_enrolled("bob", &["left-thumb"]);
+        let mut app = model(&fake).await;
+        // An administrator, so the others stay listed.
+        let me = UserOption {
+            admin: true,
+            ..user(
+                &nix::unistd::User::from_uid(nix::unistd::Uid::current())
+                    .unwrap()
+                    .unwrap()
+                    .name,
+            )
+        };
+        let _ = app.set_users(vec![user("alice"), user("bob"), me]);
+        let bob = |app: &AppModel| {
+            app.nav
+                .iter()
+                .find(|id| {
+                    app.nav
+                        .data::<UserOption>(*id)
+                        .is_some_and(|user| *user.username == "bob")
+                })
+                .and_then(|id| app.nav.text(id))
+                .map(str::to_string)
+        };
+        assert_eq!(bob(&app), Some("bob".to_string()));
+
+        // Bob is not the user running the tests, so fprintd checks setusername too.
+        let _ = app.update(Message::PermissionsChecked(Permissions {
+            set_username: Access::NeedsAuth,
+            ..Default::default()
+        }));
+        assert_eq!(
+            bob(&app),
+            Some(format!("bob · {}", fl!("user-auth-required")))
+        );
+        assert_eq!(app.clear_access(), Access::NeedsAuth);
+
+        let _ = app.update(Message::ToggleOverview);
+        assert_eq!(app.overview_access(), Access::Allowed);
+        let _ = app.update(list_user_prints(backend(&app), path(&app), "bob".to_string()).await);
+        let _ = app.update(Message::ToggleOverviewRow("bob".to_string()));
+        assert_eq!(app.overview_access(), Access::NeedsAuth);
+
+        let _ = app.update(Message::PermissionsChecked(Permissions {
+            enroll: Access::Denied,
+            ..Default::default()
+        }));
+        assert_eq!(
+            bob(&app),
+            Some(format!(
+                "bob · {}, {}",
+                fl!("user-enrolled", count = 1),
+                fl!("user-not-permitted")
+            ))
+        );
+        assert_eq!(app.overview_access(), Access::Denied);
+    }
+
+    #[tokio::test]
+    async fn test_enable_pam_needs_session_prints() {
+        let me = nix::unistd::User::from_uid(nix::unistd::Uid::current())
+            .unwrap()
+            .unwrap()
+            .name;
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut app = model(&fake).await;
+        let _ = app.set_users(vec![user("alice"), user(&me)]);
+        app.pam_plan = Some(Plan {
+            mechanism: Mechanism::PamD,
+            changes: vec![FileChange {
+                path: "/etc/pam.d/sudo".into(),
+                before: String::new(),
+                after: "auth sufficient pam_fprintd.so\n".to_string(),
+            }],
+        });
+
+        // Alice is selected and has prints, but the helper checks the session user.
+        let _ = app.update(list_user_prints(backend(&app), path(&app), me.clone()).await);
+        let _ = app.update(Message::EnablePam);
+        assert_eq!(app.status, fl!("pam-setup-no-prints"));
+        assert_eq!(app.state, OperationState::Idle);
+
+        let _ = fake.clone().with_enrolled(&me, &["right-index-finger"]);
+        let _ = app.update(list_user_prints(backend(&app), path(&app), me).await);
+        let _ = app.update(Message::EnablePam);
+        assert_eq!(app.state.confirmation(), Some(Confirmation::EnablePam));
+    }
+
+    #[tokio::test]
+    async fn test_overview_bulk_delete() {
+        let fake = FakeBackend::default()
+            .with_enrolled("alice", &["left-thumb", "right-thumb"])
+            .with_enrolled("bob", &["left-thumb"]);
+        let mut app = model(&fake).await;
+        let _ = app.set_users(vec![user("alice"), user("bob")]);
+        let _ = app.update(Message::ToggleOverview);
+        let _ = app.update(list_user_prints(backend(&app), path(&app), "bob".to_string()).await);
+
+        let _ = app.update(Message::ToggleOverviewCell(
+            "alice".to_string(),
+            Finger::LeftThumb,
+        ));
+        let _ = app.update(Message::ToggleOverviewRow("bob".to_string()));
+        assert_eq!(app.overview_selected_count(), 2);
+
+        let _ = app.update(Message::DeleteSelected);
+        assert_eq!(
+            app.state,
+            OperationState::AwaitingConfirmation(Confirmation::DeleteSelected)
+        );
+        let _ = app.update(Message::DeleteSelected);
+        assert_eq!(app.state, OperationState::Deleting);
+
+        let deletions = app.overview_deletions();
+        let _ =
+            app.update(delete_selection(backend(&app), path(&app), deletions, app.auditor()).await);
+        assert_eq!(app.state, OperationState::Idle);
+        assert_eq!(app.status, fl!("overview-deleted"));
+        assert_eq!(app.overview_selected_count(), 0);
+        assert_eq!(fake.enrolled("alice"), vec!["right-thumb"]);
+        assert!(fake.enrolled("bob").is_empty());
     }
 }
@@ -9,19 +9,41 @@ use cosmic::{
 
 use crate::{
     app::{
+        audit::Entry,
+        backend::FingerprintBackend,
+        doctor::Check,
         finger::Finger,
+        fprint::DeviceCapabilities,
         message::{DeviceOption, Message},
-        users::UserOption,
+        overview::Overview,
+        pam::PamService,
+        pam_setup::Plan,
+        polkit::Permissions,
+        service::ServiceState,
+        state::OperationState,
+        status::Sensor,
+        users::{AddUserDialog, UserOption},
     },
     config::Config,
-    fprint_dbus::DeviceProxy,
+    fl,
 };
 
+pub mod audit;
+pub mod backend;
+pub mod doctor;
 pub mod error;
 pub mod finger;
 pub mod fprint;
+pub mod hotplug;
 pub mod message;
+pub mod overview;
+pub mod pam;
+pub mod pam_setup;
+pub mod polkit;
+pub mod service;
 pub mod settings;
+pub mod state;
+pub mod status;
 pub mod subscription;
 pub mod tasks;
 pub mod users;
@@ -50,42 +72,105 @@ pub struct AppModel {
     device_path: Option<Arc<zbus::zvariant::OwnedObjectPath>>,
     // All devices
     devices: Vec<DeviceOption>,
-    // Reused device proxy
-    device_proxy: Option<DeviceProxy<'static>>,
-    // Shared DBus connection
+    // What the daemon implements for the selected device
+    capabilities: DeviceCapabilities,
+    // Shared system bus connection, once established
     connection: Option<zbus::Connection>,
-    // Whether an operation is in progress
-    busy: bool,
-    // Finger currently being enrolled (None if not enrolling)
-    enrolling_finger: Option<Arc<String>>,
-    // Whether verifying a finger
-    verifying_finger: bool,
-    // Enrollment progress
-    enroll_progress: u32,
+    // Fingerprint service used for all device operations
+    backend: Option<Arc<dyn FingerprintBackend>>,
+    // What systemd says about fprintd when no device could be found
+    service: ServiceState,
+    // What polkit lets us do with fprintd, asked without interaction
+    permissions: Permissions,
+    // Operation in progress or awaited confirmation
+    state: OperationState,
+    // Phrase typed to confirm clearing the device
+    clear_phrase: String,
     // If device supports num_enroll_stages a Some(u32) else None
     enroll_total_stages: Option<u32>,
+    // Sensor state during enroll and verify, if the daemon publishes it
+    sensor: Option<Sensor>,
     // List of users (username, realname)
     users: Vec<UserOption>,
+    // Users added by name for this session
+    added_users: Vec<UserOption>,
+    // Users × fingers overview with its selection, shown instead of the
+    // selected user when open
+    overview: Option<Overview>,
+    // Dialog to add a user by name, when open
+    add_user: Option<AddUserDialog>,
     // Selected user
     selected_user: Option<UserOption>,
     // Selected finger
     selected_finger: Finger,
     // List of enrolled fingers
     enrolled_fingers: Vec<String>,
-    // Confirmation state for clearing the device
-    confirm_clear: bool,
-    // Confirmation state for falling back to deleting all of a user's prints
-    // when single-finger delete is unsupported (e.g. open-fprintd).
-    confirm_delete_all: bool,
+    // Prints per username on the selected device, for nav badges and the overview
+    user_prints: HashMap<String, Vec<String>>,
+    // Last diagnostics report shown in settings
+    diagnostics: Vec<Check>,
+    // Whether diagnostics are running
+    diagnosing: bool,
+    // How PAM services use pam_fprintd, shown in help
+    pam_services: Vec<PamService>,
+    // What enabling pam_fprintd would change, shown in help
+    pam_plan: Option<Plan>,
+    // Audit entries read back from the journal, or why they cannot be
+    history: Result<Vec<Entry>, String>,
 }
 
 mod application;
 
+impl AppModel {
+    /// Creates the model in its initial state, before the DBus connection is ready
+    pub(crate) fn new(
+        core: cosmic::Core,
+        config_handler: Option<cosmic_config::Config>,
+        config: Config,
+    ) -> Self {
+        AppModel {
+            core,
+            context_page: ContextPage::About,
+            nav: nav_bar::Model::default(),
+            key_binds: HashMap::new(),
+            config,
+            config_handler,
+            status: fl!("status-connecting"),
+            device_path: None,
+            devices: Vec::new(),
+            capabilities: DeviceCapabilities::default(),
+            connection: None,
+            backend: None,
+            service: ServiceState::Available,
+            permissions: Permissions::default(),
+            state: OperationState::Unavailable,
+            clear_phrase: String::new(),
+            enroll_total_stages: None,
+            sensor: None,
+            users: Vec::new(),
+            added_users: Vec::new(),
+            overview: None,
+            add_user: None,
+            selected_user: None,
+            selected_finger: Finger::default(),
+            enrolled_fingers: Vec::new(),
+            user_prints: HashMap::new(),
+            diagnostics: Vec::new(),
+            diagnosing: false,
+            pam_services: Vec::new(),
+            pam_plan: None,
+            history: Ok(Vec::new()),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum MenuAction {
     About,
     Settings,
     Help,
+    Overview,
+    History,
 }
 
 impl menu::action::MenuAction for MenuAction {
@@ -96,6 +181,8 @@ impl menu::action::MenuAction for MenuAction {
             MenuAction::About => Message::ToggleContextPage(ContextPage::About),
             MenuAction::Settings => Message::ToggleContextPage(ContextPage::Settings),
             MenuAction::Help => Message::ToggleContextPage(ContextPage::Help),
+            MenuAction::Overview => Message::ToggleOverview,
+            MenuAction::History => Message::ToggleContextPage(ContextPage::History),
         }
     }
 }
@@ -106,6 +193,7 @@ pub enum ContextPage {
     About,
     Settings,
     Help,
+    History,
 }
 
 #[cfg(test)]
@@ -173,5 +261,13 @@ mod tests {
             settings_action.message(),
             Message::ToggleContextPage(ContextPage::Settings)
         ));
+        assert!(matches!(
+            MenuAction::Overview.message(),
+            Message::ToggleOverview
+        ));
+        assert!(matches!(
+            MenuAction::History.message(),
+            Message::ToggleContextPage(ContextPage::History)
+        ));
     }
 }
@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::collections::{HashMap, HashSet};
+
+use crate::app::finger::Finger;
+
+/// Prints selected in the users × fingers overview
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overview {
+    pub selected: HashSet<(String, Finger)>,
+}
+
+/// Prints of one user to delete in bulk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deletion {
+    pub username: String,
+    pub fingers: Vec<Finger>,
+    /// Every enrolled print is selected, so all of them can be deleted at once
+    pub all: bool,
+}
+
+impl Overview {
+    pub fn is_selected(&self, username: &str, finger: Finger) -> bool {
+        self.selected.contains(&(username.to_string(), finger))
+    }
+
+    pub fn toggle_cell(&mut self, username: &str, finger: Finger) {
+        let cell = (username.to_string(), finger);
+        if !self.selected.remove(&cell) {
+            self.selected.insert(cell);
+        }
+    }
+
+    /// Selects every `enrolled` print of a user, or deselects them when all
+    /// of them already are
+    pub fn toggle_row(&mut self, username: &str, enrolled: &[String]) {
+        let fingers: Vec<Finger> = enrolled
+            .iter()
+            .filter_map(|id| Finger::from_finger_id(id))
+            .collect();
+        let all_selected = fingers
+            .iter()
+            .all(|finger| self.is_selected(username, *finger));
+
+        for finger in fingers {
+            if all_selected {
+                self.selected.remove(&(username.to_string(), finger));
+            } else {
+                self.selected.insert((username.to_string(), finger));
+            }
+        }
+    }
+
+    /// Groups the selection by user in the order of `usernames`, leaving out
+    /// prints which are no longer enrolled according to `prints`.
+    ///
+    /// **Returns** one deletion per user with selected prints
+    pub fn deletions(
+        &self,
+        usernames: &[String],
+        prints: &HashMap<String, Vec<String>>,
+    ) -> Vec<Deletion> {
+        let mut deletions = Vec::new();
+
+        for username in usernames {
+            let Some(enrolled) = prints.get(username) else {
+                continue;
+            };
+            let fingers: Vec<Finger> = Finger::all()
+                .iter()
+                .copied()
+                .filter(|finger| {
+                    self.is_selected(username, *finger)
+                        && enrolled.iter().any(|id| id == finger.as_finger_id())
+                })
+                .collect();
+
+            if !fingers.is_empty() {
+                deletions.push(Deletion {
+                    username: username.clone(),
+                    all: fingers.len() == enrolled.len(),
+                    fingers,
+                });
+            }
+        }
+
+        deletions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prints() -> HashMap<String, Vec<String>> {
+        HashMap::from([
+            (
+                "alice".to_string(),
+                vec!["left-thumb".to_string(), "right-thumb".to_string()],
+            ),
+            ("bob".to_string(), vec!["right-index-finger".to_string()]),
+            ("carol".to_string(), Vec::new()),
+        ])
+    }
+
+    #[test]
+    fn test_toggle_row() {
+        let prints = prints();
+        let mut overview = Overview::default();
+
+        overview.toggle_cell("alice", Finger::LeftThumb);
+        overview.toggle_row("alice", &prints["alice"]);
+        assert!(overview.is_selected("alice", Finger::LeftThumb));
+        assert!(overview.is_selected("alice", Finger::RightThumb));
+
+        overview.toggle_row("alice", &prints["alice"]);
+        assert!(overview.selected.is_empty());
+
+        overview.toggle_cell("bob", Finger::RightIndex);
+        overview.toggle_cell("bob", Finger::RightIndex);
+        assert!(overview.selected.is_empty());
+    }
+
+    #[test]
+    fn test_deletions() {
+        let prints = prints();
+        let usernames = ["alice", "bob", "carol", "dave"].map(str::to_string);
+        let mut overview = Overview::default();
+
+        overview.toggle_cell("alice", Finger::RightThumb);
+        overview.toggle_row("bob", &prints["bob"]);
+        // Gone since it was selected, and a user whose prints are unknown.
+        overview.toggle_cell("carol", Finger::LeftThumb);
+        overview.toggle_cell("dave", Finger::LeftThumb);
+
+        assert_eq!(
+            overview.deletions(&usernames, &prints),
+            vec![
+                Deletion {
+                    username: "alice".to_string(),
+                    fingers: vec![Finger::RightThumb],
+                    all: false,
+                },
+                Deletion {
+                    username: "bob".to_string(),
+                    fingers: vec![Finger::RightIndex],
+                    all: true,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,473 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::path::{Path, PathBuf};
+
+use crate::fl;
+
+/// Directories searched for a service file, in the order Linux-PAM uses
+pub const PAM_DIRS: [&str; 2] = ["/etc/pam.d", "/usr/lib/pam.d"];
+
+/// Linux-PAM refuses deeper include chains as well
+const MAX_INCLUDE_DEPTH: usize = 32;
+/// Modules which let a user in with a password
+const PASSWORD_MODULES: [&str; 6] = [
+    "pam_unix",
+    "pam_sss",
+    "pam_ldap",
+    "pam_krb5",
+    "pam_winbind",
+    "pam_systemd_home",
+];
+
+/// Where a user authenticates, each with the service files it may use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceKind {
+    Login,
+    Sudo,
+    Polkit,
+    Greeter,
+    LockScreen,
+}
+
+impl ServiceKind {
+    pub const ALL: [ServiceKind; 5] = [
+        ServiceKind::Login,
+        ServiceKind::Sudo,
+        ServiceKind::Polkit,
+        ServiceKind::Greeter,
+        ServiceKind::LockScreen,
+    ];
+
+    fn candidates(&self) -> &'static [&'static str] {
+        match self {
+            ServiceKind::Login => &["login"],
+            ServiceKind::Sudo => &["sudo"],
+            ServiceKind::Polkit => &["polkit-1"],
+            ServiceKind::Greeter => &[
+                "cosmic-greeter",
+                "gdm-password",
+                "gdm-fingerprint",
+                "sddm",
+                "lightdm",
+                "greetd",
+            ],
+            ServiceKind::LockScreen => &["kde", "swaylock", "hyprlock", "i3lock", "xscreensaver"],
+        }
+    }
+
+    pub fn localized_name(&self) -> String {
+        match self {
+            ServiceKind::Login => fl!("pam-login"),
+            ServiceKind::Sudo => fl!("pam-sudo"),
+            ServiceKind::Polkit => fl!("pam-polkit"),
+            ServiceKind::Greeter => fl!("pam-greeter"),
+            ServiceKind::LockScreen => fl!("pam-lock-screen"),
+        }
+    }
+}
+
+/// An `auth` line loading pam_fprintd, as reached from a service
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FprintdRule {
+    /// Control flag as written, like `sufficient` or `[success=1 default=ignore]`
+    pub control: String,
+    /// Files read on the way to the line, starting with the service itself
+    pub chain: Vec<String>,
+}
+
+impl FprintdRule {
+    /// Whether authentication fails when no finger matches
+    pub fn is_required(&self) -> bool {
+        match self.control.as_str() {
+            "required" | "requisite" => true,
+            control => {
+                control.starts_with('[')
+                    && (control.contains("default=die") || control.contains("default=bad"))
+            }
+        }
+    }
+
+    pub fn localized_description(&self) -> String {
+        match self.chain.get(1..) {
+            Some(included) if !included.is_empty() => fl!(
+                "pam-rule-via",
+                control = self.control.as_str(),
+                chain = included.join(" → ")
+            ),
+            _ => self.control.clone(),
+        }
+    }
+}
+
+/// How a service file uses pam_fprintd
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PamService {
+    pub kind: ServiceKind,
+    /// Service file name, `None` when no candidate for `kind` exists
+    pub name: Option<String>,
+    pub rules: Vec<FprintdRule>,
+    /// Whether a password module is in the `auth` stack as well
+    pub password_fallback: bool,
+}
+
+impl PamService {
+    pub fn uses_fprintd(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    /// Whether a user without enrolled prints is locked out of the service,
+    /// because pam_fprintd is required or no password module follows it
+    pub fn fingerprint_only(&self) -> bool {
+        self.rules.iter().any(FprintdRule::is_required)
+            || (self.uses_fprintd() && !self.password_fallback)
+    }
+
+    /// **Returns** the service name and its control flags, for example
+    /// `sudo (sufficient)`
+    pub fn summary(&self) -> Option<String> {
+        let name = self.name.as_ref()?;
+        if !self.uses_fprintd() {
+            return None;
+        }
+        let rules: Vec<String> = self
+            .rules
+            .iter()
+            .map(FprintdRule::localized_description)
+            .collect();
+        Some(format!("{name} ({})", rules.join(", ")))
+    }
+}
+
+/// One line of a PAM service file
+#[derive(Debug, PartialEq, Eq)]
+enum Entry {
+    Rule {
+        kind: String,
+        control: String,
+        module: String,
+    },
+    /// Debian's `@include`, which pulls in every line of another file
+    Include(String),
+}
+
+/// Checks every known service in `dirs`, following `@include`, `include`
+/// and `substack` chains.
+///
+/// **Returns** one entry per existing service file, or a single entry
+/// without a name for a [`ServiceKind`] with none of its files present
+pub fn inspect<P: AsRef<Path>>(dirs: &[P]) -> Vec<PamService> {
+    let mut services = Vec::new();
+    for kind in ServiceKind::ALL {
+        let before = services.len();
+        for name in kind.candidates() {
+            if resolve(dirs, name).is_none() {
+                continue;
+            }
+            let stack = auth_stack(dirs, name);
+            services.push(PamService {
+                kind,
+                name: Some(name.to_string()),
+                password_fallback: stack.iter().any(|rule| is_password(&rule.module)),
+                rules: fprintd_rules(stack),
+            });
+        }
+        if services.len() == before {
+            services.push(PamService {
+                kind,
+                name: None,
+                rules: Vec::new(),
+                password_fallback: false,
+            });
+        }
+    }
+    services
+}
+
+/// **Returns** the pam_fprintd `auth` lines reached from the service `name`
+pub fn rules_for<P: AsRef<Path>>(dirs: &[P], name: &str) -> Vec<FprintdRule> {
+    fprintd_rules(auth_stack(dirs, name))
+}
+
+/// An `auth` line after following includes
+struct AuthRule {
+    control: String,
+    module: String,
+    chain: Vec<String>,
+}
+
+fn auth_stack<P: AsRef<Path>>(dirs: &[P], name: &str) -> Vec<AuthRule> {
+    let mut stack = Vec::new();
+    collect(dirs, name, &mut Vec::new(), &mut stack);
+    stack
+}
+
+fn fprintd_rules(stack: Vec<AuthRule>) -> Vec<FprintdRule> {
+    stack
+        .into_iter()
+        .filter(|rule| is_fprintd(&rule.module))
+        .map(|rule| FprintdRule {
+            control: rule.control,
+            chain: rule.chain,
+        })
+        .collect()
+}
+
+/// Finds a service file, absolute paths being allowed by `include`
+fn resolve<P: AsRef<Path>>(dirs: &[P], name: &str) -> Option<PathBuf> {
+    if name.starts_with('/') {
+        return Some(PathBuf::from(name)).filter(|path| path.is_file());
+    }
+    dirs.iter()
+        .map(|dir| dir.as_ref().join(name))
+        .find(|path| path.is_file())
+}
+
+fn collect<P: AsRef<Path>>(
+    dirs: &[P],
+    name: &str,
+    chain: &mut Vec<String>,
+    stack: &mut Vec<AuthRule>,
+) {
+    if chain.len() >= MAX_INCLUDE_DEPTH || chain.iter().any(|seen| seen == name) {
+        return;
+    }
+    let Some(content) = resolve(dirs, name).and_then(|path| std::fs::read_to_string(path).ok())
+    else {
+        return;
+    };
+
+    chain.push(name.to_string());
+    for entry in parse(&content) {
+        match entry {
+            Entry::Include(included) => collect(dirs, &included, chain, stack),
+            Entry::Rule {
+                kind,
+                control,
+                module,
+            } => {
+                if kind != "auth" {
+                    continue;
+                }
+                if control == "include" || control == "substack" {
+                    collect(dirs, &module, chain, stack);
+                } else {
+                    stack.push(AuthRule {
+                        control,
+                        module,
+                        chain: chain.clone(),
+                    });
+                }
+            }
+        }
+    }
+    chain.pop();
+}
+
+/// Matches `pam_fprintd.so` by file name, so full module paths and forks
+/// like `pam_fprintd_grosshack.so` count too
+fn is_fprintd(module: &str) -> bool {
+    module_name(module).is_some_and(|name| name.starts_with("pam_fprintd"))
+}
+
+fn is_password(module: &str) -> bool {
+    module_name(module).is_some_and(|name| {
+        PASSWORD_MODULES
+            .iter()
+            .any(|password| name.strip_suffix(".so") == Some(password))
+    })
+}
+
+fn module_name(module: &str) -> Option<&str> {
+    Path::new(module).file_name().and_then(|name| name.to_str())
+}
+
+/// Splits a service file into entries, joining continued lines and
+/// dropping comments
+fn parse(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut logical = String::new();
+
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        if let Some(continued) = line.strip_suffix('\\') {
+            logical.push_str(continued);
+            logical.push(' ');
+            continue;
+        }
+        logical.push_str(line);
+        if let Some(entry) = parse_line(&logical) {
+            entries.push(entry);
+        }
+        logical.clear();
+    }
+    if let Some(entry) = parse_line(&logical) {
+        entries.push(entry);
+    }
+    entries
+}
+
+fn parse_line(line: &str) -> Option<Entry> {
+    let mut tokens = line.split_whitespace();
+    let first = tokens.next()?;
+    if first == "@include" {
+        return tokens.next().map(|name| Entry::Include(name.to_string()));
+    }
+
+    // A leading dash only silences errors for missing modules.
+    let kind = first.trim_start_matches('-').to_ascii_lowercase();
+    let mut control = tokens.next()?.to_string();
+    if control.starts_with('[') {
+        while !control.ends_with(']') {
+            control.push(' ');
+            control.push_str(tokens.next()?);
+        }
+    }
+    let module = tokens.next()?.to_string();
+
+    Some(Entry::Rule {
+        kind,
+        control,
+        module,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_services(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("enroll-pam-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_parse() {
+        let content = "\
+            #%PAM-1.0\n\
+            auth [success=1 default=ignore] pam_fprintd.so max-tries=2 # comment\n\
+            -auth optional \\\n  /usr/lib/security/pam_gnome_keyring.so\n\
+            @include common-account\n\
+            # auth sufficient pam_fprintd.so\n\
+            session\n";
+
+        assert_eq!(
+            parse(content),
+            vec![
+                Entry::Rule {
+                    kind: "auth".to_string(),
+                    control: "[success=1 default=ignore]".to_string(),
+                    module: "pam_fprintd.so".to_string(),
+                },
+                Entry::Rule {
+                    kind: "auth".to_string(),
+                    control: "optional".to_string(),
+                    module: "/usr/lib/security/pam_gnome_keyring.so".to_string(),
+                },
+                Entry::Include("common-account".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inspect_follows_includes() {
+        let dir = write_services(
+            "includes",
+            &[
+                (
+                    "login",
+                    "auth substack system-login\naccount include system-login\n",
+                ),
+                ("system-login", "auth include system-auth\n"),
+                (
+                    "system-auth",
+                    "auth sufficient pam_fprintd.so\nauth required pam_unix.so\n\
+                 password sufficient pam_fprintd.so\n",
+                ),
+                ("sudo", "@include common-auth\n"),
+                ("common-auth", "auth required pam_unix.so\n@include sudo\n"),
+                ("polkit-1", "auth required pam_fprintd.so\n"),
+            ],
+        );
+
+        let services = inspect(&[&dir]);
+        let get = |name: &str| {
+            services
+                .iter()
+                .find(|service| service.name.as_deref() == Some(name))
+                .unwrap()
+        };
+
+        assert_eq!(
+            get("login").rules,
+            vec![FprintdRule {
+                control: "sufficient".to_string(),
+                chain: vec![
+                    "login".to_string(),
+                    "system-login".to_string(),
+                    "system-auth".to_string()
+                ],
+            }]
+        );
+        // Include cycles end without a match.
+        assert!(!get("sudo").uses_fprintd());
+        assert_eq!(
+            get("polkit-1").summary().as_deref(),
+            Some("polkit-1 (required)")
+        );
+
+        let greeter = services
+            .iter()
+            .find(|service| service.kind == ServiceKind::Greeter)
+            .unwrap();
+        assert_eq!(greeter.name, None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fingerprint_only() {
+        let dir = write_services(
+            "lockout",
+            &[
+                (
+                    "login",
+                    "auth sufficient pam_fprintd.so\nauth required pam_unix.so\n",
+                ),
+                (
+                    "sudo",
+                    "auth sufficient pam_fprintd.so\nauth required pam_deny.so\n",
+                ),
+                (
+                    "polkit-1",
+                    "auth [success=1 default=die] pam_fprintd.so\nauth required pam_unix.so\n",
+                ),
+                ("cosmic-greeter", "auth required pam_unix.so\n"),
+            ],
+        );
+
+        let fingerprint_only: Vec<_> = inspect(&[&dir])
+            .into_iter()
+            .filter(PamService::fingerprint_only)
+            .filter_map(|service| service.name)
+            .collect();
+        assert_eq!(fingerprint_only, vec!["sudo", "polkit-1"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_inspect_later_dirs() {
+        let vendor = write_services("dirs", &[("login", "auth sufficient pam_fprintd.so\n")]);
+        let missing = vendor.join("missing");
+
+        let services = inspect(&[&missing, &vendor]);
+        assert!(services[0].uses_fprintd());
+
+        std::fs::remove_dir_all(&vendor).unwrap();
+    }
+}
@@ -0,0 +1,492 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::app::pam::rules_for;
+use crate::fl;
+
+const AUTHSELECT_CONF: &str = "etc/authselect/authselect.conf";
+const AUTHSELECT_FEATURE: &str = "with-fingerprint";
+const PAM_CONFIGS_FPRINTD: &str = "usr/share/pam-configs/fprintd";
+const COMMON_AUTH: &str = "common-auth";
+/// Marker pam-auth-update puts above the modules it manages
+const PRIMARY_BLOCK: &str = "\"Primary\" block";
+const PAM_D: [&str; 2] = ["etc/pam.d", "usr/lib/pam.d"];
+const FPRINTD_RULE: &str = "auth       sufficient   pam_fprintd.so";
+/// Suffix of the copy kept before a service file is edited
+const BACKUP_SUFFIX: &str = ".enroll-backup";
+/// Suffix of the new version before it replaces a service file
+const NEW_SUFFIX: &str = ".enroll-new";
+
+/// How the distribution manages its PAM configuration
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mechanism {
+    /// Fedora and RHEL, through an authselect feature
+    Authselect,
+    /// Debian and Ubuntu, through the profile shipped with libpam-fprintd
+    PamAuthUpdate,
+    /// Editing the service files directly, as on Arch
+    PamD,
+}
+
+impl Mechanism {
+    /// **Returns** the tool run by the helper, `None` for a plain edit
+    pub fn command(&self) -> Option<&'static [&'static str]> {
+        match self {
+            Mechanism::Authselect => Some(&["authselect", "enable-feature", AUTHSELECT_FEATURE]),
+            Mechanism::PamAuthUpdate => {
+                Some(&["pam-auth-update", "--package", "--enable", "fprintd"])
+            }
+            Mechanism::PamD => None,
+        }
+    }
+
+    pub fn localized_name(&self) -> String {
+        match self {
+            Mechanism::Authselect => fl!("pam-setup-authselect"),
+            Mechanism::PamAuthUpdate => fl!("pam-setup-pam-auth-update"),
+            Mechanism::PamD => fl!("pam-setup-pam-d"),
+        }
+    }
+}
+
+/// A file as it is now and after enabling pam_fprintd
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub before: String,
+    pub after: String,
+}
+
+/// What enabling fingerprint authentication would change
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Plan {
+    pub mechanism: Mechanism,
+    pub changes: Vec<FileChange>,
+}
+
+impl Plan {
+    /// Whether fingerprint authentication is already enabled
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// **Returns** the command to run followed by a diff of every change
+    pub fn preview(&self) -> String {
+        let mut preview = String::new();
+        if let Some(command) = self.mechanism.command() {
+            preview.push_str(&format!("# {}\n", command.join(" ")));
+        }
+        for change in &self.changes {
+            preview.push_str(&diff(&change.path, &change.before, &change.after));
+        }
+        preview
+    }
+}
+
+/// Works out how to enable pam_fprintd below `root`, preferring the
+/// distribution's own tools over editing service files.
+pub fn plan(root: &Path) -> Plan {
+    let authselect_conf = root.join(AUTHSELECT_CONF);
+    if let Ok(conf) = std::fs::read_to_string(&authselect_conf) {
+        let enabled = conf.lines().any(|line| line.trim() == AUTHSELECT_FEATURE);
+        let changes = if enabled {
+            Vec::new()
+        } else {
+            let mut after = conf.clone();
+            if !after.is_empty() && !after.ends_with('\n') {
+                after.push('\n');
+            }
+            after.push_str(AUTHSELECT_FEATURE);
+            after.push('\n');
+            vec![FileChange {
+                path: authselect_conf,
+                before: conf,
+                after,
+            }]
+        };
+        return Plan {
+            mechanism: Mechanism::Authselect,
+            changes,
+        };
+    }
+
+    let dirs: Vec<PathBuf> = PAM_D.iter().map(|dir| root.join(dir)).collect();
+    if let Ok(profile) = std::fs::read_to_string(root.join(PAM_CONFIGS_FPRINTD)) {
+        let mut changes = Vec::new();
+        if let Some(path) = dirs
+            .iter()
+            .map(|dir| dir.join(COMMON_AUTH))
+            .find(|p| p.is_file())
+            && rules_for(&dirs, COMMON_AUTH).is_empty()
+            && let Ok(before) = std::fs::read_to_string(&path)
+        {
+            let after = insert_profile(&before, &profile);
+            changes.push(FileChange {
+                path: dirs[0].join(COMMON_AUTH),
+                before,
+                after,
+            });
+        }
+        return Plan {
+            mechanism: Mechanism::PamAuthUpdate,
+            changes,
+        };
+    }
+
+    // Arch splits console login into system-local-login.
+    let login = if dirs
+        .iter()
+        .any(|dir| dir.join("system-local-login").is_file())
+    {
+        "system-local-login"
+    } else {
+        "login"
+    };
+    let mut changes = Vec::new();
+    for service in [login, "sudo", "polkit-1"] {
+        let Some(path) = dirs
+            .iter()
+            .map(|dir| dir.join(service))
+            .find(|p| p.is_file())
+        else {
+            continue;
+        };
+        if !rules_for(&dirs, service).is_empty() {
+            continue;
+        }
+        let Ok(before) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let after = insert_rule(&before);
+        // Vendor files are overridden by a copy in /etc/pam.d.
+        changes.push(FileChange {
+            path: dirs[0].join(service),
+            before,
+            after,
+        });
+    }
+    Plan {
+        mechanism: Mechanism::PamD,
+        changes,
+    }
+}
+
+/// Applies a plan as root, running the distribution's tool or writing
+/// each edited file after keeping a copy of the original. The first copy
+/// is never overwritten, and the new file takes over the mode, owner and
+/// SELinux label of the one it replaces.
+pub fn apply(plan: &Plan) -> Result<(), String> {
+    if let Some(command) = plan.mechanism.command() {
+        let status = std::process::Command::new(command[0])
+            .args(&command[1..])
+            .status()
+            .map_err(|err| format!("{}: {err}", command[0]))?;
+        return if status.success() {
+            Ok(())
+        } else {
+            Err(format!("{}: {status}", command.join(" ")))
+        };
+    }
+
+    for change in &plan.changes {
+        let error = |err: std::io::Error| format!("{}: {err}", change.path.display());
+        let original = std::fs::metadata(&change.path).ok();
+        let backup = with_suffix(&change.path, BACKUP_SUFFIX);
+        if change.path.is_file() && !backup.exists() {
+            std::fs::copy(&change.path, &backup).map_err(error)?;
+        }
+        let temporary = with_suffix(&change.path, NEW_SUFFIX);
+        std::fs::write(&temporary, &change.after).map_err(error)?;
+        if let Some(original) = &original {
+            std::fs::set_permissions(&temporary, original.permissions()).map_err(error)?;
+            std::os::unix::fs::chown(&temporary, Some(original.uid()), Some(original.gid()))
+                .map_err(error)?;
+        }
+        std::fs::rename(&temporary, &change.path).map_err(error)?;
+        restore_context(&change.path)?;
+    }
+    Ok(())
+}
+
+/// Gives a written file the SELinux label its path should have, a file
+/// created elsewhere and renamed keeps the wrong one. Systems without
+/// SELinux have no `restorecon`.
+fn restore_context(path: &Path) -> Result<(), String> {
+    match std::process::Command::new("restorecon").arg(path).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("restorecon {}: {status}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(format!("restorecon: {err}")),
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Maps the exit code of `pkexec` running the helper to a message
+pub fn pkexec_result(code: Option<i32>) -> Result<(), String> {
+    match code {
+        Some(0) => Ok(()),
+        // The authentication dialog was dismissed or failed.
+        Some(126 | 127) => Err(fl!("pam-setup-not-authorized")),
+        Some(code) => Err(fl!("pam-setup-failed", code = code)),
+        None => Err(fl!("pam-setup-failed", code = "?")),
+    }
+}
+
+/// Adds pam_fprintd before the first rule of a service file
+fn insert_rule(content: &str) -> String {
+    let mut lines: Vec<&str> = content.lines().collect();
+    let first_rule = lines
+        .iter()
+        .position(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .unwrap_or(lines.len());
+    lines.insert(first_rule, FPRINTD_RULE);
+    let mut after = lines.join("\n");
+    after.push('\n');
+    after
+}
+
+/// Predicts the lines pam-auth-update adds to common-auth for a profile,
+/// at the top of its managed block
+fn insert_profile(common_auth: &str, profile: &str) -> String {
+    let rules: Vec<String> = profile
+        .lines()
+        .skip_while(|line| line.trim() != "Auth:")
+        .skip(1)
+        .take_while(|line| line.starts_with([' ', '\t']))
+        .map(|line| format!("auth\t{}", line.trim()))
+        .collect();
+
+    let mut lines: Vec<&str> = common_auth.lines().collect();
+    let at = match lines.iter().position(|line| line.contains(PRIMARY_BLOCK)) {
+        Some(marker) => marker + 1,
+        None => lines
+            .iter()
+            .position(|line| line.trim_start().starts_with("auth"))
+            .unwrap_or(lines.len()),
+    };
+    for (offset, rule) in rules.iter().enumerate() {
+        lines.insert(at + offset, rule);
+    }
+    let mut after = lines.join("\n");
+    after.push('\n');
+    after
+}
+
+/// Line diff of two versions of a file, unchanged lines indented by two
+/// spaces
+fn diff(path: &Path, before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Longest common subsequence lengths of every pair of suffixes.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = format!("--- {0}\n+++ {0}\n", path.display());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push_str(&format!("  {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out.push_str(&format!("+ {}\n", new[j]));
+            j += 1;
+        } else {
+            out.push_str(&format!("- {}\n", old[i]));
+            i += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_root(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("enroll-pam-setup-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_plan_authselect() {
+        let root = write_root(
+            "authselect",
+            &[(AUTHSELECT_CONF, "sssd\nwith-silent-lastlog\n")],
+        );
+
+        let plan = plan(&root);
+        assert_eq!(plan.mechanism, Mechanism::Authselect);
+        assert_eq!(
+            plan.changes[0].after,
+            "sssd\nwith-silent-lastlog\nwith-fingerprint\n"
+        );
+        assert!(
+            plan.preview()
+                .starts_with("# authselect enable-feature with-fingerprint\n")
+        );
+        assert!(plan.preview().contains("+ with-fingerprint\n"));
+
+        std::fs::write(root.join(AUTHSELECT_CONF), "sssd\nwith-fingerprint\n").unwrap();
+        assert!(super::plan(&root).is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_plan_pam_auth_update() {
+        let common_auth = "\
+# here are the per-package modules (the \"Primary\" block)
+auth\t[success=1 default=ignore]\tpam_unix.so nullok
+auth\trequisite\tpam_deny.so
+";
+        let profile = "\
+Name: Fingerprint authentication
+Auth-Type: Primary
+Auth:
+\t[success=end default=ignore]\tpam_fprintd.so max-tries=1 timeout=10
+Auth-Initial:
+\t[success=end default=ignore]\tpam_fprintd.so max-tries=1 timeout=10
+";
+        let root = write_root(
+            "pam-auth-update",
+            &[
+                (PAM_CONFIGS_FPRINTD, profile),
+                ("etc/pam.d/common-auth", common_auth),
+            ],
+        );
+
+        let plan = plan(&root);
+        assert_eq!(plan.mechanism, Mechanism::PamAuthUpdate);
+        let after: Vec<&str> = plan.changes[0].after.lines().collect();
+        assert_eq!(
+            after[1],
+            "auth\t[success=end default=ignore]\tpam_fprintd.so max-tries=1 timeout=10"
+        );
+        assert_eq!(after.len(), 4);
+
+        std::fs::write(root.join("etc/pam.d/common-auth"), &plan.changes[0].after).unwrap();
+        assert!(super::plan(&root).is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_plan_and_apply_pam_d() {
+        let root = write_root(
+            "pam-d",
+            &[
+                (
+                    "etc/pam.d/system-local-login",
+                    "#%PAM-1.0\n\nauth include system-login\n",
+                ),
+                ("etc/pam.d/login", "auth include system-local-login\n"),
+                (
+                    "etc/pam.d/sudo",
+                    "#%PAM-1.0\nauth sufficient pam_fprintd.so\n",
+                ),
+                ("usr/lib/pam.d/polkit-1", "auth include system-auth\n"),
+            ],
+        );
+
+        let plan = plan(&root);
+        assert_eq!(plan.mechanism, Mechanism::PamD);
+        let paths: Vec<_> = plan.changes.iter().map(|change| &change.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                &root.join("etc/pam.d/system-local-login"),
+                &root.join("etc/pam.d/polkit-1")
+            ]
+        );
+        assert_eq!(
+            plan.changes[0].after,
+            format!("#%PAM-1.0\n\n{FPRINTD_RULE}\nauth include system-login\n")
+        );
+        assert_eq!(
+            diff(
+                Path::new("x"),
+                &plan.changes[0].before,
+                &plan.changes[0].after
+            ),
+            format!(
+                "--- x\n+++ x\n  #%PAM-1.0\n  \n+ {FPRINTD_RULE}\n  auth include system-login\n"
+            )
+        );
+
+        apply(&plan).unwrap();
+        assert!(super::plan(&root).is_empty());
+        assert_eq!(
+            std::fs::read_to_string(with_suffix(
+                &root.join("etc/pam.d/system-local-login"),
+                BACKUP_SUFFIX
+            ))
+            .unwrap(),
+            "#%PAM-1.0\n\nauth include system-login\n"
+        );
+        assert!(!with_suffix(&root.join("etc/pam.d/polkit-1"), BACKUP_SUFFIX).exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_apply_keeps_mode_and_first_backup() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let original = "#%PAM-1.0\nauth include system-login\n";
+        let root = write_root("apply", &[("etc/pam.d/login", original)]);
+        let path = root.join("etc/pam.d/login");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        apply(&plan(&root)).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Applying again keeps the backup of the original.
+        std::fs::write(&path, "auth include system-login\n").unwrap();
+        apply(&plan(&root)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(with_suffix(&path, BACKUP_SUFFIX)).unwrap(),
+            original
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_pkexec_result() {
+        assert!(pkexec_result(Some(0)).is_ok());
+        assert_eq!(
+            pkexec_result(Some(126)),
+            Err(fl!("pam-setup-not-authorized"))
+        );
+        assert!(pkexec_result(Some(1)).is_err());
+        assert!(pkexec_result(None).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::collections::HashMap;
+
+use zbus::zvariant::Value;
+
+use crate::fl;
+use crate::polkit_dbus::{AuthorityProxy, Subject};
+
+const ENROLL_ACTION: &str = "net.reactivated.fprint.device.enroll";
+const VERIFY_ACTION: &str = "net.reactivated.fprint.device.verify";
+const SET_USERNAME_ACTION: &str = "net.reactivated.fprint.device.setusername";
+/// Our own action, which asks for the admin password every time
+pub const CLEAR_DEVICE_ACTION: &str = "org.cosmic_utils.enroll.clear-device";
+
+/// Lets polkit ask the user to authenticate
+const ALLOW_USER_INTERACTION: u32 = 1;
+/// Polkitd's error for failures other than being refused or cancelled
+const POLKIT_FAILED: &str = "org.freedesktop.PolicyKit1.Error.Failed";
+
+/// Polkit's answer for an action, asked without interaction.
+///
+/// Ordered from least to most restrictive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    #[default]
+    Allowed,
+    /// Allowed after authenticating
+    NeedsAuth,
+    Denied,
+}
+
+impl Access {
+    fn from_result(is_authorized: bool, is_challenge: bool) -> Self {
+        if is_authorized {
+            Access::Allowed
+        } else if is_challenge {
+            Access::NeedsAuth
+        } else {
+            Access::Denied
+        }
+    }
+
+    /// **Returns** the access to something which needs both
+    pub fn and(self, other: Access) -> Access {
+        self.max(other)
+    }
+
+    /// **Returns** the tooltip of a button locked behind this access
+    pub fn localized_hint(&self) -> Option<String> {
+        match self {
+            Access::Allowed => None,
+            Access::NeedsAuth => Some(fl!("auth-required")),
+            Access::Denied => Some(fl!("auth-denied")),
+        }
+    }
+}
+
+/// Polkit's answer when asked to authorize an action
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Authorization {
+    Granted,
+    /// Authentication failed or was dismissed, or polkit is unreachable
+    Refused,
+    /// Polkit does not know the action, so its policy file is not installed
+    NotRegistered,
+}
+
+impl Authorization {
+    /// **Returns** the status shown when clearing the device is not
+    /// authorized, `None` when it is
+    pub fn localized_clear_error(&self) -> Option<String> {
+        match self {
+            Authorization::Granted => None,
+            Authorization::Refused => Some(fl!("clear-device-not-authorized")),
+            Authorization::NotRegistered => Some(fl!(
+                "clear-device-policy-missing",
+                action = CLEAR_DEVICE_ACTION
+            )),
+        }
+    }
+}
+
+/// Access to the fprintd actions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Permissions {
+    pub enroll: Access,
+    pub verify: Access,
+    /// Needed by fprintd on top of the others to act on another user
+    pub set_username: Access,
+}
+
+impl Permissions {
+    /// **Returns** the access to enroll or delete prints of a user
+    pub fn manage(&self, is_current_user: bool) -> Access {
+        self.for_user(self.enroll, is_current_user)
+    }
+
+    /// **Returns** the access to verify prints of a user
+    pub fn verify(&self, is_current_user: bool) -> Access {
+        self.for_user(self.verify, is_current_user)
+    }
+
+    /// Whether only the current user's prints should be shown. The system
+    /// policy decides when it allows or denies acting on other users,
+    /// otherwise only administrators get to see everyone.
+    pub fn restricts(&self, is_admin: bool) -> bool {
+        match self.set_username {
+            Access::Allowed => false,
+            Access::NeedsAuth => !is_admin,
+            Access::Denied => true,
+        }
+    }
+
+    fn for_user(&self, access: Access, is_current_user: bool) -> Access {
+        if is_current_user {
+            access
+        } else {
+            access.and(self.set_username)
+        }
+    }
+}
+
+/// Asks polkit whether fprintd would allow this connection its actions,
+/// without prompting. An action polkit does not answer for counts as
+/// allowed, so fprintd still has the last word.
+///
+/// **Returns** the access to each action
+pub async fn check(connection: &zbus::Connection) -> Permissions {
+    let Some(subject) = subject(connection) else {
+        return Permissions::default();
+    };
+    let authority = match AuthorityProxy::new(connection).await {
+        Ok(authority) => authority,
+        Err(err) => {
+            tracing::warn!(%err, "polkit is not available");
+            return Permissions::default();
+        }
+    };
+
+    let check = async |action: &str| match authority
+        .check_authorization(&subject, action, HashMap::new(), 0, "")
+        .await
+    {
+        Ok((is_authorized, is_challenge, _)) => Access::from_result(is_authorized, is_challenge),
+        Err(err) => {
+            tracing::warn!(%err, action, "polkit check failed");
+            Access::Allowed
+        }
+    };
+
+    Permissions {
+        enroll: check(ENROLL_ACTION).await,
+        verify: check(VERIFY_ACTION).await,
+        set_username: check(SET_USERNAME_ACTION).await,
+    }
+}
+
+/// Asks polkit to authorize `action` for this connection, prompting the
+/// user if its policy says so. Unlike [`check`] this fails closed.
+///
+/// **Returns** polkit's answer, telling apart an action it does not know
+pub async fn authorize(connection: &zbus::Connection, action: &str) -> Authorization {
+    let Some(subject) = subject(connection) else {
+        return Authorization::Refused;
+    };
+    let result = match AuthorityProxy::new(connection).await {
+        Ok(authority) => {
+            authority
+                .check_authorization(&subject, action, HashMap::new(), ALLOW_USER_INTERACTION, "")
+                .await
+        }
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok((true, _, _)) => Authorization::Granted,
+        Ok((false, _, _)) => Authorization::Refused,
+        Err(err) if is_not_registered(&err) => {
+            tracing::error!(%err, action, "polkit action is not registered, install its policy file");
+            Authorization::NotRegistered
+        }
+        Err(err) => {
+            tracing::warn!(%err, action, "polkit authorization failed");
+            Authorization::Refused
+        }
+    }
+}
+
+/// Whether polkit failed because it has no policy for the action. Polkitd
+/// answers with its generic `Failed` error, which for our own bus name
+/// without details or cancellation means an unknown action.
+fn is_not_registered(err: &zbus::Error) -> bool {
+    matches!(err, zbus::Error::MethodError(name, _, _) if name.as_str() == POLKIT_FAILED)
+}
+
+/// **Returns** this connection as polkit subject
+fn subject(connection: &zbus::Connection) -> Option<Subject<'_>> {
+    let name = connection.unique_name()?;
+    Some((
+        "system-bus-name",
+        HashMap::from([("name", Value::from(name.as_str()))]),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_from_result() {
+        assert_eq!(Access::from_result(true, false), Access::Allowed);
+        assert_eq!(Access::from_result(false, true), Access::NeedsAuth);
+        assert_eq!(Access::from_result(false, false), Access::Denied);
+    }
+
+    #[test]
+    fn test_is_not_registered() {
+        use zbus::message::Message;
+        use zbus::names::ErrorName;
+
+        fn method_error(name: &str) -> zbus::Error {
+            let msg = Message::method_call("/", "Ping")
+                .unwrap()
+                .destination("org.freedesktop.DBus")
+                .unwrap()
+                .build(&())
+                .unwrap();
+            let error_name = ErrorName::try_from(name).unwrap();
+            // Polkitd's message is translated, only the name is matched.
+            zbus::Error::MethodError(error_name.into(), Some("Aktion unbekannt".to_string()), msg)
+        }
+
+        assert!(is_not_registered(&method_error(POLKIT_FAILED)));
+        assert!(!is_not_registered(&method_error(
+            "org.freedesktop.PolicyKit1.Error.NotAuthorized"
+        )));
+        assert!(!is_not_registered(&zbus::Error::Failure(format!(
+            "Action {CLEAR_DEVICE_ACTION} is not registered"
+        ))));
+        assert_eq!(
+            Authorization::NotRegistered.localized_clear_error(),
+            Some(fl!(
+                "clear-device-policy-missing",
+                action = CLEAR_DEVICE_ACTION
+            ))
+        );
+        assert_eq!(Authorization::Granted.localized_clear_error(), None);
+    }
+
+    #[test]
+    fn test_permissions_for_other_users() {
+        let permissions = Permissions {
+            enroll: Access::Allowed,
+            verify: Access::NeedsAuth,
+            set_username: Access::NeedsAuth,
+        };
+        assert_eq!(permissions.manage(true), Access::Allowed);
+        assert_eq!(permissions.manage(false), Access::NeedsAuth);
+        assert_eq!(permissions.verify(true), Access::NeedsAuth);
+
+        let permissions = Permissions {
+            set_username: Access::Denied,
+            ..permissions
+        };
+        assert_eq!(permissions.manage(true), Access::Allowed);
+        assert_eq!(permissions.verify(false), Access::Denied);
+    }
+
+    #[test]
+    fn test_restricts() {
+        let permissions = Permissions::default();
+        assert!(!permissions.restricts(false));
+
+        let permissions = Permissions {
+            set_username: Access::NeedsAuth,
+            ..permissions
+        };
+        assert!(permissions.restricts(false));
+        assert!(!permissions.restricts(true));
+
+        let permissions = Permissions {
+            set_username: Access::Denied,
+            ..permissions
+        };
+        assert!(permissions.restricts(true));
+    }
+}
@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::app::error::AppError;
+use crate::fl;
+use crate::systemd_dbus::{ManagerProxy, UnitProxy};
+
+/// Units providing the fingerprint service, in the order they are looked up
+const UNITS: [&str; 2] = ["fprintd.service", "open-fprintd.service"];
+
+/// What systemd knows about the fingerprint service
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ServiceState {
+    /// Running, started on demand or unknown to systemd
+    #[default]
+    Available,
+    /// Neither fprintd nor open-fprintd is installed
+    NotInstalled,
+    /// The unit failed to start, or cannot be started
+    Failed(String),
+    /// A start of the failed unit was requested
+    Starting(String),
+}
+
+impl ServiceState {
+    /// Reads the state of unit `name` from its `LoadState` and `ActiveState`
+    fn from_unit(name: &str, load_state: &str, active_state: &str) -> Self {
+        match (load_state, active_state) {
+            ("not-found", _) => ServiceState::NotInstalled,
+            ("masked" | "error" | "bad-setting", _) | (_, "failed") => {
+                ServiceState::Failed(name.to_string())
+            }
+            _ => ServiceState::Available,
+        }
+    }
+
+    /// **Returns** why no device can be found when the service is at fault
+    pub fn localized_message(&self) -> Option<String> {
+        match self {
+            ServiceState::Available => None,
+            ServiceState::NotInstalled => Some(AppError::ServiceNotInstalled.localized_message()),
+            ServiceState::Failed(_) => Some(AppError::ServiceFailed.localized_message()),
+            ServiceState::Starting(_) => Some(fl!("service-starting")),
+        }
+    }
+}
+
+/// Asks systemd about the units of the fingerprint service. Without
+/// systemd the service counts as available, leaving the error of looking
+/// for a device to speak for itself.
+///
+/// **Returns** the state of the first installed unit
+pub async fn check(connection: &zbus::Connection) -> ServiceState {
+    let manager = match ManagerProxy::new(connection).await {
+        Ok(manager) => manager,
+        Err(err) => {
+            tracing::warn!(%err, "systemd is not available");
+            return ServiceState::Available;
+        }
+    };
+
+    for name in UNITS {
+        match unit_state(connection, &manager, name).await {
+            Ok(ServiceState::NotInstalled) => continue,
+            Ok(state) => return state,
+            Err(err) => {
+                tracing::warn!(%err, name, "cannot read the unit state");
+                return ServiceState::Available;
+            }
+        }
+    }
+    ServiceState::NotInstalled
+}
+
+async fn unit_state(
+    connection: &zbus::Connection,
+    manager: &ManagerProxy<'_>,
+    name: &str,
+) -> zbus::Result<ServiceState> {
+    let unit = UnitProxy::builder(connection)
+        .path(manager.load_unit(name).await?)?
+        .build()
+        .await?;
+    Ok(ServiceState::from_unit(
+        name,
+        &unit.load_state().await?,
+        &unit.active_state().await?,
+    ))
+}
+
+/// Starts a failed unit through systemd, which asks polkit to authorize it
+/// and may prompt for an administrator's password.
+/// # Errors
+/// ***AppError::PermissionDenied:***
+/// if the authentication was dismissed or failed
+pub async fn start(connection: &zbus::Connection, unit: &str) -> Result<(), AppError> {
+    let manager = ManagerProxy::new(connection).await?;
+    // A unit which failed too often in a row refuses to start until reset.
+    manager.reset_failed_unit(unit).await?;
+    manager.start_unit(unit, "replace").await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_unit() {
+        let state = |load, active| ServiceState::from_unit("fprintd.service", load, active);
+
+        assert_eq!(state("loaded", "inactive"), ServiceState::Available);
+        assert_eq!(state("loaded", "active"), ServiceState::Available);
+        assert_eq!(state("not-found", "inactive"), ServiceState::NotInstalled);
+        assert_eq!(
+            state("loaded", "failed"),
+            ServiceState::Failed("fprintd.service".to_string())
+        );
+        assert_eq!(
+            state("masked", "inactive"),
+            ServiceState::Failed("fprintd.service".to_string())
+        );
+    }
+
+    #[test]
+    fn test_localized_message() {
+        assert_eq!(ServiceState::Available.localized_message(), None);
+        assert_eq!(
+            ServiceState::NotInstalled.localized_message(),
+            Some(AppError::ServiceNotInstalled.localized_message())
+        );
+        assert_eq!(
+            ServiceState::Failed("fprintd.service".to_string()).localized_message(),
+            Some(AppError::ServiceFailed.localized_message())
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::app::polkit::Access;
+use crate::app::view::lock_icon;
 use crate::app::{AppModel, message::Message};
 use crate::config::{AppTheme, Config};
 use crate::fl;
@@ -12,16 +14,24 @@ impl AppModel {
     /// Settings menu
     pub fn settings(&self) -> Element<'_, Message> {
         let cosmic_theme::Spacing { space_xs, .. } = theme::active().cosmic().spacing;
-        let clear_btn = button::text(fl!("clear-device")).tooltip(fl!("clear-tooltip"));
+        let access = self.clear_access();
+        let mut clear_btn = button::text(fl!("clear-device")).tooltip(
+            access
+                .localized_hint()
+                .unwrap_or_else(|| fl!("clear-tooltip")),
+        );
+        if access == Access::NeedsAuth {
+            clear_btn = clear_btn.trailing_icon(lock_icon());
+        }
 
         let clear_btn =
-            if !self.busy && self.device_path.is_some() && self.enrolling_finger.is_none() {
+            if !self.state.is_busy() && self.device_path.is_some() && access != Access::Denied {
                 clear_btn.on_press(Message::ClearDevice)
             } else {
                 clear_btn
             };
 
-        let theme_section = section()
+        let mut theme_section = section()
             .title(fl!("settings-ui"))
             .add(
                 builder(fl!("settings-theme"))
@@ -55,14 +65,39 @@ impl AppModel {
                     checkbox(self.config.experimental_ui)
                         .on_toggle(|value| {
                             Message::UpdateConfig(Config {
-                                app_theme: self.config.app_theme,
                                 experimental_ui: value,
+                                ..self.config.clone()
                             })
                         })
                         .label(fl!("alternative-ui")),
                 ),
+            )
+            .add(
+                builder(fl!("show-all-users")).control(
+                    checkbox(self.config.show_all_users)
+                        .on_toggle(|value| {
+                            Message::UpdateConfig(Config {
+                                show_all_users: value,
+                                ..self.config.clone()
+                            })
+                        })
+                        .label(fl!("show-all-users-description")),
+                ),
             );
 
+        for username in &self.config.remembered_users {
+            let mut config = self.config.clone();
+            config.remembered_users.retain(|name| name != username);
+            theme_section = theme_section.add(
+                builder(username)
+                    .description(fl!("remembered-user"))
+                    .control(
+                        button::standard(fl!("forget-user"))
+                            .on_press(Message::UpdateConfig(config)),
+                    ),
+            );
+        }
+
         let device_count = self.devices.len();
 
         let mut device_section = section().title(fl!("settings-device", nbr = device_count));
@@ -81,15 +116,40 @@ impl AppModel {
             ));
         }
 
-        let clear_section = section()
-            .title(fl!("danger"))
-            .add(builder(fl!("settings-clear-device")).control(item_row(vec![clear_btn.into()])));
+        let doctor_btn = if self.diagnosing {
+            button::standard(fl!("doctor-running"))
+        } else {
+            button::standard(fl!("doctor-run")).on_press(Message::RunDiagnostics)
+        };
+
+        let mut doctor_section = section()
+            .title(fl!("doctor"))
+            .add(item_row(vec![doctor_btn.into()]));
 
-        let col = Column::new()
+        for check in &self.diagnostics {
+            let description = match &check.hint {
+                Some(hint) => format!("{}\n{hint}", check.detail),
+                None => check.detail.clone(),
+            };
+            doctor_section = doctor_section.add(
+                builder(check.kind.localized_name())
+                    .description(description)
+                    .control(text::heading(check.status.localized_name())),
+            );
+        }
+
+        let mut col = Column::new()
             .push(theme_section)
             .push(device_section)
-            .push(clear_section)
+            .push(doctor_section)
             .spacing(space_xs);
+
+        // Clearing deletes the prints of other users too.
+        if !self.is_restricted() {
+            col = col.push(section().title(fl!("danger")).add(
+                builder(fl!("settings-clear-device")).control(item_row(vec![clear_btn.into()])),
+            ));
+        }
         view_column(vec![col.into()]).into()
     }
 }
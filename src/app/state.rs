@@ -0,0 +1,370 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::sync::Arc;
+
+use crate::app::finger::Finger;
+
+/// Dialogs which block the UI until the user answers them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Confirmation {
+    /// Delete prints of every known user
+    ClearDevice,
+    /// Delete all prints of the selected user because single-finger delete
+    /// is unsupported (e.g. open-fprintd)
+    DeleteAll,
+    /// Delete the prints selected in the overview
+    DeleteSelected,
+    /// Apply the previewed PAM changes enabling pam_fprintd
+    EnablePam,
+    /// Remove prints although PAM services depend on fingerprints
+    LastPrint(Removal),
+}
+
+/// Prints whose removal may leave a user without any
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Removal {
+    /// The selected finger, the only one enrolled
+    Finger,
+    /// Every print of the selected user
+    AllFingers,
+    /// Every print on the device
+    Device,
+    /// The prints selected in the overview, all of some user's among them
+    Selection,
+}
+
+/// What the application is currently doing.
+///
+/// Changed only through [`OperationState::transition`] so that handlers
+/// cannot leave stale flags behind.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum OperationState {
+    /// Connecting, searching for a device or none was found
+    #[default]
+    Unavailable,
+    /// Waiting for user input
+    Idle,
+    /// Switching to another device
+    SelectingDevice,
+    /// Enrolling a finger; `stage` counts the passed enroll stages
+    Enrolling { finger: Arc<String>, stage: u32 },
+    /// Verifying a finger
+    Verifying { finger: Finger },
+    /// Deleting one or all prints of the selected user, or those selected
+    /// in the overview
+    Deleting,
+    /// Waiting for the user to authenticate before clearing the device
+    AuthorizingClear,
+    /// Deleting prints of every known user
+    Clearing,
+    /// Waiting for the privileged helper to change the PAM configuration
+    ConfiguringPam,
+    /// Showing a confirmation dialog
+    AwaitingConfirmation(Confirmation),
+}
+
+/// A rejected state change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub from: OperationState,
+    pub to: OperationState,
+}
+
+impl OperationState {
+    /// Whether an operation blocks starting another one
+    pub fn is_busy(&self) -> bool {
+        !matches!(self, Self::Idle | Self::AwaitingConfirmation(_))
+    }
+
+    /// **Returns** the finger id being enrolled
+    pub fn enrolling_finger(&self) -> Option<&Arc<String>> {
+        match self {
+            Self::Enrolling { finger, .. } => Some(finger),
+            _ => None,
+        }
+    }
+
+    /// **Returns** the finger being verified
+    pub fn verifying_finger(&self) -> Option<Finger> {
+        match self {
+            Self::Verifying { finger } => Some(*finger),
+            _ => None,
+        }
+    }
+
+    /// **Returns** the dialog waiting for an answer
+    pub fn confirmation(&self) -> Option<Confirmation> {
+        match self {
+            Self::AwaitingConfirmation(confirmation) => Some(*confirmation),
+            _ => None,
+        }
+    }
+
+    /// Whether `next` may follow the current state
+    pub fn can_transition(&self, next: &OperationState) -> bool {
+        use OperationState::*;
+
+        match (self, next) {
+            // Completion, errors and losing the device end any operation.
+            (_, Idle | Unavailable) => true,
+            (
+                Idle,
+                SelectingDevice
+                | Enrolling { .. }
+                | Verifying { .. }
+                | Deleting
+                | AwaitingConfirmation(
+                    Confirmation::ClearDevice
+                    | Confirmation::DeleteAll
+                    | Confirmation::DeleteSelected
+                    | Confirmation::EnablePam
+                    | Confirmation::LastPrint(Removal::Finger),
+                ),
+            ) => true,
+            // Enroll progress for the same finger.
+            (Enrolling { finger, .. }, Enrolling { finger: next, .. }) => finger == next,
+            // Single-finger delete unsupported, offer deleting all.
+            (Deleting, AwaitingConfirmation(Confirmation::DeleteAll)) => true,
+            (
+                AwaitingConfirmation(Confirmation::DeleteAll | Confirmation::DeleteSelected),
+                Deleting,
+            ) => true,
+            (AwaitingConfirmation(Confirmation::ClearDevice), AuthorizingClear) => true,
+            (AuthorizingClear, Clearing) => true,
+            // Warn about a lockout before removing the last prints.
+            (
+                AwaitingConfirmation(Confirmation::DeleteAll),
+                AwaitingConfirmation(Confirmation::LastPrint(Removal::AllFingers)),
+            ) => true,
+            (
+                AwaitingConfirmation(Confirmation::ClearDevice),
+                AwaitingConfirmation(Confirmation::LastPrint(Removal::Device)),
+            ) => true,
+            (
+                AwaitingConfirmation(Confirmation::DeleteSelected),
+                AwaitingConfirmation(Confirmation::LastPrint(Removal::Selection)),
+            ) => true,
+            (
+                AwaitingConfirmation(Confirmation::LastPrint(
+                    Removal::Finger | Removal::AllFingers | Removal::Selection,
+                )),
+                Deleting,
+            ) => true,
+            (AwaitingConfirmation(Confirmation::LastPrint(Removal::Device)), AuthorizingClear) => {
+                true
+            }
+            (AwaitingConfirmation(Confirmation::EnablePam), ConfiguringPam) => true,
+            _ => false,
+        }
+    }
+
+    /// Moves to `next` when allowed, otherwise leaves the state untouched.
+    pub fn transition(&mut self, next: OperationState) -> Result<(), InvalidTransition> {
+        if self.can_transition(&next) {
+            *self = next;
+            Ok(())
+        } else {
+            Err(InvalidTransition {
+                from: self.clone(),
+                to: next,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enrolling(finger: &str, stage: u32) -> OperationState {
+        OperationState::Enrolling {
+            finger: Arc::new(finger.to_string()),
+            stage,
+        }
+    }
+
+    #[test]
+    fn test_is_busy() {
+        assert!(OperationState::Unavailable.is_busy());
+        assert!(!OperationState::Idle.is_busy());
+        assert!(OperationState::SelectingDevice.is_busy());
+        assert!(enrolling("left-thumb", 0).is_busy());
+        assert!(
+            OperationState::Verifying {
+                finger: Finger::LeftThumb
+            }
+            .is_busy()
+        );
+        assert!(OperationState::Deleting.is_busy());
+        assert!(OperationState::AuthorizingClear.is_busy());
+        assert!(OperationState::Clearing.is_busy());
+        assert!(OperationState::ConfiguringPam.is_busy());
+        assert!(!OperationState::AwaitingConfirmation(Confirmation::ClearDevice).is_busy());
+    }
+
+    #[test]
+    fn test_accessors() {
+        let state = enrolling("left-thumb", 2);
+        assert_eq!(
+            state.enrolling_finger().map(|f| f.as_str()),
+            Some("left-thumb")
+        );
+        assert_eq!(state.verifying_finger(), None);
+
+        let state = OperationState::Verifying {
+            finger: Finger::RightRing,
+        };
+        assert_eq!(state.verifying_finger(), Some(Finger::RightRing));
+        assert!(state.enrolling_finger().is_none());
+
+        let state = OperationState::AwaitingConfirmation(Confirmation::DeleteAll);
+        assert_eq!(state.confirmation(), Some(Confirmation::DeleteAll));
+        assert_eq!(OperationState::Idle.confirmation(), None);
+    }
+
+    #[test]
+    fn test_operations_start_only_when_idle() {
+        let starts = [
+            OperationState::SelectingDevice,
+            enrolling("left-thumb", 0),
+            OperationState::Verifying {
+                finger: Finger::LeftThumb,
+            },
+            OperationState::Deleting,
+            OperationState::AwaitingConfirmation(Confirmation::ClearDevice),
+            OperationState::AwaitingConfirmation(Confirmation::DeleteSelected),
+            OperationState::AwaitingConfirmation(Confirmation::EnablePam),
+        ];
+
+        for start in starts {
+            assert!(OperationState::Idle.can_transition(&start), "{start:?}");
+            assert!(
+                !OperationState::Unavailable.can_transition(&start),
+                "{start:?}"
+            );
+            assert!(
+                !OperationState::Clearing.can_transition(&start),
+                "{start:?}"
+            );
+        }
+        // Enrolling and verifying cannot overlap.
+        assert!(
+            !enrolling("left-thumb", 1).can_transition(&OperationState::Verifying {
+                finger: Finger::LeftThumb
+            })
+        );
+    }
+
+    #[test]
+    fn test_any_state_can_finish() {
+        let states = [
+            OperationState::Unavailable,
+            OperationState::Idle,
+            OperationState::SelectingDevice,
+            enrolling("left-thumb", 3),
+            OperationState::Verifying {
+                finger: Finger::LeftThumb,
+            },
+            OperationState::Deleting,
+            OperationState::AuthorizingClear,
+            OperationState::Clearing,
+            OperationState::ConfiguringPam,
+            OperationState::AwaitingConfirmation(Confirmation::DeleteAll),
+        ];
+
+        for state in states {
+            assert!(state.can_transition(&OperationState::Idle), "{state:?}");
+            assert!(
+                state.can_transition(&OperationState::Unavailable),
+                "{state:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_enroll_progress_keeps_finger() {
+        let mut state = enrolling("left-thumb", 0);
+        assert!(state.transition(enrolling("left-thumb", 1)).is_ok());
+        assert_eq!(state, enrolling("left-thumb", 1));
+
+        let err = state.transition(enrolling("right-thumb", 0)).unwrap_err();
+        assert_eq!(err.to, enrolling("right-thumb", 0));
+        assert_eq!(state, enrolling("left-thumb", 1));
+    }
+
+    #[test]
+    fn test_confirmations() {
+        let mut state = OperationState::Idle;
+        assert!(
+            state
+                .transition(OperationState::AwaitingConfirmation(
+                    Confirmation::ClearDevice
+                ))
+                .is_ok()
+        );
+        // The clear dialog cannot jump to deleting a single user, nor skip
+        // authentication.
+        assert!(state.transition(OperationState::Deleting).is_err());
+        assert!(state.transition(OperationState::Clearing).is_err());
+        assert!(state.transition(OperationState::AuthorizingClear).is_ok());
+        assert!(state.transition(OperationState::Clearing).is_ok());
+        assert!(!OperationState::Idle.can_transition(&OperationState::AuthorizingClear));
+
+        let mut state = OperationState::Deleting;
+        assert!(
+            state
+                .transition(OperationState::AwaitingConfirmation(
+                    Confirmation::DeleteAll
+                ))
+                .is_ok()
+        );
+        assert!(state.transition(OperationState::Clearing).is_err());
+        assert!(state.transition(OperationState::Deleting).is_ok());
+
+        // Offered up front when the daemon cannot delete a single print.
+        let mut state = OperationState::Idle;
+        assert!(
+            state
+                .transition(OperationState::AwaitingConfirmation(
+                    Confirmation::DeleteAll
+                ))
+                .is_ok()
+        );
+        assert!(state.transition(OperationState::Deleting).is_ok());
+
+        let mut state = OperationState::AwaitingConfirmation(Confirmation::EnablePam);
+        assert!(state.transition(OperationState::Clearing).is_err());
+        assert!(state.transition(OperationState::ConfiguringPam).is_ok());
+    }
+
+    #[test]
+    fn test_last_print_warnings() {
+        let warning =
+            |removal| OperationState::AwaitingConfirmation(Confirmation::LastPrint(removal));
+
+        let mut state = OperationState::Idle;
+        assert!(state.transition(warning(Removal::Finger)).is_ok());
+        assert!(state.transition(OperationState::Clearing).is_err());
+        assert!(state.transition(OperationState::Deleting).is_ok());
+
+        let mut state = OperationState::AwaitingConfirmation(Confirmation::DeleteAll);
+        assert!(state.transition(warning(Removal::Device)).is_err());
+        assert!(state.transition(warning(Removal::AllFingers)).is_ok());
+        assert!(state.transition(OperationState::Deleting).is_ok());
+
+        let mut state = OperationState::AwaitingConfirmation(Confirmation::ClearDevice);
+        assert!(state.transition(warning(Removal::Device)).is_ok());
+        assert!(state.transition(OperationState::Deleting).is_err());
+        assert!(state.transition(OperationState::AuthorizingClear).is_ok());
+
+        let mut state = OperationState::AwaitingConfirmation(Confirmation::DeleteSelected);
+        assert!(state.transition(warning(Removal::AllFingers)).is_err());
+        assert!(state.transition(warning(Removal::Selection)).is_ok());
+        assert!(state.transition(OperationState::Clearing).is_err());
+        assert!(state.transition(OperationState::Deleting).is_ok());
+
+        // Removing everything is always confirmed before the warning.
+        assert!(!OperationState::Idle.can_transition(&warning(Removal::Device)));
+        assert!(!OperationState::Idle.can_transition(&warning(Removal::Selection)));
+    }
+}
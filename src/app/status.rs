@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::fl;
+
+/// Maps an fprintd `EnrollStatus` result to a localized string.
+///
+/// Unknown results are passed through unchanged.
+pub fn enroll_status_text(status: &str) -> String {
+    match status {
+        "enroll-stage-passed" => fl!("enroll-stage-passed"),
+        "enroll-retry-scan" => fl!("enroll-retry-scan"),
+        "enroll-swipe-too-short" => fl!("enroll-swipe-too-short"),
+        "enroll-finger-not-centered" => fl!("enroll-finger-not-centered"),
+        "enroll-remove-and-retry" => fl!("enroll-remove-and-retry"),
+        "enroll-unknown-error" => fl!("enroll-unknown-error"),
+        "enroll-completed" => fl!("enroll-completed"),
+        "enroll-failed" => fl!("enroll-failed"),
+        "enroll-disconnected" => fl!("enroll-disconnected"),
+        "enroll-data-full" => fl!("enroll-data-full"),
+        "enroll-too-fast" => fl!("enroll-too-fast"),
+        "enroll-duplicate" => fl!("enroll-duplicate"),
+        "enroll-cancelled" => fl!("enroll-cancelled"),
+        _ => status.to_string(),
+    }
+}
+
+/// Maps an fprintd `VerifyStatus` result to a localized string.
+///
+/// Unknown results are passed through unchanged.
+pub fn verify_status_text(status: &str) -> String {
+    match status {
+        "verify-match" => fl!("verify-match"),
+        "verify-no-match" => fl!("verify-no-match"),
+        "verify-retry-scan" => fl!("verify-retry-scan"),
+        "verify-swipe-too-short" => fl!("verify-swipe-too-short"),
+        "verify-finger-not-centered" => fl!("verify-finger-not-centered"),
+        "verify-remove-and-retry" => fl!("verify-remove-and-retry"),
+        "verify-too-fast" => fl!("verify-too-fast"),
+        "verify-disconnected" => fl!("verify-disconnected"),
+        "verify-unknown-error" => fl!("verify-unknown-error"),
+        "verify-cancelled" => fl!("verify-cancelled"),
+        _ => status.to_string(),
+    }
+}
+
+/// How a finger is scanned, from the device's `scan-type` property
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScanType {
+    /// The finger is laid on the sensor
+    #[default]
+    Press,
+    /// The finger is swiped across the sensor
+    Swipe,
+}
+
+impl ScanType {
+    /// **Returns** the scan type of a `scan-type` value, `press` unless it is `swipe`
+    pub fn from_property(value: &str) -> Self {
+        match value {
+            "swipe" => ScanType::Swipe,
+            _ => ScanType::Press,
+        }
+    }
+
+    /// **Returns** the localized instruction for scanning a finger
+    pub fn localized_instruction(self) -> String {
+        match self {
+            ScanType::Press => fl!("sensor-touch"),
+            ScanType::Swipe => fl!("sensor-swipe"),
+        }
+    }
+}
+
+/// State of the sensor while enrolling or verifying, as published by fprintd
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sensor {
+    pub scan_type: ScanType,
+    /// A finger is on the sensor
+    pub finger_present: bool,
+    /// The device waits for a finger to scan
+    pub finger_needed: bool,
+}
+
+impl Sensor {
+    /// **Returns** the localized text shown next to the progress: whether a
+    /// finger was detected, or how to scan one while the device waits for it
+    pub fn localized_indicator(&self) -> Option<String> {
+        if self.finger_present {
+            Some(fl!("sensor-finger-detected"))
+        } else if self.finger_needed {
+            Some(self.scan_type.localized_instruction())
+        } else {
+            None
+        }
+    }
+}
@@ -1,33 +1,32 @@
 use crate::app::{
-    Message,
-    error::AppError,
-    finger::Finger,
-    fprint::{enroll_fingerprint_process, verify_finger_process},
+    Message, backend::FingerprintBackend, finger::Finger, fprint::watch_service,
+    hotplug::watch_devices, users::watch_users,
 };
 use ashpd::desktop::settings::{ColorScheme, Settings};
 use cosmic::iced::{
     Event, Subscription, futures::channel::mpsc::Sender, keyboard, stream::channel,
 };
 use futures_util::{SinkExt, StreamExt};
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub(crate) struct VerifyData {
-    device_path: std::sync::Arc<zbus::zvariant::OwnedObjectPath>,
-    connection: zbus::Connection,
-    username: std::sync::Arc<String>,
+    device_path: Arc<zbus::zvariant::OwnedObjectPath>,
+    backend: Arc<dyn FingerprintBackend>,
+    username: Arc<String>,
     finger: Finger,
 }
 
 impl VerifyData {
     pub(crate) fn new(
-        device_path: std::sync::Arc<zbus::zvariant::OwnedObjectPath>,
-        connection: zbus::Connection,
-        username: std::sync::Arc<String>,
+        device_path: Arc<zbus::zvariant::OwnedObjectPath>,
+        backend: Arc<dyn FingerprintBackend>,
+        username: Arc<String>,
         finger: Finger,
     ) -> Self {
         Self {
             device_path,
-            connection,
+            backend,
             username,
             finger,
         }
@@ -43,23 +42,23 @@ impl std::hash::Hash for VerifyData {
 
 #[derive(Clone)]
 pub(crate) struct EnrollData {
-    finger_name: std::sync::Arc<String>,
-    device_path: std::sync::Arc<zbus::zvariant::OwnedObjectPath>,
-    connection: zbus::Connection,
-    username: std::sync::Arc<String>,
+    finger_name: Arc<String>,
+    device_path: Arc<zbus::zvariant::OwnedObjectPath>,
+    backend: Arc<dyn FingerprintBackend>,
+    username: Arc<String>,
 }
 
 impl EnrollData {
     pub(crate) fn new(
-        finger_name: std::sync::Arc<String>,
-        device_path: std::sync::Arc<zbus::zvariant::OwnedObjectPath>,
-        connection: zbus::Connection,
-        username: std::sync::Arc<String>,
+        finger_name: Arc<String>,
+        device_path: Arc<zbus::zvariant::OwnedObjectPath>,
+        backend: Arc<dyn FingerprintBackend>,
+        username: Arc<String>,
     ) -> Self {
         Self {
             finger_name,
             device_path,
-            connection,
+            backend,
             username,
         }
     }
@@ -77,22 +76,17 @@ pub(crate) fn enroll_subscription(data: EnrollData) -> Subscription<Message> {
     Subscription::run_with(data, |data| {
         let data = data.clone();
         channel(100, move |mut output: Sender<Message>| async move {
-            // Implement enrollment stream here
-            match enroll_fingerprint_process(
-                data.connection,
-                &data.device_path,
-                &data.finger_name,
-                &data.username,
-                &mut output,
-            )
-            .await
+            if let Err(e) = data
+                .backend
+                .enroll(
+                    (*data.device_path).clone(),
+                    (*data.finger_name).clone(),
+                    (*data.username).clone(),
+                    output.clone(),
+                )
+                .await
             {
-                Ok(_) => {}
-                Err(e) => {
-                    let _ = output
-                        .send(Message::OperationError(AppError::from(e)))
-                        .await;
-                }
+                let _ = output.send(Message::OperationError(e)).await;
             }
             futures_util::future::pending().await
         })
@@ -104,27 +98,83 @@ pub(crate) fn verify_subscription(data: VerifyData) -> Subscription<Message> {
     Subscription::run_with(data, |data| {
         let data = data.clone();
         channel(100, move |mut output: Sender<Message>| async move {
-            match verify_finger_process(
-                data.connection,
-                &data.device_path,
-                data.finger.as_finger_id(),
-                &data.username,
-                &mut output,
-            )
-            .await
+            if let Err(e) = data
+                .backend
+                .verify(
+                    (*data.device_path).clone(),
+                    data.finger.as_finger_id().to_string(),
+                    (*data.username).clone(),
+                    output.clone(),
+                )
+                .await
             {
-                Ok(_) => {}
-                Err(e) => {
-                    let _ = output
-                        .send(Message::OperationError(AppError::from(e)))
-                        .await;
-                }
+                let _ = output.send(Message::OperationError(e)).await;
             }
             futures_util::future::pending().await
         })
     })
 }
 
+/// Connection to watch accounts-daemon on, told apart by its unique name
+#[derive(Clone)]
+pub(crate) struct UsersWatch(zbus::Connection);
+
+impl std::hash::Hash for UsersWatch {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.unique_name().map(|name| name.as_str()).hash(state);
+    }
+}
+
+/// **Returns** a subscription keeping the user list in sync with accounts-daemon
+pub fn users_subscription(connection: zbus::Connection) -> Subscription<Message> {
+    Subscription::run_with(UsersWatch(connection), |watch| {
+        let connection = watch.0.clone();
+        channel(10, async move |mut output: Sender<Message>| {
+            if let Err(err) = watch_users(&connection, &mut output).await {
+                tracing::warn!("Not watching accounts-daemon for user changes: {err}");
+            }
+            std::future::pending::<()>().await;
+        })
+    })
+}
+
+/// **Returns** a subscription to USB devices being plugged in or removed
+pub fn hotplug_subscription() -> Subscription<Message> {
+    struct HotplugSubscription;
+
+    Subscription::run_with(std::any::TypeId::of::<HotplugSubscription>(), |_| {
+        channel(10, async move |mut output: Sender<Message>| {
+            if let Err(err) = watch_devices(&mut output).await {
+                tracing::warn!("Not watching for fingerprint readers being plugged in: {err}");
+            }
+            std::future::pending::<()>().await;
+        })
+    })
+}
+
+/// Connection to watch fprintd on, told apart by its unique name
+#[derive(Clone)]
+pub(crate) struct FprintdWatch(zbus::Connection);
+
+impl std::hash::Hash for FprintdWatch {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.unique_name().map(|name| name.as_str()).hash(state);
+    }
+}
+
+/// **Returns** a subscription to fprintd leaving and joining the bus
+pub(crate) fn fprintd_subscription(connection: zbus::Connection) -> Subscription<Message> {
+    Subscription::run_with(FprintdWatch(connection), |watch| {
+        let connection = watch.0.clone();
+        channel(10, async move |mut output: Sender<Message>| {
+            if let Err(err) = watch_service(&connection, &mut output).await {
+                tracing::warn!("Not watching fprintd for restarts: {err}");
+            }
+            std::future::pending::<()>().await;
+        })
+    })
+}
+
 /// On non-COSMIC desktops, subscribe to XDG portal color-scheme changes
 /// so theme updates when user changes their desktop appearance
 ///
@@ -197,7 +247,7 @@ pub fn key_subscription() -> Subscription<Message> {
                 match c.as_str() {
                     "r" => Some(Message::Register),
                     "v" => Some(Message::VerifyFinger),
-                    "c" => Some(Message::EnrollStop),
+                    "c" => Some(Message::Cancel),
                     "1" => Some(Message::SelectFingerByNumber(1)),
                     "2" => Some(Message::SelectFingerByNumber(2)),
                     "3" => Some(Message::SelectFingerByNumber(3)),
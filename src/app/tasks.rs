@@ -2,42 +2,83 @@
 
 use crate::app::AppModel;
 use crate::app::{
+    audit::{self, Action, Auditor, outcome},
+    backend::FingerprintBackend,
+    doctor::diagnose,
     error::AppError,
-    fprint::*,
-    message::{DeviceOption, Message},
-    users::fetch_users,
+    fprint::DeviceCapabilities,
+    message::Message,
+    overview::Deletion,
+    pam::{PAM_DIRS, inspect},
+    pam_setup::{self, pkexec_result},
+    polkit, service,
+    users::{UserOption, fetch_users, find_user},
 };
-use crate::{fl, fprint_dbus::*};
+use crate::fl;
 use cosmic::{ApplicationExt, Task};
+use futures_util::{StreamExt, stream};
+use std::sync::Arc;
+use std::time::Duration;
+use zbus::zvariant::OwnedObjectPath;
+
+/// Pause between users when counting prints for the nav bar badges
+const LIST_THROTTLE: Duration = Duration::from_millis(100);
 
 impl AppModel {
     /// Gets all registered prints for requested user
     pub(crate) fn list_fingers_task(&self) -> Task<cosmic::Action<Message>> {
-        if let (Some(proxy), Some(user)) = (&self.device_proxy, &self.selected_user) {
-            let proxy = proxy.clone();
-            let username = (*user.username).clone();
+        if let (Some(backend), Some(path), Some(user)) =
+            (&self.backend, &self.device_path, &self.selected_user)
+        {
             return Task::perform(
-                async move {
-                    match list_enrolled_fingers_dbus(proxy, username).await {
-                        Ok(fingers) => Message::EnrolledFingers(fingers),
-                        Err(e) => Message::OperationError(
-                            AppError::from(e).with_context("Failed to list fingers"),
-                        ),
-                    }
-                },
+                list_fingers(
+                    backend.clone(),
+                    path.as_ref().clone(),
+                    (*user.username).clone(),
+                ),
                 cosmic::Action::App,
             );
         }
         Task::none()
     }
 
+    /// Lists the prints of every user in the nav bar, one user after the
+    /// other with a pause in between, so fprintd is not flooded.
+    ///
+    /// **Returns** ***Task*** sending ***Message***::*UserPrints* per user
+    pub(crate) fn list_user_prints_task(&self) -> Task<cosmic::Action<Message>> {
+        let (Some(backend), Some(path)) = (self.backend.clone(), self.device_path.clone()) else {
+            return Task::none();
+        };
+        let usernames: Vec<String> = self
+            .nav
+            .iter()
+            .filter_map(|id| self.nav.data::<UserOption>(id))
+            .map(|user| (*user.username).clone())
+            .collect();
+
+        let prints = stream::iter(usernames)
+            .enumerate()
+            .then(move |(index, username)| {
+                let backend = backend.clone();
+                let path = path.as_ref().clone();
+                async move {
+                    if index > 0 {
+                        tokio::time::sleep(LIST_THROTTLE).await;
+                    }
+                    list_user_prints(backend, path, username).await
+                }
+            });
+        Task::run(prints, cosmic::Action::App)
+    }
+
     /// Updates the header and window titles.
     pub fn update_title_task(&mut self) -> Task<cosmic::Action<Message>> {
         let mut window_title = fl!("app-title");
 
-        if let Some(page) = self.nav.text(self.nav.active()) {
+        if let Some(user) = self.nav.data::<UserOption>(self.nav.active()) {
             window_title.push_str(" — ");
-            window_title.push_str(page);
+            window_title.push_str(&user.to_string());
         }
 
         if let Some(id) = self.core.main_window_id() {
@@ -48,174 +89,333 @@ impl AppModel {
     }
 }
 
+/// Lists a user's prints for their nav bar badge and the overview
+///
+/// **Returns** ***Message***::*UserPrints*, without prints when listing fails
+pub(crate) async fn list_user_prints(
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
+    username: String,
+) -> Message {
+    let fingers = match backend
+        .list_enrolled_fingers(path.clone(), username.clone())
+        .await
+    {
+        Ok(fingers) => Some(fingers),
+        Err(AppError::NoEnrolledPrints) => Some(Vec::new()),
+        Err(_) => None,
+    };
+    Message::UserPrints(path, username, fingers)
+}
+
+/// Lists users enrolled prints
+///
+/// **Returns** ***Message***::*EnrolledFingers* or ***Message***::*OperationError*
+pub(crate) async fn list_fingers(
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
+    username: String,
+) -> Message {
+    match backend.list_enrolled_fingers(path, username).await {
+        Ok(fingers) => Message::EnrolledFingers(fingers),
+        Err(e) => Message::OperationError(e.with_context("Failed to list fingers")),
+    }
+}
+
+/// Deletes users all prints
+///
+/// **Returns** ***Message***::*DeleteComplete*(true) or ***Message***::*OperationError*
+pub(crate) async fn delete_prints(
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
+    username: String,
+    auditor: Auditor,
+) -> Message {
+    let result = backend.delete_all(path, username.clone()).await;
+    auditor.record(Action::DeleteAll, &username, None, &outcome(&result));
+    match result {
+        Ok(_) => Message::DeleteComplete(true),
+        Err(e) => Message::OperationError(e),
+    }
+}
+
 /// **Returns** ***Task*** which request deletion of users all prints
 pub fn task_delete_prints(
-    path: zbus::zvariant::OwnedObjectPath,
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
     username: String,
-    conn: zbus::Connection,
+    auditor: Auditor,
 ) -> Task<cosmic::Action<Message>> {
     Task::perform(
-        async move {
-            match delete_fingers(&conn, path, username).await {
-                Ok(_) => Message::DeleteComplete(true),
-                Err(e) => Message::OperationError(AppError::from(e)),
-            }
-        },
+        delete_prints(backend, path, username, auditor),
         cosmic::Action::App,
     )
 }
 
 /// **Returns** a ***Task*** which updates vector of scanner devices
-pub fn get_devices_task(conn: zbus::Connection) -> Task<cosmic::Action<Message>> {
+pub fn get_devices_task(backend: Arc<dyn FingerprintBackend>) -> Task<cosmic::Action<Message>> {
     Task::perform(
         async move {
-            match find_all_devices(&conn).await {
-                Ok(paths) => {
-                    let mut devices = Vec::new();
-                    for path in paths {
-                        let name = match DeviceProxy::builder(&conn).path(path.clone()) {
-                            Ok(builder) => match builder.build().await {
-                                Ok(proxy) => {
-                                    proxy.name().await.unwrap_or_else(|_| path.to_string())
-                                }
-                                Err(_) => path.to_string(),
-                            },
-                            Err(_) => path.to_string(),
-                        };
-                        devices.push(DeviceOption { path, name });
-                    }
-                    Message::UpdateDevices(devices)
-                }
-                Err(e) => Message::OperationError(AppError::from(e)),
+            match backend.list_devices().await {
+                Ok(devices) => Message::UpdateDevices(devices),
+                Err(e) => Message::OperationError(e),
             }
         },
         cosmic::Action::App,
     )
 }
 
+/// **Returns** a ***Task*** which lists the devices after a hotplug
+pub fn task_list_changed_devices(
+    backend: Arc<dyn FingerprintBackend>,
+) -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move { Message::DevicesChanged(backend.list_devices().await) },
+        cosmic::Action::App,
+    )
+}
+
+/// Deletes users given print
+///
+/// **Returns** ***Message***::*DeleteComplete*(false), ***Message***::*DeleteSingleUnsupported*
+/// or ***Message***::*OperationError*
+pub(crate) async fn delete_print(
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
+    username: String,
+    finger_name: String,
+    auditor: Auditor,
+) -> Message {
+    let result = backend
+        .delete_finger(path, username.clone(), finger_name.clone())
+        .await;
+    auditor.record(
+        Action::Delete,
+        &username,
+        Some(&finger_name),
+        &outcome(&result),
+    );
+    match result {
+        Ok(_) => Message::DeleteComplete(false),
+        Err(AppError::UnsupportedOperation) => Message::DeleteSingleUnsupported,
+        Err(e) => Message::OperationError(e),
+    }
+}
+
 /// **Returns** ***Task*** which requests deletion of given users given print
 pub fn task_delete_print(
-    path: zbus::zvariant::OwnedObjectPath,
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
     username: String,
     finger_name: String,
-    conn: zbus::Connection,
+    auditor: Auditor,
 ) -> Task<cosmic::Action<Message>> {
     Task::perform(
-        async move {
-            match delete_fingerprint_dbus(&conn, path, finger_name, username).await {
-                Ok(_) => Message::DeleteComplete(false),
-                Err(e) => {
-                    let err = AppError::from(e);
-                    if err == AppError::UnsupportedOperation {
-                        Message::DeleteSingleUnsupported
-                    } else {
-                        Message::OperationError(err)
-                    }
-                }
-            }
-        },
+        delete_print(backend, path, username, finger_name, auditor),
         cosmic::Action::App,
     )
 }
 
 /// **Returns** ***Task*** which sends a signal to stop current enroll process
 pub fn task_enroll_stop(
-    path: zbus::zvariant::OwnedObjectPath,
-    conn: zbus::Connection,
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
 ) -> Task<cosmic::Action<Message>> {
-    Task::perform(
-        async move {
-            let device = DeviceProxy::builder(&conn).path(path)?.build().await?;
-            let _ = device.enroll_stop().await;
-            device.release().await?;
-            Ok::<(), zbus::Error>(())
-        },
-        |res| match res {
-            Ok(_) => {
-                cosmic::Action::App(Message::EnrollStatus("enroll-cancelled".to_string(), true))
-            }
-            Err(e) => cosmic::Action::App(Message::OperationError(AppError::from(e))),
-        },
-    )
+    Task::perform(backend.enroll_stop(path), |res| match res {
+        Ok(_) => cosmic::Action::App(Message::EnrollStatus("enroll-cancelled".to_string(), true)),
+        Err(e) => cosmic::Action::App(Message::OperationError(e)),
+    })
 }
 
 /// **Returns** ***Task*** which sends a signal to stop current verify process
 pub fn task_verify_stop(
-    path: zbus::zvariant::OwnedObjectPath,
-    conn: zbus::Connection,
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
 ) -> Task<cosmic::Action<Message>> {
-    Task::perform(
-        async move {
-            let device = DeviceProxy::builder(&conn).path(path)?.build().await?;
-            let _ = device.verify_stop().await;
-            device.release().await?;
-            Ok::<(), zbus::Error>(())
-        },
-        |res| match res {
-            Ok(_) => {
-                cosmic::Action::App(Message::VerifyStatus("verify-cancelled".to_string(), true))
-            }
-            Err(e) => cosmic::Action::App(Message::OperationError(AppError::from(e))),
-        },
-    )
+    Task::perform(backend.verify_stop(path), |res| match res {
+        Ok(_) => cosmic::Action::App(Message::VerifyStatus("verify-cancelled".to_string(), true)),
+        Err(e) => cosmic::Action::App(Message::OperationError(e)),
+    })
+}
+
+/// Deletes all prints for all given users
+///
+/// **Returns** ***Message***::*ClearComplete*
+pub(crate) async fn clear_device(
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
+    usernames: Vec<String>,
+    auditor: Auditor,
+) -> Message {
+    let result = backend.clear(path, usernames.clone()).await;
+    let result_text = outcome(&result);
+    for username in &usernames {
+        auditor.record(Action::Clear, username, None, &result_text);
+    }
+    Message::ClearComplete(result)
 }
 
 /// **Returns** ***Task*** which requests deletion of all prints for all users
 pub fn task_clear_device(
-    path: zbus::zvariant::OwnedObjectPath,
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
     usernames: Vec<String>,
-    conn: zbus::Connection,
+    auditor: Auditor,
 ) -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        clear_device(backend, path, usernames, auditor),
+        cosmic::Action::App,
+    )
+}
+
+/// **Returns** ***Task*** which asks polkit for a fresh authentication
+/// before clearing the device
+pub fn task_authorize_clear_device(conn: zbus::Connection) -> Task<cosmic::Action<Message>> {
     Task::perform(
         async move {
-            match clear_all_fingers_dbus(&conn, path, usernames).await {
-                Ok(_) => Message::ClearComplete(Ok(())),
-                Err(e) => Message::ClearComplete(Err(AppError::from(e))),
-            }
+            Message::ClearAuthorized(polkit::authorize(&conn, polkit::CLEAR_DEVICE_ACTION).await)
         },
         cosmic::Action::App,
     )
 }
 
-/// **Returns** a ***Task*** which changes device used for all operations.
-pub fn task_select_device(
-    conn: zbus::Connection,
-    path: zbus::zvariant::OwnedObjectPath,
+/// Deletes the prints selected in the overview user by user, all of a user's
+/// prints at once when every one is selected. Stops at the first failure.
+///
+/// **Returns** ***Message***::*SelectionDeleted*
+pub(crate) async fn delete_selection(
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
+    deletions: Vec<Deletion>,
+    auditor: Auditor,
+) -> Message {
+    for deletion in deletions {
+        if deletion.all {
+            let result = backend
+                .delete_all(path.clone(), deletion.username.clone())
+                .await;
+            auditor.record(
+                Action::DeleteAll,
+                &deletion.username,
+                None,
+                &outcome(&result),
+            );
+            if let Err(e) = result {
+                return Message::SelectionDeleted(Err(e));
+            }
+            continue;
+        }
+        for finger in deletion.fingers {
+            let finger_name = finger.as_finger_id();
+            let result = backend
+                .delete_finger(
+                    path.clone(),
+                    deletion.username.clone(),
+                    finger_name.to_string(),
+                )
+                .await;
+            auditor.record(
+                Action::Delete,
+                &deletion.username,
+                Some(finger_name),
+                &outcome(&result),
+            );
+            if let Err(e) = result {
+                return Message::SelectionDeleted(Err(e));
+            }
+        }
+    }
+    Message::SelectionDeleted(Ok(()))
+}
+
+/// **Returns** ***Task*** which requests deletion of the prints selected in
+/// the overview
+pub fn task_delete_selection(
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
+    deletions: Vec<Deletion>,
+    auditor: Auditor,
 ) -> Task<cosmic::Action<Message>> {
     Task::perform(
-        async move {
-            match DeviceProxy::builder(&conn).path(path.clone()) {
-                Ok(builder) => match builder.build().await {
-                    Ok(proxy) => Message::DeviceFound(Some((path, proxy))),
-                    Err(e) => Message::OperationError(AppError::from(e)),
-                },
-                Err(e) => Message::OperationError(AppError::from(e)),
-            }
-        },
+        delete_selection(backend, path, deletions, auditor),
         cosmic::Action::App,
     )
 }
 
+/// **Returns** a ***Task*** which changes device used for all operations.
+pub fn task_select_device(path: OwnedObjectPath) -> Task<cosmic::Action<Message>> {
+    Task::done(cosmic::Action::App(Message::DeviceFound(Some(path))))
+}
+
+/// Probes what the daemon implements for a device, assuming everything
+/// fprintd provides when it cannot be introspected
+///
+/// **Returns** ***Message***::*CapabilitiesProbed*
+pub(crate) async fn probe_capabilities(
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
+) -> Message {
+    let capabilities = backend
+        .capabilities(path.clone())
+        .await
+        .unwrap_or_else(|err| {
+            tracing::warn!(?err, "cannot probe the device capabilities");
+            DeviceCapabilities::default()
+        });
+    Message::CapabilitiesProbed(path, capabilities)
+}
+
+/// **Returns** ***Task*** which probes what the daemon implements for a device
+pub fn task_probe_capabilities(
+    backend: Arc<dyn FingerprintBackend>,
+    path: OwnedObjectPath,
+) -> Task<cosmic::Action<Message>> {
+    Task::perform(probe_capabilities(backend, path), cosmic::Action::App)
+}
+
 /// **Returns** ***Task*** which finds and returns default fingerprint scanner device
-pub fn task_find_device(conn_clone: zbus::Connection) -> Task<cosmic::Action<Message>> {
+pub fn task_find_device(backend: Arc<dyn FingerprintBackend>) -> Task<cosmic::Action<Message>> {
     Task::perform(
         async move {
-            match find_device(&conn_clone).await {
-                Ok((path, proxy)) => Message::DeviceFound(Some((path, proxy))),
-                Err(e) => {
-                    let error = AppError::from(e);
-                    if matches!(error, AppError::Unknown(_)) {
-                        Message::OperationError(AppError::DeviceNotFound)
-                    } else {
-                        Message::OperationError(error)
-                    }
-                }
+            match backend.find_device().await {
+                Ok(path) => Message::DeviceFound(Some(path)),
+                Err(AppError::Unknown(_)) => Message::OperationError(AppError::DeviceNotFound),
+                Err(e) => Message::OperationError(e),
             }
         },
         cosmic::Action::App,
     )
 }
 
+/// Finds the device to use again after fprintd was (re)started, keeping
+/// `previous` while the service still lists it
+///
+/// **Returns** ***Message***::*DeviceFound* or ***Message***::*OperationError*
+pub(crate) async fn restore_device(
+    backend: Arc<dyn FingerprintBackend>,
+    previous: Option<OwnedObjectPath>,
+) -> Message {
+    if let Some(previous) = previous
+        && let Ok(devices) = backend.list_devices().await
+        && devices.iter().any(|device| device.path == previous)
+    {
+        return Message::DeviceFound(Some(previous));
+    }
+    match backend.find_device().await {
+        Ok(path) => Message::DeviceFound(Some(path)),
+        Err(AppError::Unknown(_)) => Message::OperationError(AppError::DeviceNotFound),
+        Err(e) => Message::OperationError(e),
+    }
+}
+
+/// **Returns** ***Task*** which finds the device to use after fprintd was (re)started
+pub fn task_restore_device(
+    backend: Arc<dyn FingerprintBackend>,
+    previous: Option<OwnedObjectPath>,
+) -> Task<cosmic::Action<Message>> {
+    Task::perform(restore_device(backend, previous), cosmic::Action::App)
+}
+
 /// **Returns** ***Task*** which connects to DBus
 pub fn task_connect() -> Task<cosmic::Action<Message>> {
     Task::perform(
@@ -236,3 +436,106 @@ pub fn task_load_users() -> Task<cosmic::Action<Message>> {
         cosmic::Action::App,
     )
 }
+
+/// **Returns** ***Task*** which resolves a user typed into the add user dialog
+pub fn task_find_user(username: String) -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move {
+            let user = find_user(&username).await;
+            Message::UserFound(username, user)
+        },
+        cosmic::Action::App,
+    )
+}
+
+/// **Returns** ***Task*** which resolves the users remembered in config,
+/// leaving out those NSS no longer knows
+pub fn task_find_remembered_users(usernames: Vec<String>) -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move {
+            let mut users = Vec::with_capacity(usernames.len());
+            for username in &usernames {
+                users.extend(find_user(username).await);
+            }
+            Message::RememberedUsersFound(users)
+        },
+        cosmic::Action::App,
+    )
+}
+
+/// **Returns** ***Task*** which asks polkit what fprintd would let this
+/// connection do
+pub fn task_check_permissions(conn: zbus::Connection) -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move { Message::PermissionsChecked(polkit::check(&conn).await) },
+        cosmic::Action::App,
+    )
+}
+
+/// **Returns** ***Task*** which asks systemd what became of the fingerprint service
+pub fn task_check_service(conn: zbus::Connection) -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move { Message::ServiceChecked(service::check(&conn).await) },
+        cosmic::Action::App,
+    )
+}
+
+/// **Returns** ***Task*** which has systemd start the fingerprint service
+pub fn task_start_service(conn: zbus::Connection, unit: String) -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move { Message::ServiceStarted(service::start(&conn, &unit).await) },
+        cosmic::Action::App,
+    )
+}
+
+/// **Returns** ***Task*** which reads the audit entries back from the journal
+pub fn task_load_history() -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move { Message::HistoryLoaded(audit::history().await) },
+        cosmic::Action::App,
+    )
+}
+
+/// **Returns** ***Task*** which checks the whole fingerprint stack
+pub fn task_run_diagnostics() -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move { Message::DiagnosticsReady(diagnose().await) },
+        cosmic::Action::App,
+    )
+}
+
+/// **Returns** ***Task*** which reads how PAM services use pam_fprintd
+/// and what enabling it would change
+pub fn task_inspect_pam() -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move {
+            Message::PamInspected(
+                inspect(&PAM_DIRS),
+                pam_setup::plan(std::path::Path::new("/")),
+            )
+        },
+        cosmic::Action::App,
+    )
+}
+
+/// **Returns** ***Task*** which enables pam_fprintd by running this
+/// binary's `pam-setup` command through pkexec, which checks the user's
+/// prints on `device`
+pub fn task_apply_pam_setup(device: OwnedObjectPath) -> Task<cosmic::Action<Message>> {
+    Task::perform(
+        async move {
+            let result = match std::env::current_exe() {
+                Ok(exe) => tokio::process::Command::new("pkexec")
+                    .arg(exe)
+                    .args(["pam-setup", "--device", device.as_str()])
+                    .status()
+                    .await
+                    .map_err(|err| format!("pkexec: {err}"))
+                    .and_then(|status| pkexec_result(status.code())),
+                Err(err) => Err(err.to_string()),
+            };
+            Message::PamSetupApplied(result)
+        },
+        cosmic::Action::App,
+    )
+}
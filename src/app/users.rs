@@ -1,34 +1,156 @@
 // SPDX-License-Identifier: MPL-2.0
 
 pub(crate) use crate::accounts_dbus::{AccountsProxy, UserProxy};
+use crate::app::message::Message;
+use crate::app::polkit::{Access, Permissions};
+use crate::fl;
+use cosmic::iced::futures::channel::mpsc::Sender;
 use cosmic::widget::{icon, nav_bar};
-use nix::unistd::{Uid, User};
-use std::sync::Arc;
+use futures_util::stream::{AbortHandle, BoxStream, SelectAll, abortable};
+use futures_util::{SinkExt, StreamExt};
+use nix::libc;
+use nix::unistd::{Gid, Group, Uid, User, getgrouplist};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
 
-/// Fetches users from accounts-daemon asynchronously.
+const LOGIN_DEFS: &str = "/etc/login.defs";
+/// `AccountType` of administrators in accounts-daemon
+const ACCOUNT_TYPE_ADMINISTRATOR: i32 = 1;
+/// Groups whose members administer the system on common distributions
+const ADMIN_GROUPS: [&str; 3] = ["wheel", "sudo", "admin"];
+/// shadow-utils defaults when login.defs does not set a range
+const DEFAULT_UIDS: RangeInclusive<u32> = 1000..=60000;
+/// Held while the passwd database is enumerated, whose cursor is global
+static PASSWD_ENUMERATION: Mutex<()> = Mutex::new(());
+
+/// Fetches users from accounts-daemon asynchronously, falling back to the
+/// NSS passwd database when accounts-daemon is missing or lists nobody.
 ///
 /// **Returns** list of users
 pub async fn fetch_users() -> Vec<UserOption> {
-    let mut users = Vec::new();
-
     if let Ok(conn) = zbus::Connection::system().await
         && let Ok(accounts) = AccountsProxy::new(&conn).await
-        && let Ok(user_paths) = accounts.list_cached_users().await
     {
+        let users = list_users(&conn, &accounts).await;
+        if !users.is_empty() {
+            return users;
+        }
+    }
+
+    tracing::info!("No users from accounts-daemon, listing them through NSS");
+    let uids = std::fs::read_to_string(LOGIN_DEFS)
+        .map(|login_defs| uid_range(&login_defs))
+        .unwrap_or(DEFAULT_UIDS);
+    // Enumerating directory users can block for seconds.
+    tokio::task::spawn_blocking(move || nss_users(uids))
+        .await
+        .unwrap_or_default()
+}
+
+/// **Returns** the `UID_MIN`..=`UID_MAX` range of human accounts set in
+/// login.defs
+fn uid_range(login_defs: &str) -> RangeInclusive<u32> {
+    let mut min = *DEFAULT_UIDS.start();
+    let mut max = *DEFAULT_UIDS.end();
+    for line in login_defs.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(key), Some(value)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        match (key, value.parse()) {
+            ("UID_MIN", Ok(value)) => min = value,
+            ("UID_MAX", Ok(value)) => max = value,
+            _ => {}
+        }
+    }
+    min..=max
+}
+
+/// Enumerates accounts with a UID in `uids` through NSS, so local, LDAP
+/// and systemd-homed users are found alike. The current user is always
+/// included. Blocks while NSS answers, and while another enumeration runs.
+///
+/// **Returns** list of users, without icons
+fn nss_users(uids: RangeInclusive<u32>) -> Vec<UserOption> {
+    let current = Uid::current();
+    // Name, GECOS, UID and primary group of each account
+    let mut entries: Vec<(String, String, u32, Gid)> = Vec::new();
+
+    let enumeration = PASSWD_ENUMERATION
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    // SAFETY: the setpwent/getpwent/endpwent cursor is process global. Every
+    // enumeration in the application runs here while holding
+    // PASSWD_ENUMERATION, so no two of them interleave, and each entry is
+    // copied before the next getpwent call reuses its buffer.
+    unsafe {
+        libc::setpwent();
+        loop {
+            let entry = libc::getpwent();
+            if entry.is_null() {
+                break;
+            }
+            let entry = &*entry;
+            if !uids.contains(&entry.pw_uid) && entry.pw_uid != current.as_raw() {
+                continue;
+            }
+            let name = CStr::from_ptr(entry.pw_name).to_string_lossy().into_owned();
+            // Several NSS sources may return the same account.
+            if entries.iter().any(|(known, ..)| *known == name) {
+                continue;
+            }
+            let gecos = if entry.pw_gecos.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(entry.pw_gecos)
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            entries.push((name, gecos, entry.pw_uid, Gid::from_raw(entry.pw_gid)));
+        }
+        libc::endpwent();
+    }
+    drop(enumeration);
+
+    if let Ok(Some(user)) = User::from_uid(current)
+        && !entries.iter().any(|(known, ..)| *known == user.name)
+    {
+        let gecos = user.gecos.to_string_lossy().into_owned();
+        entries.push((user.name, gecos, current.as_raw(), user.gid));
+    }
+    entries
+        .into_iter()
+        .map(|(name, gecos, uid, gid)| UserOption::from_passwd(name, &gecos, uid, gid))
+        .collect()
+}
+
+/// Whether `username`, whose primary group is `gid`, is a member of one of
+/// the [`ADMIN_GROUPS`]. This is how administrators are told apart without
+/// accounts-daemon.
+fn is_admin(username: &str, gid: Gid) -> bool {
+    let Ok(name) = CString::new(username) else {
+        return false;
+    };
+    let Ok(groups) = getgrouplist(&name, gid) else {
+        return false;
+    };
+    ADMIN_GROUPS
+        .iter()
+        .filter_map(|group| Group::from_name(group).ok().flatten())
+        .any(|group| groups.contains(&group.gid))
+}
+
+/// **Returns** every cached user with its properties
+async fn list_users(conn: &zbus::Connection, accounts: &AccountsProxy<'_>) -> Vec<UserOption> {
+    let mut users = Vec::new();
+
+    if let Ok(user_paths) = accounts.list_cached_users().await {
         for path in user_paths {
-            if let Ok(builder) = UserProxy::builder(&conn).path(&path)
-                && let Ok(user_proxy) = builder.build().await
-                && let (Ok(name), Ok(real_name), Ok(icon)) = (
-                    user_proxy.user_name().await,
-                    user_proxy.real_name().await,
-                    user_proxy.icon_file().await,
-                )
-            {
-                users.push(UserOption {
-                    username: Arc::new(name),
-                    realname: Arc::new(real_name),
-                    icon: Arc::new(icon),
-                });
+            if let Some(user) = user_option(conn, &path).await {
+                users.push(user);
             }
         }
     }
@@ -36,10 +158,204 @@ pub async fn fetch_users() -> Vec<UserOption> {
     users
 }
 
-/// Builds the nav bar model from a list of users and selects the current user.
+/// Resolves a user who is not in the accounts-daemon cache, such as a
+/// directory user who never logged in. The name must be known to NSS,
+/// accounts-daemon is then asked for the details.
+///
+/// **Returns** the user, or `None` when NSS does not know the name
+pub async fn find_user(username: &str) -> Option<UserOption> {
+    let passwd = User::from_name(username).ok().flatten()?;
+
+    if let Ok(conn) = zbus::Connection::system().await
+        && let Ok(accounts) = AccountsProxy::new(&conn).await
+        && let Ok(path) = accounts.find_user_by_name(&passwd.name).await
+        && let Some(user) = user_option(&conn, &path).await
+    {
+        return Some(user);
+    }
+
+    Some(UserOption::from_passwd(
+        passwd.name,
+        &passwd.gecos.to_string_lossy(),
+        passwd.uid.as_raw(),
+        passwd.gid,
+    ))
+}
+
+/// **Returns** the properties of the accounts-daemon user at `path`
+async fn user_option(conn: &zbus::Connection, path: &OwnedObjectPath) -> Option<UserOption> {
+    let user_proxy = UserProxy::builder(conn)
+        .path(path)
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+    let (Ok(name), Ok(real_name), Ok(icon)) = (
+        user_proxy.user_name().await,
+        user_proxy.real_name().await,
+        user_proxy.icon_file().await,
+    ) else {
+        return None;
+    };
+
+    // Older accounts-daemon versions lack some of these.
+    Some(UserOption {
+        username: Arc::new(name),
+        realname: Arc::new(real_name),
+        icon: Arc::new(icon),
+        uid: user_proxy
+            .uid()
+            .await
+            .ok()
+            .and_then(|uid| u32::try_from(uid).ok()),
+        system: user_proxy.system_account().await.unwrap_or_default(),
+        locked: user_proxy.locked().await.unwrap_or_default(),
+        admin: user_proxy
+            .account_type()
+            .await
+            .is_ok_and(|account_type| account_type == ACCOUNT_TYPE_ADMINISTRATOR),
+    })
+}
+
+/// A user followed by [`watch_users`]
+struct WatchedUser {
+    path: OwnedObjectPath,
+    user: UserOption,
+    /// Stops following the user's Changed signals
+    changes: AbortHandle,
+}
+
+/// Sends each user accounts-daemon adds or deletes, or who changes. Only the
+/// user concerned is read again, and the application merges them into its
+/// list, which may hold users accounts-daemon does not know about.
+///
+/// **Returns** once the application stops listening, or an error when
+/// accounts-daemon cannot be watched
+pub async fn watch_users(
+    conn: &zbus::Connection,
+    output: &mut Sender<Message>,
+) -> zbus::Result<()> {
+    let accounts = AccountsProxy::new(conn).await?;
+    let mut added = accounts.receive_user_added().await?;
+    let mut deleted = accounts.receive_user_deleted().await?;
+    // Paths of the users whose Changed signal arrived
+    let mut changed = SelectAll::new();
+    let mut users: Vec<WatchedUser> = Vec::new();
+    for path in accounts.list_cached_users().await? {
+        users.extend(watch_user(conn, path, &mut changed).await);
+    }
+
+    loop {
+        let message = tokio::select! {
+            Some(signal) = added.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                let path = OwnedObjectPath::from(args.user().clone());
+                forget_user(&mut users, &path);
+                let Some(watched) = watch_user(conn, path, &mut changed).await else {
+                    continue;
+                };
+                let message = Message::UserChanged(watched.user.clone());
+                users.push(watched);
+                message
+            }
+            Some(signal) = deleted.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                let Some(user) = forget_user(&mut users, args.user()) else {
+                    continue;
+                };
+                Message::UserRemoved(user)
+            }
+            Some(path) = changed.next(), if !changed.is_empty() => {
+                let (Some(watched), Some(user)) = (
+                    users.iter_mut().find(|watched| watched.path == path),
+                    user_option(conn, &path).await,
+                ) else {
+                    continue;
+                };
+                watched.user = user.clone();
+                Message::UserChanged(user)
+            }
+            else => return Ok(()),
+        };
+
+        if output.send(message).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Reads a user and follows their Changed signals through `changed`
+///
+/// **Returns** the user, `None` when accounts-daemon does not answer for them
+async fn watch_user(
+    conn: &zbus::Connection,
+    path: OwnedObjectPath,
+    changed: &mut SelectAll<BoxStream<'static, OwnedObjectPath>>,
+) -> Option<WatchedUser> {
+    let user = user_option(conn, &path).await?;
+    let user_proxy = UserProxy::builder(conn)
+        .path(path.clone())
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+    let (stream, changes) = abortable(user_proxy.receive_changed().await.ok()?);
+    let changed_path = path.clone();
+    changed.push(stream.map(move |_| changed_path.clone()).boxed());
+    Some(WatchedUser {
+        path,
+        user,
+        changes,
+    })
+}
+
+/// Drops the user at `path` and stops following their changes
 ///
-/// **Returns** tuple of nav model and selected user
-pub fn build_nav(users: &[UserOption]) -> (nav_bar::Model, Option<UserOption>) {
+/// **Returns** the user, `None` when they were not followed
+fn forget_user(users: &mut Vec<WatchedUser>, path: &ObjectPath<'_>) -> Option<UserOption> {
+    let index = users
+        .iter()
+        .position(|watched| watched.path.as_str() == path.as_str())?;
+    let watched = users.remove(index);
+    watched.changes.abort();
+    Some(watched.user)
+}
+
+/// Nav bar item which opens the dialog to add a user by name
+#[derive(Debug, Clone, Copy)]
+pub struct AddUserItem;
+
+/// State of the dialog to add a user by name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddUserDialog {
+    pub username: String,
+    /// Keep the user in the config for later sessions
+    pub remember: bool,
+    pub searching: bool,
+    pub error: Option<String>,
+}
+
+/// Builds the nav bar model from a list of users and activates `selected`,
+/// or the current user when no username is given. System accounts are left
+/// out unless `show_all` is set, the current user is always shown, and is
+/// the only one when `restricted`. Users in `enrolled` get a badge with
+/// their number of prints, and users whose prints polkit keeps from us a
+/// lock badge. An [`AddUserItem`] comes last unless `restricted`.
+///
+/// **Returns** tuple of nav model and selected user, `None` when `selected`
+/// is not shown
+pub fn build_nav(
+    users: &[UserOption],
+    selected: Option<&str>,
+    show_all: bool,
+    restricted: bool,
+    enrolled: &HashMap<String, Vec<String>>,
+    permissions: &Permissions,
+) -> (nav_bar::Model, Option<UserOption>) {
     let mut nav = nav_bar::Model::default();
     let mut selected_user = None;
     let current_username = User::from_uid(Uid::current())
@@ -47,8 +363,22 @@ pub fn build_nav(users: &[UserOption]) -> (nav_bar::Model, Option<UserOption>) {
         .flatten()
         .map(|u| u.name);
 
-    for user_opt in users {
-        let mut item = nav.insert().text(user_opt.to_string());
+    let shown = users.iter().filter(|user| {
+        let is_current = current_username.as_deref() == Some(&*user.username);
+        if restricted {
+            is_current
+        } else {
+            show_all || !user.system || is_current
+        }
+    });
+    for user_opt in shown {
+        let mut item = nav
+            .insert()
+            .text(user_opt.nav_label(
+                enrolled.get(user_opt.username.as_str()).map(Vec::len),
+                permissions.manage(user_opt.is_current_user()),
+            ))
+            .data(user_opt.clone());
         let mut icon_str = user_opt.icon.as_str();
 
         if icon_str.starts_with("file://") {
@@ -71,19 +401,102 @@ pub fn build_nav(users: &[UserOption]) -> (nav_bar::Model, Option<UserOption>) {
 
         item = item.icon(icon);
         let id = item.id();
-        if selected_user.is_none() || current_username.as_deref() == Some(&*user_opt.username) {
+        let activate = match selected {
+            Some(selected) => selected == user_opt.username.as_str(),
+            None => {
+                selected_user.is_none() || current_username.as_deref() == Some(&*user_opt.username)
+            }
+        };
+        if activate {
             nav.activate(id);
             selected_user = Some(user_opt.clone());
         }
     }
+
+    if !restricted {
+        nav.insert()
+            .text(fl!("add-user"))
+            .icon(icon::from_name("list-add-symbolic").icon())
+            .data(AddUserItem);
+    }
+
     (nav, selected_user)
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct UserOption {
     pub username: Arc<String>,
     pub realname: Arc<String>,
     pub icon: Arc<String>,
+    pub uid: Option<u32>,
+    /// Service account, hidden unless all users are shown
+    pub system: bool,
+    pub locked: bool,
+    pub admin: bool,
+}
+
+impl UserOption {
+    /// A user from the passwd database, whose real name is the first field
+    /// of the GECOS comment, and who is an administrator when in one of the
+    /// [`ADMIN_GROUPS`]
+    fn from_passwd(username: String, gecos: &str, uid: u32, gid: Gid) -> Self {
+        Self {
+            admin: is_admin(&username, gid),
+            username: Arc::new(username),
+            realname: Arc::new(gecos.split(',').next().unwrap_or_default().to_string()),
+            uid: Some(uid),
+            ..Default::default()
+        }
+    }
+
+    /// **Returns** whether `other` is the same account, by uid when both
+    /// are known and by name otherwise, so renamed users are matched too
+    pub fn is_same_account(&self, other: &UserOption) -> bool {
+        match (self.uid, other.uid) {
+            (Some(uid), Some(other)) => uid == other,
+            _ => self.username == other.username,
+        }
+    }
+
+    /// **Returns** whether this is the user running the app
+    pub fn is_current_user(&self) -> bool {
+        match self.uid {
+            Some(uid) => Uid::from_raw(uid) == Uid::current(),
+            None => User::from_uid(Uid::current())
+                .ok()
+                .flatten()
+                .is_some_and(|user| user.name == *self.username),
+        }
+    }
+
+    /// **Returns** the name shown in the nav bar, marking administrators,
+    /// locked accounts, how many prints are `enrolled` when known and
+    /// whether managing them needs authentication or is denied by `access`
+    pub fn nav_label(&self, enrolled: Option<usize>, access: Access) -> String {
+        let mut marks = Vec::new();
+        if self.admin {
+            marks.push(fl!("user-admin"));
+        }
+        if self.locked {
+            marks.push(fl!("user-locked"));
+        }
+        match enrolled {
+            Some(0) => marks.push(fl!("user-not-enrolled")),
+            Some(count) => marks.push(fl!("user-enrolled", count = count)),
+            None => {}
+        }
+        match access {
+            Access::Allowed => {}
+            Access::NeedsAuth => marks.push(fl!("user-auth-required")),
+            Access::Denied => marks.push(fl!("user-not-permitted")),
+        }
+
+        if marks.is_empty() {
+            self.to_string()
+        } else {
+            format!("{self} · {}", marks.join(", "))
+        }
+    }
 }
 
 impl std::fmt::Display for UserOption {
@@ -101,12 +514,109 @@ mod tests {
     use super::*;
     use std::sync::Arc;
 
+    #[test]
+    fn test_uid_range() {
+        assert_eq!(uid_range(""), DEFAULT_UIDS);
+        assert_eq!(
+            uid_range("# UID_MIN 1\nUID_MIN\t\t 500\nUID_MAX 29999\nSYS_UID_MAX 499\n"),
+            500..=29999
+        );
+        assert_eq!(uid_range("UID_MIN nope\nUID_MAX 70000\n"), 1000..=70000);
+    }
+
+    #[test]
+    fn test_nss_users_include_current_user() {
+        let current = User::from_uid(Uid::current()).unwrap().unwrap();
+        // An empty range still lists the current user, once.
+        #[allow(clippy::reversed_empty_ranges)]
+        let users = nss_users(1..=0);
+
+        assert_eq!(users.len(), 1);
+        assert_eq!(*users[0].username, current.name);
+    }
+
+    #[test]
+    fn test_user_option_from_passwd() {
+        let user =
+            UserOption::from_passwd("jdoe".to_string(), "John Doe,,,", 1000, Gid::from_raw(1000));
+        assert_eq!(user.to_string(), "John Doe (jdoe)");
+        assert_eq!(user.uid, Some(1000));
+        assert!(!user.admin);
+    }
+
+    #[test]
+    fn test_is_admin_by_group() {
+        // Primary groups count as well as supplementary ones.
+        if let Some(group) = ADMIN_GROUPS
+            .iter()
+            .find_map(|group| Group::from_name(group).ok().flatten())
+        {
+            assert!(is_admin("jdoe", group.gid));
+        }
+        assert!(!is_admin("jdoe", Gid::from_raw(1000)));
+        assert!(!is_admin("j\0doe", Gid::from_raw(1000)));
+    }
+
+    #[test]
+    fn test_is_current_user() {
+        let current = User::from_uid(Uid::current()).unwrap().unwrap();
+        let user = UserOption {
+            username: Arc::new(current.name),
+            ..Default::default()
+        };
+        assert!(user.is_current_user());
+        assert!(
+            UserOption {
+                uid: Some(current.uid.as_raw()),
+                ..Default::default()
+            }
+            .is_current_user()
+        );
+        assert!(
+            !UserOption {
+                uid: Some(current.uid.as_raw().wrapping_add(1)),
+                ..user
+            }
+            .is_current_user()
+        );
+    }
+
+    #[test]
+    fn test_nav_label_marks() {
+        let user = UserOption {
+            username: Arc::new("jdoe".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(user.nav_label(None, Access::Allowed), "jdoe");
+        assert_eq!(
+            user.nav_label(Some(0), Access::Allowed),
+            format!("jdoe · {}", fl!("user-not-enrolled"))
+        );
+
+        let user = UserOption {
+            admin: true,
+            locked: true,
+            ..user
+        };
+        assert_eq!(
+            user.nav_label(Some(2), Access::NeedsAuth),
+            format!(
+                "jdoe · {}, {}, {}, {}",
+                fl!("user-admin"),
+                fl!("user-locked"),
+                fl!("user-enrolled", count = 2),
+                fl!("user-auth-required")
+            )
+        );
+    }
+
     #[test]
     fn test_user_option_display_with_realname() {
         let user_option = UserOption {
             username: Arc::new("jdoe".to_string()),
             realname: Arc::new("John Doe".to_string()),
             icon: Arc::new("".to_string()),
+            ..Default::default()
         };
         assert_eq!(user_option.to_string(), "John Doe (jdoe)");
     }
@@ -117,6 +627,7 @@ mod tests {
             username: Arc::new("jdoe".to_string()),
             realname: Arc::new("".to_string()),
             icon: Arc::new("".to_string()),
+            ..Default::default()
         };
         assert_eq!(user_option.to_string(), "jdoe");
     }
@@ -127,6 +638,7 @@ mod tests {
             username: Arc::new("jdoe".to_string()),
             realname: Arc::new("   ".to_string()),
             icon: Arc::new("".to_string()),
+            ..Default::default()
         };
         assert_eq!(user_option.to_string(), "    (jdoe)");
     }
@@ -137,6 +649,7 @@ mod tests {
             username: Arc::new("".to_string()),
             realname: Arc::new("John Doe".to_string()),
             icon: Arc::new("".to_string()),
+            ..Default::default()
         };
         assert_eq!(user_option.to_string(), "John Doe ()");
     }
@@ -147,6 +660,7 @@ mod tests {
             username: Arc::new("".to_string()),
             realname: Arc::new("".to_string()),
             icon: Arc::new("".to_string()),
+            ..Default::default()
         };
         assert_eq!(user_option.to_string(), "");
     }
@@ -2,6 +2,12 @@
 
 use crate::app::AppModel;
 use crate::app::Finger;
+use crate::app::audit::format_time;
+use crate::app::overview::Overview;
+use crate::app::polkit::Access;
+use crate::app::service::ServiceState;
+use crate::app::state::OperationState;
+use crate::app::users::{AddUserDialog, UserOption};
 use crate::{
     app::message::{Message, REPOSITORY},
     fl,
@@ -14,7 +20,7 @@ use cosmic::iced::widget;
 use cosmic::iced::widget::{ProgressBar, pick_list};
 use cosmic::theme;
 use cosmic::widget::{Column, Row};
-use cosmic::widget::{button, container, svg, text};
+use cosmic::widget::{button, container, icon, svg, text};
 use cosmic::{Apply, Element};
 const APP_ICON: &[u8] = include_bytes!("../../resources/icons/hicolor/scalable/apps/enroll.svg");
 const FPRINT_ICON: &[u8] = include_bytes!("../../resources/icons/hicolor/scalable/apps/fprint.svg");
@@ -26,6 +32,11 @@ const WIKI_ARCH: &str = "https://wiki.archlinux.org/title/Fprint#Login_configura
 pub(crate) const MAIN_PADDING: u16 = 20;
 pub(crate) const MAIN_SPACING: u16 = 20;
 
+/// **Returns** the icon trailing buttons which need authentication
+pub(crate) fn lock_icon() -> icon::Handle {
+    icon::from_name("system-lock-screen-symbolic").handle()
+}
+
 impl AppModel {
     /// The about page for this app.
     pub fn about(&self) -> Element<'_, Message> {
@@ -90,10 +101,58 @@ impl AppModel {
         let wiki_arch_url = button::link(WIKI_ARCH)
             .on_press(Message::LaunchUrl(WIKI_ARCH.into()))
             .padding(0);
-        let pam_section = cosmic::widget::settings::section()
+        let mut pam_section = cosmic::widget::settings::section()
             .title("PAM")
             .add(help_section_pam)
             .add(wiki_arch_url);
+
+        for service in &self.pam_services {
+            let title = match &service.name {
+                Some(name) => format!("{} ({name})", service.kind.localized_name()),
+                None => service.kind.localized_name(),
+            };
+            let (description, status) = if service.uses_fprintd() {
+                let rules: Vec<String> = service
+                    .rules
+                    .iter()
+                    .map(|rule| rule.localized_description())
+                    .collect();
+                (rules.join(", "), fl!("pam-enabled"))
+            } else if service.name.is_some() {
+                (fl!("pam-not-used"), fl!("pam-disabled"))
+            } else {
+                (fl!("pam-no-service"), fl!("pam-disabled"))
+            };
+            pam_section = pam_section.add(
+                cosmic::widget::settings::item::builder(title)
+                    .description(description)
+                    .control(text::heading(status)),
+            );
+        }
+
+        if let Some(plan) = &self.pam_plan {
+            let setup_btn = button::standard(fl!("pam-setup-preview"));
+            let (description, setup_btn) = if plan.is_empty() {
+                (fl!("pam-setup-done"), setup_btn)
+            } else if !self.session_has_prints() {
+                (fl!("pam-setup-no-prints"), setup_btn)
+            } else {
+                let description = fl!(
+                    "pam-setup-description",
+                    mechanism = plan.mechanism.localized_name()
+                );
+                if self.state.is_busy() {
+                    (description, setup_btn)
+                } else {
+                    (description, setup_btn.on_press(Message::EnablePam))
+                }
+            };
+            pam_section = pam_section.add(
+                cosmic::widget::settings::item::builder(fl!("pam-setup"))
+                    .description(description)
+                    .control(setup_btn),
+            );
+        }
         help.push(fprintd_section)
             .push(pam_section)
             .align_x(Alignment::Center)
@@ -101,6 +160,64 @@ impl AppModel {
             .into()
     }
 
+    /// Fingerprint changes read back from the journal, newest first
+    pub fn history(&self) -> Element<'_, Message> {
+        let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
+        let mut section = cosmic::widget::settings::section().title(fl!("history-description"));
+
+        match &self.history {
+            Err(err) => section = section.add(text(fl!("history-unavailable", err = err.as_str()))),
+            Ok(entries) if entries.is_empty() => section = section.add(text(fl!("history-empty"))),
+            Ok(entries) => {
+                for entry in entries {
+                    let mut title = entry
+                        .action
+                        .map(|action| action.localized_name())
+                        .unwrap_or_default();
+                    if let Some(finger) = entry.finger.as_deref().and_then(Finger::from_finger_id) {
+                        title = format!("{title} · {}", finger.localized_name());
+                    }
+                    // Only the sender is vouched for by journald.
+                    let actor = if entry.sender.is_empty() {
+                        &entry.actor
+                    } else {
+                        &entry.sender
+                    };
+                    let mut details = vec![fl!(
+                        "history-entry",
+                        actor = actor.as_str(),
+                        target = entry.target.as_str()
+                    )];
+                    if entry.is_forged() {
+                        details.push(fl!("history-forged", actor = entry.actor.as_str()));
+                    }
+                    if let Some(timestamp) = entry.timestamp {
+                        details.insert(0, format_time(timestamp));
+                    }
+                    if !entry.device_name.is_empty() {
+                        details.push(entry.device_name.clone());
+                    }
+                    let result = if entry.is_success() {
+                        fl!("history-success")
+                    } else {
+                        fl!("history-failed", result = entry.result.as_str())
+                    };
+                    section = section.add(
+                        cosmic::widget::settings::item::builder(title)
+                            .description(details.join(" · "))
+                            .control(text::caption(result)),
+                    );
+                }
+            }
+        }
+
+        Column::new()
+            .push(section)
+            .push(button::standard(fl!("history-refresh")).on_press(Message::RefreshHistory))
+            .spacing(space_xxs)
+            .into()
+    }
+
     /// Used to construct the main view of application
     ///
     /// **Returns** column with one or two rows of button widgets
@@ -275,17 +392,43 @@ impl AppModel {
     }
 
     /// Generates a bar reflecting how many succesful attempts away
-    /// enrolling print is
+    /// enrolling print is, next to whether the sensor detects a finger or
+    /// how to scan one while it waits
     ///
-    /// **Returns** progress_bar widget from *0* to *num_enroll_steps*
-    #[rustfmt::skip]
+    /// **Returns** progress_bar widget from *0* to *num_enroll_steps* with the
+    /// sensor indicator, which is shown alone while verifying
     pub(crate) fn view_progress(&self) -> Option<Element<'_, Message>> {
-        self.enrolling_finger.as_ref()?;
+        let cosmic_theme::Spacing { space_s, .. } = theme::active().cosmic().spacing;
+        let progress = match self.state {
+            OperationState::Enrolling { stage, .. } => self
+                .enroll_total_stages
+                .map(|total| ProgressBar::new(0.0..=(total as f32), stage as f32)),
+            OperationState::Verifying { .. } => None,
+            _ => return None,
+        };
+        let Some((sensor, indicator)) = self
+            .sensor
+            .and_then(|sensor| Some((sensor, sensor.localized_indicator()?)))
+        else {
+            return progress.map(Into::into);
+        };
 
-        self.enroll_total_stages
-            .map(|total| {
-            	ProgressBar::new(0.0..=(total as f32), self.enroll_progress as f32).into()
-            })
+        let mut icon = svg(svg::Handle::from_memory(FPRINT_ICON))
+            .symbolic(true)
+            .width(Length::Fixed(20.0))
+            .height(Length::Fixed(20.0));
+        if sensor.finger_present {
+            icon = icon.class(cosmic::theme::Svg::Custom(std::rc::Rc::new(|theme| {
+                cosmic::widget::svg::Style {
+                    color: Some(theme.cosmic().accent.base.into()),
+                }
+            })));
+        }
+        let mut row = Row::new().spacing(space_s).align_y(Vertical::Center);
+        if let Some(progress) = progress {
+            row = row.push(progress);
+        }
+        Some(row.push(icon).push(text::body(indicator)).into())
     }
 
     /// State dependent generation for main controls of the application:
@@ -294,52 +437,99 @@ impl AppModel {
     ///
     /// **Returns** row widget containing text button widget
     pub(crate) fn view_controls(&self) -> Element<'_, Message> {
-        let buttons_enabled =
-            !self.busy && self.device_path.is_some() && self.enrolling_finger.is_none();
+        let buttons_enabled = !self.state.is_busy() && self.device_path.is_some();
         let cosmic_theme::Spacing { space_s, .. } = theme::active().cosmic().spacing;
 
         let is_enrolled = self
             .enrolled_fingers
             .iter()
             .any(|ef| ef == self.selected_finger.as_finger_id());
+        // Without per-finger deletes only all of the user's prints can go.
+        let single_delete = self.capabilities.delete_finger;
 
-        let register_btn = button::suggested(fl!("register")).tooltip(fl!("register-tooltip"));
-        let verify_btn = button::standard(fl!("verify")).tooltip(fl!("verify-tooltip"));
-        let delete_btn = button::destructive(fl!("delete")).tooltip(fl!("delete-tooltip"));
+        let (manage, verify) = match &self.selected_user {
+            Some(user) => (self.manage_access(user), self.verify_access(user)),
+            None => (Access::Allowed, Access::Allowed),
+        };
+
+        let mut register_btn = button::suggested(fl!("register")).tooltip(
+            manage
+                .localized_hint()
+                .unwrap_or_else(|| fl!("register-tooltip")),
+        );
+        let mut verify_btn = button::standard(fl!("verify")).tooltip(
+            verify
+                .localized_hint()
+                .unwrap_or_else(|| fl!("verify-tooltip")),
+        );
+        let mut delete_btn = if single_delete {
+            button::destructive(fl!("delete")).tooltip(
+                manage
+                    .localized_hint()
+                    .unwrap_or_else(|| fl!("delete-tooltip")),
+            )
+        } else {
+            button::destructive(fl!("delete-all")).tooltip(
+                manage
+                    .localized_hint()
+                    .unwrap_or_else(|| fl!("delete-all-tooltip")),
+            )
+        };
+
+        if manage == Access::NeedsAuth {
+            register_btn = register_btn.trailing_icon(lock_icon());
+            delete_btn = delete_btn.trailing_icon(lock_icon());
+        }
+        if verify == Access::NeedsAuth {
+            verify_btn = verify_btn.trailing_icon(lock_icon());
+        }
 
-        let register_btn = if buttons_enabled {
+        let register_btn = if buttons_enabled && manage != Access::Denied {
             register_btn.on_press(Message::Register)
         } else {
             register_btn
         };
 
-        let verify_btn = if buttons_enabled && is_enrolled {
+        let verify_btn = if buttons_enabled && is_enrolled && verify != Access::Denied {
             verify_btn.on_press(Message::VerifyFinger)
         } else {
             verify_btn
         };
 
-        let delete_btn = if buttons_enabled && is_enrolled {
-            delete_btn.on_press(Message::Delete)
+        let (has_prints, on_delete) = if single_delete {
+            (is_enrolled, Message::Delete)
+        } else {
+            (!self.enrolled_fingers.is_empty(), Message::DeleteAll)
+        };
+        let delete_btn = if buttons_enabled && has_prints && manage != Access::Denied {
+            delete_btn.on_press(on_delete)
         } else {
             delete_btn
         };
 
-        let mut cancel_btn = button::standard(fl!("cancel"));
-        if self.enrolling_finger.is_some() {
-            cancel_btn = cancel_btn.on_press(Message::EnrollStop);
-        } else if self.verifying_finger {
-            cancel_btn = cancel_btn.on_press(Message::VerifyStop);
-        }
-
         let mut row = Row::new()
             .push(register_btn)
             .push(verify_btn)
             .push(delete_btn)
             .spacing(space_s);
 
-        if self.enrolling_finger.is_some() || self.verifying_finger {
-            row = row.push(cancel_btn);
+        if matches!(
+            self.state,
+            OperationState::Enrolling { .. } | OperationState::Verifying { .. }
+        ) {
+            row = row.push(button::standard(fl!("cancel")).on_press(Message::Cancel));
+        }
+
+        if let ServiceState::Failed(_) | ServiceState::Starting(_) = self.service {
+            let start_btn = button::suggested(fl!("start-service"))
+                .tooltip(fl!("start-service-tooltip"))
+                .trailing_icon(lock_icon());
+            let start_btn = if matches!(self.service, ServiceState::Failed(_)) {
+                start_btn.on_press(Message::StartService)
+            } else {
+                start_btn
+            };
+            row = row.push(start_btn);
         }
 
         row.apply(container)
@@ -348,4 +538,141 @@ impl AppModel {
             .padding([MAIN_PADDING, MAIN_PADDING])
             .into()
     }
+
+    /// Dialog to add a user by name who accounts-daemon does not list
+    pub fn add_user_dialog<'a>(&'a self, add_user: &'a AddUserDialog) -> Element<'a, Message> {
+        let cosmic_theme::Spacing { space_xs, .. } = theme::active().cosmic().spacing;
+        let can_search = !add_user.searching && !add_user.username.trim().is_empty();
+
+        let mut input = cosmic::widget::text_input(fl!("add-user-placeholder"), &add_user.username)
+            .on_input(Message::AddUserInput);
+        if can_search {
+            input = input.on_submit(|_| Message::AddUser);
+        }
+
+        let mut controls = Column::new()
+            .push(input)
+            .push(
+                cosmic::widget::checkbox(add_user.remember)
+                    .label(fl!("add-user-remember"))
+                    .on_toggle(Message::AddUserRemember),
+            )
+            .spacing(space_xs);
+        if let Some(error) = &add_user.error {
+            controls = controls.push(text::body(error));
+        }
+
+        let add_btn = button::suggested(fl!("add-user-confirm"));
+        cosmic::widget::dialog()
+            .title(fl!("add-user"))
+            .body(fl!("add-user-description"))
+            .control(controls)
+            .primary_action(if can_search {
+                add_btn.on_press(Message::AddUser)
+            } else {
+                add_btn
+            })
+            .secondary_action(button::standard(fl!("cancel")).on_press(Message::CloseAddUser))
+            .into()
+    }
+
+    /// Grid with a row per user in the nav bar and a column per finger,
+    /// whose enrolled prints can be selected to delete in bulk
+    pub(crate) fn view_overview<'a>(&'a self, overview: &'a Overview) -> Element<'a, Message> {
+        let cosmic_theme::Spacing {
+            space_xs, space_s, ..
+        } = theme::active().cosmic().spacing;
+        let editable = !self.state.is_busy();
+
+        let mut header = Row::new()
+            .push(text::heading(fl!("overview-user")).width(Length::FillPortion(3)))
+            .spacing(space_xs);
+        for finger in Finger::all() {
+            header = header.push(
+                container(text::caption(finger.localized_name()))
+                    .width(Length::FillPortion(1))
+                    .align_x(Horizontal::Center),
+            );
+        }
+
+        let mut grid = Column::new().push(header).spacing(space_xs);
+        for user in self
+            .nav
+            .iter()
+            .filter_map(|id| self.nav.data::<UserOption>(id))
+        {
+            let username = user.username.as_str();
+            let enrolled = self.user_prints.get(username);
+            let row_selected = enrolled.is_some_and(|enrolled| {
+                !enrolled.is_empty()
+                    && enrolled.iter().all(|id| {
+                        Finger::from_finger_id(id)
+                            .is_some_and(|finger| overview.is_selected(username, finger))
+                    })
+            });
+
+            let mut row_box = cosmic::widget::checkbox(row_selected).label(user.to_string());
+            if editable && enrolled.is_some_and(|enrolled| !enrolled.is_empty()) {
+                row_box =
+                    row_box.on_toggle(move |_| Message::ToggleOverviewRow(username.to_string()));
+            }
+            let mut row = Row::new()
+                .push(container(row_box).width(Length::FillPortion(3)))
+                .spacing(space_xs)
+                .align_y(Vertical::Center);
+
+            for finger in Finger::all().iter().copied() {
+                let cell: Element<'_, Message> = match enrolled {
+                    None => text::body(fl!("overview-unknown")).into(),
+                    Some(enrolled) if enrolled.iter().any(|id| id == finger.as_finger_id()) => {
+                        let mut cell_box =
+                            cosmic::widget::checkbox(overview.is_selected(username, finger));
+                        // Single prints cannot be deleted on their own
+                        // without per-finger deletes, whole rows can.
+                        if editable && self.capabilities.delete_finger {
+                            cell_box = cell_box.on_toggle(move |_| {
+                                Message::ToggleOverviewCell(username.to_string(), finger)
+                            });
+                        }
+                        cell_box.into()
+                    }
+                    Some(_) => text::body("—").into(),
+                };
+                row = row.push(
+                    container(cell)
+                        .width(Length::FillPortion(1))
+                        .align_x(Horizontal::Center),
+                );
+            }
+            grid = grid.push(row);
+        }
+
+        let count = self.overview_selected_count();
+        let access = self.overview_access();
+        let mut delete_btn = button::destructive(fl!("overview-delete", count = count));
+        if let Some(hint) = access.localized_hint() {
+            delete_btn = delete_btn.tooltip(hint);
+        }
+        if access == Access::NeedsAuth {
+            delete_btn = delete_btn.trailing_icon(lock_icon());
+        }
+        let delete_btn = if editable && count > 0 && access != Access::Denied {
+            delete_btn.on_press(Message::DeleteSelected)
+        } else {
+            delete_btn
+        };
+        let controls = Row::new()
+            .push(delete_btn)
+            .push(button::standard(fl!("overview-close")).on_press(Message::ToggleOverview))
+            .spacing(space_s);
+
+        Column::new()
+            .push(text::title3(fl!("overview")))
+            .push(cosmic::widget::scrollable(grid).height(Length::Fill))
+            .push(self.view_status())
+            .push(controls)
+            .spacing(MAIN_SPACING)
+            .padding(MAIN_PADDING)
+            .into()
+    }
 }
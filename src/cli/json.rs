@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::app::{message::DeviceOption, users::UserOption};
+
+/// A fingerprint device as printed by `devices --json`
+#[derive(Debug, Serialize)]
+pub struct DeviceRecord {
+    pub path: String,
+    pub name: String,
+    #[serde(rename = "num-enroll-stages")]
+    pub num_enroll_stages: Option<u32>,
+}
+
+impl From<DeviceOption> for DeviceRecord {
+    fn from(device: DeviceOption) -> Self {
+        Self {
+            path: device.path.to_string(),
+            name: device.name,
+            num_enroll_stages: device.num_enroll_stages,
+        }
+    }
+}
+
+/// A user and their enrolled fingers as printed by `users --json`
+#[derive(Debug, Serialize)]
+pub struct UserRecord {
+    pub username: String,
+    pub realname: String,
+    pub fingers: Vec<String>,
+}
+
+impl UserRecord {
+    pub fn new(user: &UserOption, fingers: Vec<String>) -> Self {
+        Self {
+            username: (*user.username).clone(),
+            realname: (*user.realname).clone(),
+            fingers,
+        }
+    }
+}
+
+/// Writes `value` as pretty printed JSON followed by a newline
+pub fn print<W: Write, T: Serialize>(out: &mut W, value: &T) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value)?;
+    writeln!(out)
+}
@@ -0,0 +1,1100 @@
+// SPDX-License-Identifier: MPL-2.0
+
+mod json;
+mod provision;
+
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+use clap::{Args, CommandFactory, Parser, Subcommand, error::ErrorKind};
+use cosmic::iced::futures::channel::mpsc;
+use futures_util::StreamExt;
+use futures_util::future::BoxFuture;
+use nix::unistd::{Uid, User};
+use zbus::zvariant::OwnedObjectPath;
+
+use crate::app::{
+    audit::{Action, Auditor, SUCCESS, failure, outcome},
+    backend::{BackendResult, FingerprintBackend, FprintdBackend},
+    doctor::{Check, CheckStatus, diagnose},
+    error::AppError,
+    finger::Finger,
+    message::Message,
+    pam_setup,
+    polkit::{self, Authorization},
+    status::{enroll_status_text, verify_status_text},
+    users::{UserOption, fetch_users},
+};
+use crate::fl;
+use json::{DeviceRecord, UserRecord};
+use provision::Manifest;
+
+/// The command finished successfully
+pub const EXIT_SUCCESS: u8 = 0;
+/// Unknown error, or the scan ended without success
+pub const EXIT_FAILURE: u8 = 1;
+/// The scanned finger did not match
+pub const EXIT_NO_MATCH: u8 = 20;
+/// Ctrl+C stopped the scan
+pub const EXIT_INTERRUPTED: u8 = 130;
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0    success
+  1    unknown error or failed scan
+  2    invalid arguments
+  3    permission denied
+  4    device already in use
+  5    device could not be claimed
+  6    device not found
+  7    no enrolled prints
+  8    prints not deleted
+  9    timeout
+  10   operation not supported by the fingerprint service
+  11   could not connect to the system bus
+  12   internal fprintd error
+  13   fingerprint service not installed
+  14   fingerprint service failed to start
+  20   fingerprint did not match
+  130  interrupted";
+
+/// Register, verify and delete fingerprints.
+///
+/// Starts the graphical interface when no command is given.
+#[derive(Debug, Parser)]
+#[command(version, after_help = EXIT_CODES)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List the enrolled fingers of a user
+    List(Target),
+    /// Enroll a finger, streaming scan progress
+    Enroll(Target),
+    /// Verify a finger, or any enrolled finger when none is given
+    Verify(Target),
+    /// Delete a finger, or every print of the user when none is given
+    Delete(Target),
+    /// Delete every print of a user, or of every known user with `--all`
+    Clear(ClearArgs),
+    /// List fingerprint devices as "path<TAB>name"
+    Devices(Target),
+    /// List known users, or only the given user, with their enrolled fingers
+    Users(Target),
+    /// Enroll the fingers listed in a TOML or JSON manifest, asking before each scan
+    Provision(ProvisionArgs),
+    /// Check the fingerprint stack and suggest fixes for failures
+    Doctor,
+    /// Enable pam_fprintd as previewed in the app, run as root through pkexec
+    #[command(hide = true)]
+    PamSetup(PamSetupArgs),
+}
+
+/// Options shared by every command.
+#[derive(Debug, Clone, Default, Args)]
+pub struct Target {
+    /// User whose prints to manage [default: current user]
+    #[arg(short, long)]
+    pub user: Option<String>,
+    /// Device object path, or its index in `devices` [default: fprintd's default device]
+    #[arg(short, long)]
+    pub device: Option<String>,
+    /// Finger such as `right-index` or `right-index-finger`
+    #[arg(short, long, value_parser = parse_finger)]
+    pub finger: Option<Finger>,
+    /// Print `devices`, `list` and `users` output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ClearArgs {
+    #[command(flatten)]
+    pub target: Target,
+    /// Delete the prints of every known user instead of a single one
+    #[arg(long, conflicts_with = "user")]
+    pub all: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ProvisionArgs {
+    /// Manifest mapping users to fingers, e.g. `alice = "right-index, left-index"`
+    /// under a `[users]` table
+    #[arg(value_parser = Manifest::load)]
+    pub manifest: Manifest,
+    /// Device object path, or its index in `devices` [default: fprintd's default device]
+    #[arg(short, long)]
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PamSetupArgs {
+    /// Device on which the invoking user must have prints [default: fprintd's default device]
+    #[arg(short, long)]
+    pub device: Option<String>,
+}
+
+fn parse_finger(id: &str) -> Result<Finger, String> {
+    Finger::from_finger_id(id).ok_or_else(|| {
+        let ids: Vec<_> = Finger::all().iter().map(|f| f.as_finger_id()).collect();
+        format!("expected one of: {}", ids.join(", "))
+    })
+}
+
+/// Maps errors to distinct exit codes, see [`EXIT_CODES`]
+pub fn exit_code(err: &AppError) -> u8 {
+    match err {
+        AppError::Unknown(_) => EXIT_FAILURE,
+        AppError::PermissionDenied => 3,
+        AppError::AlreadyInUse => 4,
+        AppError::ClaimDevice => 5,
+        AppError::DeviceNotFound => 6,
+        AppError::NoEnrolledPrints => 7,
+        AppError::PrintsNotDeleted => 8,
+        AppError::Timeout => 9,
+        AppError::UnsupportedOperation => 10,
+        AppError::ConnectDbus(_) => 11,
+        AppError::Internal => 12,
+        AppError::ServiceNotInstalled => 13,
+        AppError::ServiceFailed => 14,
+    }
+}
+
+/// Runs a command against fprintd on the system bus.
+///
+/// **Returns** the process exit code
+pub fn run(command: Command) -> u8 {
+    if let Command::Enroll(target) = &command
+        && target.finger.is_none()
+    {
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                fl!("cli-finger-required"),
+            )
+            .exit();
+    }
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_FAILURE;
+        }
+    };
+
+    let result = runtime.block_on(async {
+        // Diagnostics report a missing system bus themselves.
+        if let Command::Doctor = command {
+            return Ok(doctor(&mut std::io::stdout()).await);
+        }
+
+        let connection = zbus::Connection::system()
+            .await
+            .map_err(|err| AppError::ConnectDbus(err.to_string()))?;
+        let backend = Arc::new(FprintdBackend::new(connection.clone()));
+        let authorize_clear = move || -> BoxFuture<'static, Authorization> {
+            let connection = connection.clone();
+            Box::pin(
+                async move { polkit::authorize(&connection, polkit::CLEAR_DEVICE_ACTION).await },
+            )
+        };
+        Runner::new(backend, std::io::stdin().lock(), std::io::stdout())
+            .with_authorize_clear(authorize_clear)
+            .execute(command)
+            .await
+    });
+
+    match result {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}", err.localized_message());
+            exit_code(&err)
+        }
+    }
+}
+
+/// Prints a diagnostics report with a hint below each failure
+///
+/// **Returns** [`EXIT_FAILURE`] if any check failed
+fn print_report<W: Write>(out: &mut W, checks: &[Check]) -> u8 {
+    for check in checks {
+        let _ = write!(
+            out,
+            "[{}] {}",
+            check.status.localized_name(),
+            check.kind.localized_name()
+        );
+        if check.detail.is_empty() {
+            let _ = writeln!(out);
+        } else {
+            let _ = writeln!(out, ": {}", check.detail);
+        }
+        if let Some(hint) = &check.hint {
+            let _ = writeln!(out, "    {hint}");
+        }
+    }
+
+    if checks.iter().any(|check| check.status == CheckStatus::Fail) {
+        EXIT_FAILURE
+    } else {
+        EXIT_SUCCESS
+    }
+}
+
+async fn doctor<W: Write>(out: &mut W) -> u8 {
+    print_report(out, &diagnose().await)
+}
+
+/// Executes commands through a backend, reading answers from `input` and
+/// writing their output to `out`.
+struct Runner<R, W> {
+    backend: Arc<dyn FingerprintBackend>,
+    input: R,
+    out: W,
+    /// Asks for a fresh authentication before clearing the whole device,
+    /// like the app's *Clear Device*
+    authorize_clear: Box<dyn Fn() -> BoxFuture<'static, Authorization>>,
+}
+
+impl<R: BufRead, W: Write> Runner<R, W> {
+    /// A runner which refuses to clear the whole device until given a way
+    /// to authorize it
+    fn new(backend: Arc<dyn FingerprintBackend>, input: R, out: W) -> Self {
+        Self {
+            backend,
+            input,
+            out,
+            authorize_clear: Box::new(|| Box::pin(async { Authorization::Refused })),
+        }
+    }
+
+    fn with_authorize_clear(
+        mut self,
+        authorize: impl Fn() -> BoxFuture<'static, Authorization> + 'static,
+    ) -> Self {
+        self.authorize_clear = Box::new(authorize);
+        self
+    }
+
+    /// **Returns** the exit code of a finished command
+    async fn execute(&mut self, command: Command) -> Result<u8, AppError> {
+        match command {
+            Command::Devices(target) => self.devices(target.json).await,
+            Command::List(target) => {
+                let device = self.device(target.device.as_deref()).await?;
+                self.list(&device, &username(&target)?, target.json).await
+            }
+            Command::Users(target) => {
+                let device = self.device(target.device.as_deref()).await?;
+                let mut users = fetch_users().await;
+                if let Some(username) = target.user {
+                    users.retain(|user| *user.username == username);
+                    if users.is_empty() {
+                        users.push(UserOption {
+                            username: Arc::new(username),
+                            ..Default::default()
+                        });
+                    }
+                } else {
+                    users.retain(|user| !user.system);
+                }
+                self.users(&device, users, target.json).await
+            }
+            Command::Doctor => Ok(doctor(&mut self.out).await),
+            Command::PamSetup(args) => {
+                let device = self.device(args.device.as_deref()).await?;
+                self.pam_setup(&device, &invoking_user()?).await
+            }
+            Command::Provision(args) => {
+                let device = self.device(args.device.as_deref()).await?;
+                self.provision(&device, &args.manifest).await
+            }
+            Command::Enroll(target) => {
+                let finger = target
+                    .finger
+                    .ok_or_else(|| AppError::Unknown(fl!("cli-finger-required")))?;
+                let device = self.device(target.device.as_deref()).await?;
+                self.enroll(&device, &username(&target)?, finger).await
+            }
+            Command::Verify(target) => {
+                let device = self.device(target.device.as_deref()).await?;
+                self.verify(&device, &username(&target)?, target.finger)
+                    .await
+            }
+            Command::Delete(target) => {
+                let device = self.device(target.device.as_deref()).await?;
+                self.delete(&device, &username(&target)?, target.finger)
+                    .await
+            }
+            Command::Clear(args) => {
+                let device = self.device(args.target.device.as_deref()).await?;
+                let usernames = if args.all {
+                    fetch_users()
+                        .await
+                        .into_iter()
+                        .map(|user| (*user.username).clone())
+                        .collect()
+                } else {
+                    vec![username(&args.target)?]
+                };
+                self.clear(&device, usernames, args.all).await
+            }
+        }
+    }
+
+    /// **Returns** an auditor for changes on `device`, named when the
+    /// device list has it
+    async fn auditor(&self, device: &OwnedObjectPath) -> Auditor {
+        let name = self
+            .backend
+            .list_devices()
+            .await
+            .ok()
+            .and_then(|devices| devices.into_iter().find(|option| option.path == *device))
+            .map(|option| option.name)
+            .unwrap_or_default();
+        Auditor::new(&name, device.as_str())
+    }
+
+    /// Resolves `--device` as an object path or an index into the device list
+    async fn device(&self, device: Option<&str>) -> Result<OwnedObjectPath, AppError> {
+        let Some(device) = device else {
+            return self.backend.find_device().await;
+        };
+
+        if let Ok(index) = device.parse::<usize>() {
+            return self
+                .backend
+                .list_devices()
+                .await?
+                .into_iter()
+                .nth(index)
+                .map(|device| device.path)
+                .ok_or(AppError::DeviceNotFound);
+        }
+
+        OwnedObjectPath::try_from(device).map_err(|_| AppError::DeviceNotFound)
+    }
+
+    async fn devices(&mut self, json: bool) -> Result<u8, AppError> {
+        let devices = self.backend.list_devices().await?;
+        if json {
+            let records: Vec<DeviceRecord> = devices.into_iter().map(DeviceRecord::from).collect();
+            let _ = json::print(&mut self.out, &records);
+        } else {
+            for device in devices {
+                let _ = writeln!(self.out, "{}\t{}", device.path.as_str(), device.name);
+            }
+        }
+        Ok(EXIT_SUCCESS)
+    }
+
+    /// **Returns** the enrolled finger ids of a user, empty if there are none
+    async fn enrolled_fingers(
+        &self,
+        device: &OwnedObjectPath,
+        username: &str,
+    ) -> Result<Vec<String>, AppError> {
+        match self
+            .backend
+            .list_enrolled_fingers(device.clone(), username.to_string())
+            .await
+        {
+            Ok(fingers) => Ok(fingers),
+            Err(AppError::NoEnrolledPrints) => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn list(
+        &mut self,
+        device: &OwnedObjectPath,
+        username: &str,
+        json: bool,
+    ) -> Result<u8, AppError> {
+        let fingers = self.enrolled_fingers(device, username).await?;
+        if json {
+            let _ = json::print(&mut self.out, &fingers);
+        } else {
+            for finger in fingers {
+                let _ = writeln!(self.out, "{finger}");
+            }
+        }
+        Ok(EXIT_SUCCESS)
+    }
+
+    async fn users(
+        &mut self,
+        device: &OwnedObjectPath,
+        users: Vec<UserOption>,
+        json: bool,
+    ) -> Result<u8, AppError> {
+        let mut records = Vec::with_capacity(users.len());
+        for user in &users {
+            let fingers = self.enrolled_fingers(device, &user.username).await?;
+            records.push(UserRecord::new(user, fingers));
+        }
+
+        if json {
+            let _ = json::print(&mut self.out, &records);
+        } else {
+            for record in records {
+                let _ = writeln!(
+                    self.out,
+                    "{}\t{}",
+                    record.username,
+                    record.fingers.join(",")
+                );
+            }
+        }
+        Ok(EXIT_SUCCESS)
+    }
+
+    async fn enroll(
+        &mut self,
+        device: &OwnedObjectPath,
+        username: &str,
+        finger: Finger,
+    ) -> Result<u8, AppError> {
+        let (output, receiver) = mpsc::channel(16);
+        let operation = self.backend.enroll(
+            device.clone(),
+            finger.as_finger_id().to_string(),
+            username.to_string(),
+            output,
+        );
+
+        let auditor = self.auditor(device).await;
+        let mut total = None;
+        let mut stage = 0;
+        let mut last_status = String::new();
+        let watched = self
+            .watch(operation, receiver, |out, message| match message {
+                Message::EnrollStart(stages) => {
+                    total = stages;
+                    let _ = writeln!(out, "{}", fl!("enroll-starting"));
+                    Ok(None)
+                }
+                Message::EnrollStatus(status, done) => {
+                    if status == "enroll-stage-passed" {
+                        stage += 1;
+                    }
+                    last_status.clone_from(&status);
+                    let text = enroll_status_text(&status);
+                    match total {
+                        Some(total) if !done => {
+                            let _ = writeln!(out, "[{stage}/{total}] {text}");
+                        }
+                        _ => {
+                            let _ = writeln!(out, "{text}");
+                        }
+                    }
+                    Ok(done.then_some(if status == "enroll-completed" {
+                        EXIT_SUCCESS
+                    } else {
+                        EXIT_FAILURE
+                    }))
+                }
+                Message::OperationError(err) => Err(err),
+                _ => Ok(None),
+            })
+            .await;
+
+        let result = match &watched {
+            Some(Ok(EXIT_SUCCESS)) => SUCCESS.to_string(),
+            Some(Ok(_)) => last_status,
+            Some(Err(err)) => failure(err),
+            None => "enroll-cancelled".to_string(),
+        };
+        auditor.record(
+            Action::Enroll,
+            username,
+            Some(finger.as_finger_id()),
+            &result,
+        );
+
+        match watched {
+            Some(result) => result,
+            None => {
+                let _ = self.backend.enroll_stop(device.clone()).await;
+                let _ = writeln!(self.out, "{}", enroll_status_text("enroll-cancelled"));
+                Ok(EXIT_INTERRUPTED)
+            }
+        }
+    }
+
+    async fn verify(
+        &mut self,
+        device: &OwnedObjectPath,
+        username: &str,
+        finger: Option<Finger>,
+    ) -> Result<u8, AppError> {
+        let finger = finger.map_or("any", |finger| finger.as_finger_id());
+        let (output, receiver) = mpsc::channel(16);
+        let operation = self.backend.verify(
+            device.clone(),
+            finger.to_string(),
+            username.to_string(),
+            output,
+        );
+
+        let watched = self
+            .watch(operation, receiver, |out, message| match message {
+                Message::VerifyStatus(status, done) => {
+                    let _ = writeln!(out, "{}", verify_status_text(&status));
+                    Ok(done.then_some(match status.as_str() {
+                        "verify-match" => EXIT_SUCCESS,
+                        "verify-no-match" => EXIT_NO_MATCH,
+                        _ => EXIT_FAILURE,
+                    }))
+                }
+                Message::OperationError(err) => Err(err),
+                _ => Ok(None),
+            })
+            .await;
+
+        match watched {
+            Some(result) => result,
+            None => {
+                let _ = self.backend.verify_stop(device.clone()).await;
+                let _ = writeln!(self.out, "{}", verify_status_text("verify-cancelled"));
+                Ok(EXIT_INTERRUPTED)
+            }
+        }
+    }
+
+    /// Drives an enroll or verify while handing its progress messages to
+    /// `on_message`, which returns the exit code once the scan is done.
+    ///
+    /// **Returns** `None` if Ctrl+C interrupted the scan
+    async fn watch(
+        &mut self,
+        operation: BoxFuture<'static, BackendResult<()>>,
+        mut receiver: mpsc::Receiver<Message>,
+        mut on_message: impl FnMut(&mut W, Message) -> Result<Option<u8>, AppError>,
+    ) -> Option<Result<u8, AppError>> {
+        let out = &mut self.out;
+        let progress = async move {
+            let mut code = EXIT_FAILURE;
+            while let Some(message) = receiver.next().await {
+                if let Some(done) = on_message(out, message)? {
+                    code = done;
+                }
+            }
+            Ok(code)
+        };
+
+        tokio::select! {
+            (result, code) = futures_util::future::join(operation, progress) => {
+                Some(result.and(code))
+            }
+            _ = tokio::signal::ctrl_c() => None,
+        }
+    }
+
+    async fn delete(
+        &mut self,
+        device: &OwnedObjectPath,
+        username: &str,
+        finger: Option<Finger>,
+    ) -> Result<u8, AppError> {
+        let auditor = self.auditor(device).await;
+        match finger {
+            Some(finger) => {
+                let result = self
+                    .backend
+                    .delete_finger(
+                        device.clone(),
+                        username.to_string(),
+                        finger.as_finger_id().to_string(),
+                    )
+                    .await;
+                auditor.record(
+                    Action::Delete,
+                    username,
+                    Some(finger.as_finger_id()),
+                    &outcome(&result),
+                );
+                result?
+            }
+            None => {
+                let result = self
+                    .backend
+                    .delete_all(device.clone(), username.to_string())
+                    .await;
+                auditor.record(Action::DeleteAll, username, None, &outcome(&result));
+                result?
+            }
+        }
+        let _ = writeln!(self.out, "{}", fl!("deleted"));
+        Ok(EXIT_SUCCESS)
+    }
+
+    /// Applies the PAM changes the app previewed, working them out again
+    /// instead of trusting the unprivileged caller. Like the app, it refuses
+    /// while `username` has no prints on `device` to sign in with.
+    async fn pam_setup(
+        &mut self,
+        device: &OwnedObjectPath,
+        username: &str,
+    ) -> Result<u8, AppError> {
+        if self.enrolled_fingers(device, username).await?.is_empty() {
+            return Err(AppError::Unknown(fl!("pam-setup-no-prints")));
+        }
+        if !Uid::effective().is_root() {
+            return Err(AppError::Unknown(fl!("pam-setup-not-root")));
+        }
+
+        let plan = pam_setup::plan(std::path::Path::new("/"));
+        if plan.is_empty() {
+            let _ = writeln!(self.out, "{}", fl!("pam-setup-done"));
+            return Ok(EXIT_SUCCESS);
+        }
+        pam_setup::apply(&plan).map_err(AppError::Unknown)?;
+        let _ = writeln!(self.out, "{}", fl!("pam-setup-applied"));
+        Ok(EXIT_SUCCESS)
+    }
+
+    /// Deletes every print of `usernames`. Clearing `all` users or several
+    /// of them needs a fresh authentication first, a single user does not.
+    ///
+    /// **Returns** the exit code, or why the prints were not deleted
+    async fn clear(
+        &mut self,
+        device: &OwnedObjectPath,
+        usernames: Vec<String>,
+        all: bool,
+    ) -> Result<u8, AppError> {
+        if all || usernames.len() > 1 {
+            match (self.authorize_clear)().await {
+                Authorization::Granted => {}
+                Authorization::Refused => return Err(AppError::PermissionDenied),
+                not_registered => {
+                    return Err(AppError::Unknown(
+                        not_registered.localized_clear_error().unwrap_or_default(),
+                    ));
+                }
+            }
+        }
+        let auditor = self.auditor(device).await;
+        let result = self.backend.clear(device.clone(), usernames.clone()).await;
+        let result_text = outcome(&result);
+        for user in &usernames {
+            auditor.record(Action::Clear, user, None, &result_text);
+        }
+        result?;
+        for user in usernames {
+            let _ = writeln!(self.out, "{}", fl!("cli-cleared", user = user));
+        }
+        Ok(EXIT_SUCCESS)
+    }
+}
+
+/// **Returns** the `--user` option or the name of the current user
+fn username(target: &Target) -> Result<String, AppError> {
+    if let Some(user) = &target.user {
+        return Ok(user.clone());
+    }
+    User::from_uid(Uid::current())
+        .ok()
+        .flatten()
+        .map(|user| user.name)
+        .ok_or_else(|| AppError::Unknown(fl!("cli-unknown-user")))
+}
+
+/// **Returns** the name of the user who ran pkexec, or of the current user
+/// when not run through pkexec
+fn invoking_user() -> Result<String, AppError> {
+    let uid = std::env::var("PKEXEC_UID")
+        .ok()
+        .and_then(|uid| uid.parse().ok())
+        .map_or_else(Uid::current, Uid::from_raw);
+    User::from_uid(uid)
+        .ok()
+        .flatten()
+        .map(|user| user.name)
+        .ok_or_else(|| AppError::Unknown(fl!("cli-unknown-user")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::backend::fake::FakeBackend;
+    use crate::app::doctor::CheckKind;
+
+    fn runner(fake: &FakeBackend) -> Runner<&'static [u8], Vec<u8>> {
+        Runner::new(Arc::new(fake.clone()), &[][..], Vec::new())
+            .with_authorize_clear(|| Box::pin(async { Authorization::Granted }))
+    }
+
+    fn output(runner: &Runner<&[u8], Vec<u8>>) -> String {
+        String::from_utf8(runner.out.clone()).unwrap()
+    }
+
+    fn parse(args: &[&str]) -> Command {
+        let args = std::iter::once("cosmic-utils-enroll").chain(args.iter().copied());
+        Cli::try_parse_from(args).unwrap().command.unwrap()
+    }
+
+    #[test]
+    fn test_print_report() {
+        let checks = [
+            Check {
+                kind: CheckKind::SystemBus,
+                status: CheckStatus::Pass,
+                detail: "ok".to_string(),
+                hint: None,
+            },
+            Check {
+                kind: CheckKind::Devices,
+                status: CheckStatus::Fail,
+                detail: "none".to_string(),
+                hint: Some("plug one in".to_string()),
+            },
+        ];
+        let mut out = Vec::new();
+
+        assert_eq!(print_report(&mut out, &checks[..1]), EXIT_SUCCESS);
+        assert_eq!(print_report(&mut out, &checks), EXIT_FAILURE);
+        let lines: Vec<_> = std::str::from_utf8(&out).unwrap().lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with(&format!("[{}]", fl!("doctor-fail"))));
+        assert!(lines[2].ends_with(": none"));
+        assert_eq!(lines[3], "    plug one in");
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        assert!(
+            Cli::try_parse_from(["cosmic-utils-enroll"])
+                .unwrap()
+                .command
+                .is_none()
+        );
+
+        let command = parse(&["enroll", "--user", "alice", "--finger", "right-index"]);
+        let Command::Enroll(target) = command else {
+            panic!("expected enroll, got {command:?}");
+        };
+        assert_eq!(target.user.as_deref(), Some("alice"));
+        assert_eq!(target.finger, Some(Finger::RightIndex));
+        assert!(target.device.is_none());
+
+        let command = parse(&["verify", "-d", "1", "-f", "left-little-finger"]);
+        let Command::Verify(target) = command else {
+            panic!("expected verify, got {command:?}");
+        };
+        assert_eq!(target.finger, Some(Finger::LeftPinky));
+        assert_eq!(target.device.as_deref(), Some("1"));
+
+        let err =
+            Cli::try_parse_from(["cosmic-utils-enroll", "delete", "--finger", "nose"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            AppError::Unknown(String::new()),
+            AppError::PermissionDenied,
+            AppError::AlreadyInUse,
+            AppError::ClaimDevice,
+            AppError::DeviceNotFound,
+            AppError::NoEnrolledPrints,
+            AppError::PrintsNotDeleted,
+            AppError::Timeout,
+            AppError::UnsupportedOperation,
+            AppError::ConnectDbus(String::new()),
+            AppError::Internal,
+            AppError::ServiceNotInstalled,
+            AppError::ServiceFailed,
+        ];
+        let mut codes: Vec<u8> = errors.iter().map(exit_code).collect();
+        codes.extend([EXIT_SUCCESS, 2, EXIT_NO_MATCH, EXIT_INTERRUPTED]);
+        let count = codes.len();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), count);
+    }
+
+    #[tokio::test]
+    async fn test_devices_and_device_lookup() {
+        let fake = FakeBackend::default();
+        let mut runner = runner(&fake);
+
+        let code = runner.execute(parse(&["devices"])).await.unwrap();
+        assert_eq!(code, EXIT_SUCCESS);
+        assert_eq!(
+            output(&runner),
+            "/net/reactivated/Fprint/Device/0\tFake Reader\n"
+        );
+
+        assert_eq!(runner.device(Some("0")).await.unwrap(), fake.device.path);
+        assert_eq!(runner.device(None).await.unwrap(), fake.device.path);
+        assert_eq!(
+            runner.device(Some("1")).await.unwrap_err(),
+            AppError::DeviceNotFound
+        );
+
+        let err = runner
+            .execute(parse(&[
+                "list",
+                "-u",
+                "alice",
+                "-d",
+                "/net/reactivated/Fprint/Device/7",
+            ]))
+            .await
+            .unwrap_err();
+        assert_eq!(err, AppError::DeviceNotFound);
+    }
+
+    #[tokio::test]
+    async fn test_list() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb", "right-thumb"]);
+        let mut runner = runner(&fake);
+
+        runner
+            .execute(parse(&["list", "-u", "alice"]))
+            .await
+            .unwrap();
+        assert_eq!(output(&runner), "left-thumb\nright-thumb\n");
+
+        // Users without prints are an empty list, not an error.
+        let code = runner.execute(parse(&["list", "-u", "bob"])).await.unwrap();
+        assert_eq!(code, EXIT_SUCCESS);
+        assert_eq!(output(&runner), "left-thumb\nright-thumb\n");
+    }
+
+    #[tokio::test]
+    async fn test_enroll_streams_progress() {
+        let fake = FakeBackend::default();
+        let mut runner = runner(&fake);
+
+        let code = runner
+            .execute(parse(&["enroll", "-u", "alice", "-f", "left-index"]))
+            .await
+            .unwrap();
+
+        assert_eq!(code, EXIT_SUCCESS);
+        let stage = fl!("enroll-stage-passed");
+        assert_eq!(
+            output(&runner),
+            format!(
+                "{}\n[1/2] {stage}\n[2/2] {stage}\n{}\n",
+                fl!("enroll-starting"),
+                fl!("enroll-completed")
+            )
+        );
+        assert_eq!(fake.enrolled("alice"), vec!["left-index-finger"]);
+    }
+
+    #[tokio::test]
+    async fn test_verify() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut runner = runner(&fake);
+
+        let code = runner
+            .execute(parse(&["verify", "-u", "alice"]))
+            .await
+            .unwrap();
+        assert_eq!(code, EXIT_SUCCESS);
+        assert_eq!(output(&runner), format!("{}\n", fl!("verify-match")));
+        assert_eq!(fake.calls().last().unwrap(), "Verify(alice, any)");
+
+        let err = runner
+            .execute(parse(&["verify", "-u", "alice", "-f", "right-thumb"]))
+            .await
+            .unwrap_err();
+        assert_eq!(exit_code(&err), 7);
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let fake = FakeBackend::default()
+            .with_enrolled("alice", &["left-thumb", "right-thumb"])
+            .with_enrolled("bob", &["left-thumb"]);
+        let mut runner = runner(&fake);
+
+        runner
+            .execute(parse(&["delete", "-u", "alice", "-f", "left-thumb"]))
+            .await
+            .unwrap();
+        assert_eq!(fake.enrolled("alice"), vec!["right-thumb"]);
+
+        runner
+            .execute(parse(&["delete", "-u", "alice"]))
+            .await
+            .unwrap();
+        assert!(fake.enrolled("alice").is_empty());
+        assert_eq!(fake.enrolled("bob"), vec!["left-thumb"]);
+        assert_eq!(output(&runner), format!("{0}\n{0}\n", fl!("deleted")));
+    }
+
+    #[tokio::test]
+    async fn test_delete_single_unsupported() {
+        let fake = FakeBackend::default()
+            .open_fprintd()
+            .with_enrolled("alice", &["left-thumb"]);
+        let mut runner = runner(&fake);
+
+        let err = runner
+            .execute(parse(&["delete", "-u", "alice", "-f", "left-thumb"]))
+            .await
+            .unwrap_err();
+        assert_eq!(err, AppError::UnsupportedOperation);
+        assert_eq!(fake.enrolled("alice"), vec!["left-thumb"]);
+    }
+
+    #[tokio::test]
+    async fn test_clear_single_user() {
+        let fake = FakeBackend::default()
+            .with_enrolled("alice", &["left-thumb"])
+            .with_enrolled("bob", &["left-thumb"]);
+        let mut runner = runner(&fake);
+
+        runner
+            .execute(parse(&["clear", "-u", "alice"]))
+            .await
+            .unwrap();
+        assert!(fake.enrolled("alice").is_empty());
+        assert_eq!(fake.enrolled("bob"), vec!["left-thumb"]);
+        assert_eq!(fake.calls().last().unwrap(), "Clear(alice)");
+        assert_eq!(
+            output(&runner),
+            format!("{}\n", fl!("cli-cleared", user = "alice"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pam_setup_requires_prints() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut runner = runner(&fake);
+
+        let result = runner.pam_setup(&fake.device.path, "bob").await;
+        assert_eq!(result, Err(AppError::Unknown(fl!("pam-setup-no-prints"))));
+        assert!(output(&runner).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_clear_requires_authorization() {
+        let fake = FakeBackend::default()
+            .with_enrolled("alice", &["left-thumb"])
+            .with_enrolled("bob", &["left-thumb"]);
+        let device = fake.device.path.clone();
+        let users = || vec!["alice".to_string(), "bob".to_string()];
+        let mut runner =
+            runner(&fake).with_authorize_clear(|| Box::pin(async { Authorization::Refused }));
+
+        let result = runner.clear(&device, users(), false).await;
+        assert_eq!(result, Err(AppError::PermissionDenied));
+        let result = runner.clear(&device, vec!["alice".to_string()], true).await;
+        assert_eq!(result, Err(AppError::PermissionDenied));
+
+        let mut runner =
+            runner.with_authorize_clear(|| Box::pin(async { Authorization::NotRegistered }));
+        let result = runner.clear(&device, users(), false).await;
+        assert!(matches!(result, Err(AppError::Unknown(_))));
+        assert!(!fake.calls().iter().any(|call| call.starts_with("Clear")));
+        assert_eq!(fake.enrolled("alice"), vec!["left-thumb"]);
+    }
+
+    #[tokio::test]
+    async fn test_clear_single_user_without_authorization() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb"]);
+        let mut runner =
+            runner(&fake).with_authorize_clear(|| Box::pin(async { Authorization::Refused }));
+
+        runner
+            .execute(parse(&["clear", "-u", "alice"]))
+            .await
+            .unwrap();
+        assert!(fake.enrolled("alice").is_empty());
+        assert_eq!(fake.calls().last().unwrap(), "Clear(alice)");
+    }
+
+    #[tokio::test]
+    async fn test_clear_defaults_to_current_user() {
+        let current = User::from_uid(Uid::current()).unwrap().unwrap().name;
+        let fake = FakeBackend::default()
+            .with_enrolled(&current, &["left-thumb"])
+            .with_enrolled("alice", &["left-thumb"]);
+        let mut runner = runner(&fake);
+
+        runner.execute(parse(&["clear"])).await.unwrap();
+        assert_eq!(fake.calls().last().unwrap(), &format!("Clear({current})"));
+        assert!(fake.enrolled(&current).is_empty());
+        assert_eq!(fake.enrolled("alice"), vec!["left-thumb"]);
+
+        // Every user is only cleared when asked for explicitly.
+        let args = ["cosmic-utils-enroll", "clear", "--all", "-u", "alice"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_output() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb", "right-thumb"]);
+        let mut runner = runner(&fake);
+
+        runner.execute(parse(&["devices", "--json"])).await.unwrap();
+        let devices: serde_json::Value = serde_json::from_str(&output(&runner)).unwrap();
+        assert_eq!(
+            devices,
+            serde_json::json!([{
+                "path": "/net/reactivated/Fprint/Device/0",
+                "name": "Fake Reader",
+                "num-enroll-stages": 2,
+            }])
+        );
+
+        let mut runner = self::runner(&fake);
+        runner
+            .execute(parse(&["list", "-u", "alice", "--json"]))
+            .await
+            .unwrap();
+        let fingers: serde_json::Value = serde_json::from_str(&output(&runner)).unwrap();
+        assert_eq!(fingers, serde_json::json!(["left-thumb", "right-thumb"]));
+    }
+
+    #[tokio::test]
+    async fn test_users() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["left-thumb", "right-thumb"]);
+        let path = fake.device.path.clone();
+        let users = || {
+            vec![
+                UserOption {
+                    username: Arc::new("alice".to_string()),
+                    realname: Arc::new("Alice Liddell".to_string()),
+                    ..Default::default()
+                },
+                UserOption {
+                    username: Arc::new("bob".to_string()),
+                    ..Default::default()
+                },
+            ]
+        };
+
+        let mut runner = runner(&fake);
+        runner.users(&path, users(), false).await.unwrap();
+        assert_eq!(output(&runner), "alice\tleft-thumb,right-thumb\nbob\t\n");
+
+        let mut runner = self::runner(&fake);
+        runner.users(&path, users(), true).await.unwrap();
+        let records: serde_json::Value = serde_json::from_str(&output(&runner)).unwrap();
+        assert_eq!(
+            records,
+            serde_json::json!([
+                {
+                    "username": "alice",
+                    "realname": "Alice Liddell",
+                    "fingers": ["left-thumb", "right-thumb"],
+                },
+                {"username": "bob", "realname": "", "fingers": []},
+            ])
+        );
+    }
+}
@@ -0,0 +1,318 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use indexmap::IndexMap;
+use serde::Deserialize;
+use zbus::zvariant::OwnedObjectPath;
+
+use super::{EXIT_FAILURE, EXIT_INTERRUPTED, EXIT_SUCCESS, Runner};
+use crate::app::{error::AppError, finger::Finger};
+use crate::fl;
+
+/// Users and the fingers each of them must have enrolled, in the order
+/// of the file.
+///
+/// Read from TOML, or JSON when the file ends in `.json`:
+///
+/// ```toml
+/// [users]
+/// alice = ["right-index", "left-index"]
+/// bob = "right-thumb, right-index"
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub users: Vec<(String, Vec<Finger>)>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    users: IndexMap<String, Fingers>,
+}
+
+/// A list of fingers, or a single comma separated string of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Fingers {
+    List(Vec<String>),
+    Text(String),
+}
+
+impl Manifest {
+    /// Reads a manifest from a file, used as a clap value parser
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+        let json = Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        Self::parse(&content, json)
+    }
+
+    /// Parses a TOML or JSON manifest, rejecting unknown finger names
+    pub fn parse(content: &str, json: bool) -> Result<Self, String> {
+        let file: ManifestFile = if json {
+            serde_json::from_str(content).map_err(|err| err.to_string())?
+        } else {
+            toml::from_str(content).map_err(|err| err.to_string())?
+        };
+
+        let mut users = Vec::with_capacity(file.users.len());
+        for (username, fingers) in file.users {
+            let ids: Vec<String> = match fingers {
+                Fingers::List(ids) => ids,
+                Fingers::Text(text) => text.split(',').map(|id| id.trim().to_string()).collect(),
+            };
+
+            let mut fingers = Vec::with_capacity(ids.len());
+            for id in ids.iter().filter(|id| !id.is_empty()) {
+                let finger = Finger::from_finger_id(id)
+                    .ok_or_else(|| format!("{username}: unknown finger `{id}`"))?;
+                if !fingers.contains(&finger) {
+                    fingers.push(finger);
+                }
+            }
+            users.push((username, fingers));
+        }
+
+        Ok(Self { users })
+    }
+}
+
+/// What happened to one finger of the manifest
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Enrolled,
+    AlreadyEnrolled,
+    Skipped,
+    Failed(String),
+}
+
+impl Outcome {
+    fn localized_message(&self) -> String {
+        match self {
+            Outcome::Enrolled => fl!("provision-enrolled"),
+            Outcome::AlreadyEnrolled => fl!("provision-already-enrolled"),
+            Outcome::Skipped => fl!("provision-skipped"),
+            Outcome::Failed(reason) => fl!("provision-failed", reason = reason.as_str()),
+        }
+    }
+}
+
+/// Answer to the prompt before each scan
+enum Answer {
+    Scan,
+    Skip,
+    Quit,
+}
+
+impl<R: BufRead, W: Write> Runner<R, W> {
+    /// Walks through a manifest, enrolling every finger a user is missing
+    /// after asking on `input`, and ends with a summary.
+    ///
+    /// **Returns** [`EXIT_FAILURE`] if any finger failed to enroll
+    pub(super) async fn provision(
+        &mut self,
+        device: &OwnedObjectPath,
+        manifest: &Manifest,
+    ) -> Result<u8, AppError> {
+        let mut results: Vec<(&str, Finger, Outcome)> = Vec::new();
+        let mut interrupted = false;
+
+        'users: for (username, fingers) in &manifest.users {
+            let _ = writeln!(
+                self.out,
+                "{}",
+                fl!("provision-user", user = username.as_str())
+            );
+
+            let enrolled = match self.enrolled_fingers(device, username).await {
+                Ok(enrolled) => enrolled,
+                Err(err) => {
+                    let outcome = Outcome::Failed(err.localized_message());
+                    for finger in fingers {
+                        results.push((username, *finger, outcome.clone()));
+                    }
+                    continue;
+                }
+            };
+
+            for finger in fingers {
+                if enrolled.iter().any(|id| id == finger.as_finger_id()) {
+                    results.push((username, *finger, Outcome::AlreadyEnrolled));
+                    continue;
+                }
+
+                let outcome = match self.ask(username, *finger) {
+                    Answer::Skip => Outcome::Skipped,
+                    Answer::Quit => {
+                        interrupted = true;
+                        break 'users;
+                    }
+                    Answer::Scan => match self.enroll(device, username, *finger).await {
+                        Ok(EXIT_SUCCESS) => Outcome::Enrolled,
+                        Ok(EXIT_INTERRUPTED) => {
+                            interrupted = true;
+                            break 'users;
+                        }
+                        Ok(_) => Outcome::Failed(fl!("enroll-failed")),
+                        Err(err) => Outcome::Failed(err.localized_message()),
+                    },
+                };
+                results.push((username, *finger, outcome));
+            }
+        }
+
+        let _ = writeln!(self.out, "\n{}", fl!("provision-summary"));
+        for (username, finger, outcome) in &results {
+            let _ = writeln!(
+                self.out,
+                "{username}\t{}\t{}",
+                finger.as_finger_id(),
+                outcome.localized_message()
+            );
+        }
+
+        Ok(if interrupted {
+            EXIT_INTERRUPTED
+        } else if results
+            .iter()
+            .any(|(_, _, outcome)| matches!(outcome, Outcome::Failed(_)))
+        {
+            EXIT_FAILURE
+        } else {
+            EXIT_SUCCESS
+        })
+    }
+
+    /// Asks whether to scan a finger, until the answer is understood.
+    /// The end of input counts as quitting.
+    fn ask(&mut self, username: &str, finger: Finger) -> Answer {
+        loop {
+            let _ = write!(
+                self.out,
+                "{} ",
+                fl!(
+                    "provision-prompt",
+                    user = username,
+                    finger = finger.localized_name()
+                )
+            );
+            let _ = self.out.flush();
+
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) | Err(_) => return Answer::Quit,
+                Ok(_) => {}
+            }
+            match line.trim() {
+                "" => return Answer::Scan,
+                "s" | "S" => return Answer::Skip,
+                "q" | "Q" => return Answer::Quit,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::backend::fake::FakeBackend;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_toml_and_json() {
+        let toml = r#"
+            [users]
+            bob = "right-thumb, right-index-finger"
+            alice = ["right-index", "left-index", "right-index"]
+        "#;
+        let json = r#"{"users": {"bob": "right-thumb,right-index", "alice": ["right-index", "left-index"]}}"#;
+        // Users are enrolled in the order of the file.
+        let expected = Manifest {
+            users: vec![
+                (
+                    "bob".to_string(),
+                    vec![Finger::RightThumb, Finger::RightIndex],
+                ),
+                (
+                    "alice".to_string(),
+                    vec![Finger::RightIndex, Finger::LeftIndex],
+                ),
+            ],
+        };
+
+        assert_eq!(Manifest::parse(toml, false).unwrap(), expected);
+        assert_eq!(Manifest::parse(json, true).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        let err = Manifest::parse("[users]\nalice = [\"nose\"]", false).unwrap_err();
+        assert!(err.contains("nose"), "{err}");
+        assert!(Manifest::parse("[people]\nalice = []", false).is_err());
+        assert!(Manifest::parse("{\"users\": {\"alice\": 1}}", true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_provision_enrolls_missing_fingers() {
+        let fake = FakeBackend::default().with_enrolled("alice", &["right-index-finger"]);
+        let manifest = Manifest::parse(
+            "[users]\nalice = \"right-index, left-index\"\nbob = \"left-thumb, right-thumb\"",
+            false,
+        )
+        .unwrap();
+        // Scan alice's left index, retype an unknown answer, skip bob's left thumb
+        // and scan his right thumb.
+        let mut runner = Runner::new(Arc::new(fake.clone()), &b"\nx\ns\n\n"[..], Vec::new());
+
+        let code = runner
+            .provision(&fake.device.path, &manifest)
+            .await
+            .unwrap();
+
+        assert_eq!(code, EXIT_SUCCESS);
+        assert_eq!(
+            fake.enrolled("alice"),
+            vec!["right-index-finger", "left-index-finger"]
+        );
+        assert_eq!(fake.enrolled("bob"), vec!["right-thumb"]);
+        assert!(
+            !fake
+                .calls()
+                .contains(&"Enroll(alice, right-index-finger)".to_string())
+        );
+
+        let output = String::from_utf8(runner.out).unwrap();
+        let summary = output.split(&fl!("provision-summary")).nth(1).unwrap();
+        assert_eq!(
+            summary,
+            format!(
+                "\nalice\tright-index-finger\t{}\nalice\tleft-index-finger\t{}\n\
+                 bob\tleft-thumb\t{}\nbob\tright-thumb\t{}\n",
+                fl!("provision-already-enrolled"),
+                fl!("provision-enrolled"),
+                fl!("provision-skipped"),
+                fl!("provision-enrolled"),
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provision_quit() {
+        let fake = FakeBackend::default();
+        let manifest =
+            Manifest::parse("[users]\nalice = \"left-thumb, right-thumb\"", false).unwrap();
+        let mut runner = Runner::new(Arc::new(fake.clone()), &b"q\n"[..], Vec::new());
+
+        let code = runner
+            .provision(&fake.device.path, &manifest)
+            .await
+            .unwrap();
+
+        assert_eq!(code, EXIT_INTERRUPTED);
+        assert!(fake.enrolled("alice").is_empty());
+    }
+}
@@ -53,6 +53,10 @@ impl AppTheme {
 pub struct Config {
     pub app_theme: AppTheme,
     pub experimental_ui: bool,
+    /// Also list system accounts in the nav bar
+    pub show_all_users: bool,
+    /// Users added by name which accounts-daemon does not list
+    pub remembered_users: Vec<String>,
 }
 
 /// Whether we're running on the COSMIC desktop (cached for process lifetime).
@@ -41,4 +41,13 @@ pub trait Device {
 
     #[zbus(property, name = "name")]
     fn name(&self) -> zbus::Result<String>;
+
+    #[zbus(property, name = "scan-type")]
+    fn scan_type(&self) -> zbus::Result<String>;
+
+    #[zbus(property, name = "finger-present")]
+    fn finger_present(&self) -> zbus::Result<bool>;
+
+    #[zbus(property, name = "finger-needed")]
+    fn finger_needed(&self) -> zbus::Result<bool>;
 }
@@ -0,0 +1,588 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Scripted in-process fprintd daemon used by the test suite.
+//!
+//! Serves `net.reactivated.Fprint.Manager` and `net.reactivated.Fprint.Device`
+//! on a peer-to-peer connection, records every device call in order and
+//! replays scripted `EnrollStatus`/`VerifyStatus` signals, each while
+//! `finger-present` is set. With
+//! [`Flavor::OpenFprintd`] the device lacks `DeleteEnrolledFinger`,
+//! `DeleteEnrolledFingers2` and the sensor properties, like open-fprintd does.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use zbus::names::InterfaceName;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedObjectPath, Value};
+use zbus::{DBusError, interface};
+
+pub const MANAGER_PATH: &str = "/net/reactivated/Fprint/Manager";
+pub const DEVICE_PATH: &str = "/net/reactivated/Fprint/Device/0";
+const DEVICE_INTERFACE: &str = "net.reactivated.Fprint.Device";
+
+const FINGER_NAMES: &[&str] = &[
+    "left-thumb",
+    "left-index-finger",
+    "left-middle-finger",
+    "left-ring-finger",
+    "left-little-finger",
+    "right-thumb",
+    "right-index-finger",
+    "right-middle-finger",
+    "right-ring-finger",
+    "right-little-finger",
+];
+
+/// Which daemon implementation the mock pretends to be.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Flavor {
+    #[default]
+    Fprintd,
+    OpenFprintd,
+}
+
+/// Errors in the `net.reactivated.Fprint.Error` namespace.
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "net.reactivated.Fprint.Error")]
+pub enum FprintError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    PermissionDenied(String),
+    AlreadyInUse(String),
+    ClaimDevice(String),
+    NoEnrolledPrints(String),
+    NoActionInProgress(String),
+    PrintsNotDeleted(String),
+    InvalidFingername(String),
+}
+
+/// Initial state and scripts of the mock device.
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    pub flavor: Flavor,
+    pub name: String,
+    pub num_enroll_stages: i32,
+    /// `press` or `swipe`, published by fprintd only.
+    pub scan_type: String,
+    /// Signals emitted after `EnrollStart`, as `(result, done)`.
+    pub enroll_script: Vec<(String, bool)>,
+    /// Signals emitted after `VerifyStart`, as `(result, done)`.
+    pub verify_script: Vec<(String, bool)>,
+    /// Enrolled finger ids keyed by username.
+    pub enrolled: HashMap<String, Vec<String>>,
+    /// Usernames for which `Claim` is rejected with `PermissionDenied`.
+    pub denied_users: Vec<String>,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            flavor: Flavor::Fprintd,
+            name: "Mock Fingerprint Reader".to_string(),
+            num_enroll_stages: 3,
+            scan_type: "press".to_string(),
+            enroll_script: vec![
+                ("enroll-stage-passed".to_string(), false),
+                ("enroll-stage-passed".to_string(), false),
+                ("enroll-completed".to_string(), true),
+            ],
+            verify_script: vec![("verify-match".to_string(), true)],
+            enrolled: HashMap::new(),
+            denied_users: Vec::new(),
+        }
+    }
+}
+
+impl MockConfig {
+    pub fn flavor(mut self, flavor: Flavor) -> Self {
+        self.flavor = flavor;
+        self
+    }
+
+    pub fn enrolled(mut self, username: &str, fingers: &[&str]) -> Self {
+        self.enrolled.insert(
+            username.to_string(),
+            fingers.iter().map(|f| f.to_string()).collect(),
+        );
+        self
+    }
+
+    pub fn enroll_script(mut self, script: &[(&str, bool)]) -> Self {
+        self.enroll_script = script.iter().map(|(r, d)| (r.to_string(), *d)).collect();
+        self
+    }
+
+    pub fn verify_script(mut self, script: &[(&str, bool)]) -> Self {
+        self.verify_script = script.iter().map(|(r, d)| (r.to_string(), *d)).collect();
+        self
+    }
+
+    pub fn deny(mut self, username: &str) -> Self {
+        self.denied_users.push(username.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct DeviceState {
+    calls: Vec<String>,
+    claimed_by: Option<String>,
+    enrolling: Option<String>,
+    verifying: bool,
+    enrolled: HashMap<String, Vec<String>>,
+    finger_present: bool,
+    finger_needed: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Shared {
+    config: Arc<MockConfig>,
+    state: Arc<Mutex<DeviceState>>,
+}
+
+impl Shared {
+    fn record(&self, call: String) {
+        self.state.lock().unwrap().calls.push(call);
+    }
+
+    /// Sets `finger-present` or `finger-needed` and emits `PropertiesChanged`,
+    /// which open-fprintd does not since it lacks both.
+    async fn set_sensor(&self, emitter: &SignalEmitter<'_>, name: &str, value: bool) {
+        {
+            let mut state = self.state.lock().unwrap();
+            match name {
+                "finger-present" => state.finger_present = value,
+                _ => state.finger_needed = value,
+            }
+        }
+        if self.config.flavor == Flavor::Fprintd {
+            let _ = zbus::fdo::Properties::properties_changed(
+                emitter,
+                InterfaceName::from_static_str_unchecked(DEVICE_INTERFACE),
+                HashMap::from([(name, Value::from(value))]),
+                Cow::Borrowed(&[]),
+            )
+            .await;
+        }
+    }
+
+    /// **Returns** the user holding the claim or `ClaimDevice`
+    fn claimed_user(&self) -> Result<String, FprintError> {
+        self.state
+            .lock()
+            .unwrap()
+            .claimed_by
+            .clone()
+            .ok_or_else(|| FprintError::ClaimDevice("Device was not claimed".to_string()))
+    }
+
+    fn claim(&self, username: &str) -> Result<(), FprintError> {
+        self.record(format!("Claim({username})"));
+        if self.config.denied_users.iter().any(|u| u == username) {
+            return Err(FprintError::PermissionDenied("Not authorized".to_string()));
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.claimed_by.is_some() {
+            return Err(FprintError::AlreadyInUse(
+                "Device was already claimed".to_string(),
+            ));
+        }
+        state.claimed_by = Some(username.to_string());
+        Ok(())
+    }
+
+    fn release(&self) -> Result<(), FprintError> {
+        self.record("Release".to_string());
+        let mut state = self.state.lock().unwrap();
+        if state.claimed_by.take().is_none() {
+            return Err(FprintError::ClaimDevice(
+                "Device was not claimed".to_string(),
+            ));
+        }
+        state.enrolling = None;
+        state.verifying = false;
+        state.finger_present = false;
+        state.finger_needed = false;
+        Ok(())
+    }
+
+    fn list_enrolled_fingers(&self, username: &str) -> Result<Vec<String>, FprintError> {
+        self.record(format!("ListEnrolledFingers({username})"));
+        match self.state.lock().unwrap().enrolled.get(username) {
+            Some(fingers) if !fingers.is_empty() => Ok(fingers.clone()),
+            _ => Err(FprintError::NoEnrolledPrints(
+                "No prints enrolled".to_string(),
+            )),
+        }
+    }
+
+    fn delete_enrolled_finger(&self, finger_name: &str) -> Result<(), FprintError> {
+        self.record(format!("DeleteEnrolledFinger({finger_name})"));
+        let username = self.claimed_user()?;
+        let mut state = self.state.lock().unwrap();
+        let fingers = state.enrolled.entry(username).or_default();
+        let before = fingers.len();
+        fingers.retain(|f| f != finger_name);
+        if fingers.len() == before {
+            return Err(FprintError::NoEnrolledPrints(
+                "Finger not enrolled".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn delete_enrolled_fingers2(&self) -> Result<(), FprintError> {
+        self.record("DeleteEnrolledFingers2".to_string());
+        let username = self.claimed_user()?;
+        self.state.lock().unwrap().enrolled.remove(&username);
+        Ok(())
+    }
+
+    fn delete_enrolled_fingers(&self, username: &str) -> Result<(), FprintError> {
+        self.record(format!("DeleteEnrolledFingers({username})"));
+        self.state.lock().unwrap().enrolled.remove(username);
+        Ok(())
+    }
+
+    fn enroll_start(
+        &self,
+        finger_name: &str,
+        emitter: SignalEmitter<'_>,
+    ) -> Result<(), FprintError> {
+        self.record(format!("EnrollStart({finger_name})"));
+        let username = self.claimed_user()?;
+        if !FINGER_NAMES.contains(&finger_name) {
+            return Err(FprintError::InvalidFingername(finger_name.to_string()));
+        }
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.enrolling.is_some() || state.verifying {
+                return Err(FprintError::AlreadyInUse("Action in progress".to_string()));
+            }
+            state.enrolling = Some(finger_name.to_string());
+        }
+
+        let shared = self.clone();
+        let emitter = emitter.into_owned();
+        let finger_name = finger_name.to_string();
+        tokio::spawn(async move {
+            shared.set_sensor(&emitter, "finger-needed", true).await;
+            for (result, done) in shared.config.enroll_script.iter() {
+                if shared.state.lock().unwrap().enrolling.is_none() {
+                    return;
+                }
+                if *done && result == "enroll-completed" {
+                    let mut state = shared.state.lock().unwrap();
+                    let fingers = state.enrolled.entry(username.clone()).or_default();
+                    if !fingers.contains(&finger_name) {
+                        fingers.push(finger_name.clone());
+                    }
+                }
+                shared.set_sensor(&emitter, "finger-present", true).await;
+                let _ = Device::enroll_status(&emitter, result, *done).await;
+                shared.set_sensor(&emitter, "finger-present", false).await;
+            }
+            shared.set_sensor(&emitter, "finger-needed", false).await;
+        });
+        Ok(())
+    }
+
+    fn enroll_stop(&self) -> Result<(), FprintError> {
+        self.record("EnrollStop".to_string());
+        self.claimed_user()?;
+        if self.state.lock().unwrap().enrolling.take().is_none() {
+            return Err(FprintError::NoActionInProgress("No enrollment".to_string()));
+        }
+        Ok(())
+    }
+
+    fn verify_start(
+        &self,
+        finger_name: &str,
+        emitter: SignalEmitter<'_>,
+    ) -> Result<(), FprintError> {
+        self.record(format!("VerifyStart({finger_name})"));
+        let username = self.claimed_user()?;
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.enrolled.get(&username).is_none_or(|f| f.is_empty()) {
+                return Err(FprintError::NoEnrolledPrints(
+                    "No prints enrolled".to_string(),
+                ));
+            }
+            if state.enrolling.is_some() || state.verifying {
+                return Err(FprintError::AlreadyInUse("Action in progress".to_string()));
+            }
+            state.verifying = true;
+        }
+
+        let shared = self.clone();
+        let emitter = emitter.into_owned();
+        tokio::spawn(async move {
+            shared.set_sensor(&emitter, "finger-needed", true).await;
+            for (result, done) in shared.config.verify_script.iter() {
+                if !shared.state.lock().unwrap().verifying {
+                    return;
+                }
+                shared.set_sensor(&emitter, "finger-present", true).await;
+                let _ = Device::verify_status(&emitter, result, *done).await;
+                shared.set_sensor(&emitter, "finger-present", false).await;
+            }
+            shared.set_sensor(&emitter, "finger-needed", false).await;
+        });
+        Ok(())
+    }
+
+    fn verify_stop(&self) -> Result<(), FprintError> {
+        self.record("VerifyStop".to_string());
+        self.claimed_user()?;
+        let mut state = self.state.lock().unwrap();
+        if !state.verifying {
+            return Err(FprintError::NoActionInProgress(
+                "No verification".to_string(),
+            ));
+        }
+        state.verifying = false;
+        Ok(())
+    }
+}
+
+struct Manager;
+
+#[interface(name = "net.reactivated.Fprint.Manager")]
+impl Manager {
+    fn get_default_device(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(DEVICE_PATH).unwrap()
+    }
+
+    fn get_devices(&self) -> Vec<OwnedObjectPath> {
+        vec![OwnedObjectPath::try_from(DEVICE_PATH).unwrap()]
+    }
+}
+
+/// Device interface as published by fprintd.
+struct Device(Shared);
+
+#[interface(name = "net.reactivated.Fprint.Device")]
+impl Device {
+    fn claim(&self, username: &str) -> Result<(), FprintError> {
+        self.0.claim(username)
+    }
+
+    fn release(&self) -> Result<(), FprintError> {
+        self.0.release()
+    }
+
+    fn list_enrolled_fingers(&self, username: &str) -> Result<Vec<String>, FprintError> {
+        self.0.list_enrolled_fingers(username)
+    }
+
+    fn delete_enrolled_finger(&self, finger_name: &str) -> Result<(), FprintError> {
+        self.0.delete_enrolled_finger(finger_name)
+    }
+
+    fn delete_enrolled_fingers2(&self) -> Result<(), FprintError> {
+        self.0.delete_enrolled_fingers2()
+    }
+
+    fn delete_enrolled_fingers(&self, username: &str) -> Result<(), FprintError> {
+        self.0.delete_enrolled_fingers(username)
+    }
+
+    fn enroll_start(
+        &self,
+        finger_name: &str,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<(), FprintError> {
+        self.0.enroll_start(finger_name, emitter)
+    }
+
+    fn enroll_stop(&self) -> Result<(), FprintError> {
+        self.0.enroll_stop()
+    }
+
+    fn verify_start(
+        &self,
+        finger_name: &str,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<(), FprintError> {
+        self.0.verify_start(finger_name, emitter)
+    }
+
+    fn verify_stop(&self) -> Result<(), FprintError> {
+        self.0.verify_stop()
+    }
+
+    #[zbus(signal)]
+    async fn enroll_status(
+        emitter: &SignalEmitter<'_>,
+        result: &str,
+        done: bool,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn verify_status(
+        emitter: &SignalEmitter<'_>,
+        result: &str,
+        done: bool,
+    ) -> zbus::Result<()>;
+
+    #[zbus(property, name = "num-enroll-stages")]
+    fn num_enroll_stages(&self) -> i32 {
+        self.0.config.num_enroll_stages
+    }
+
+    #[zbus(property, name = "name")]
+    fn name(&self) -> String {
+        self.0.config.name.clone()
+    }
+
+    #[zbus(property, name = "scan-type")]
+    fn scan_type(&self) -> String {
+        self.0.config.scan_type.clone()
+    }
+
+    #[zbus(property, name = "finger-present")]
+    fn finger_present(&self) -> bool {
+        self.0.state.lock().unwrap().finger_present
+    }
+
+    #[zbus(property, name = "finger-needed")]
+    fn finger_needed(&self) -> bool {
+        self.0.state.lock().unwrap().finger_needed
+    }
+}
+
+/// Device interface as published by open-fprintd, which only knows the
+/// legacy `DeleteEnrolledFingers(username)`.
+struct LegacyDevice(Shared);
+
+#[interface(name = "net.reactivated.Fprint.Device")]
+impl LegacyDevice {
+    fn claim(&self, username: &str) -> Result<(), FprintError> {
+        self.0.claim(username)
+    }
+
+    fn release(&self) -> Result<(), FprintError> {
+        self.0.release()
+    }
+
+    fn list_enrolled_fingers(&self, username: &str) -> Result<Vec<String>, FprintError> {
+        self.0.list_enrolled_fingers(username)
+    }
+
+    fn delete_enrolled_fingers(&self, username: &str) -> Result<(), FprintError> {
+        self.0.delete_enrolled_fingers(username)
+    }
+
+    fn enroll_start(
+        &self,
+        finger_name: &str,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<(), FprintError> {
+        self.0.enroll_start(finger_name, emitter)
+    }
+
+    fn enroll_stop(&self) -> Result<(), FprintError> {
+        self.0.enroll_stop()
+    }
+
+    fn verify_start(
+        &self,
+        finger_name: &str,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<(), FprintError> {
+        self.0.verify_start(finger_name, emitter)
+    }
+
+    fn verify_stop(&self) -> Result<(), FprintError> {
+        self.0.verify_stop()
+    }
+
+    #[zbus(property, name = "num-enroll-stages")]
+    fn num_enroll_stages(&self) -> i32 {
+        self.0.config.num_enroll_stages
+    }
+
+    #[zbus(property, name = "name")]
+    fn name(&self) -> String {
+        self.0.config.name.clone()
+    }
+}
+
+/// A running mock daemon and the client side of its connection.
+pub struct MockFprintd {
+    /// Client connection to pass to the functions under test.
+    pub connection: zbus::Connection,
+    /// Object path of the single mock device.
+    pub device_path: OwnedObjectPath,
+    // Keeps the server side of the peer-to-peer connection alive.
+    _server: zbus::Connection,
+    shared: Shared,
+}
+
+impl MockFprintd {
+    /// Starts the daemon on a private socket pair.
+    pub async fn start(config: MockConfig) -> zbus::Result<Self> {
+        let shared = Shared {
+            state: Arc::new(Mutex::new(DeviceState {
+                enrolled: config.enrolled.clone(),
+                ..Default::default()
+            })),
+            config: Arc::new(config),
+        };
+
+        let (server_stream, client_stream) = tokio::net::UnixStream::pair()?;
+        let builder = zbus::connection::Builder::unix_stream(server_stream)
+            .server(zbus::Guid::generate())?
+            .p2p()
+            .serve_at(MANAGER_PATH, Manager)?;
+        let builder = match shared.config.flavor {
+            Flavor::Fprintd => builder.serve_at(DEVICE_PATH, Device(shared.clone()))?,
+            Flavor::OpenFprintd => builder.serve_at(DEVICE_PATH, LegacyDevice(shared.clone()))?,
+        };
+
+        let (server, connection) = futures_util::try_join!(
+            builder.build(),
+            zbus::connection::Builder::unix_stream(client_stream)
+                .p2p()
+                .build(),
+        )?;
+
+        Ok(Self {
+            connection,
+            device_path: OwnedObjectPath::try_from(DEVICE_PATH)?,
+            _server: server,
+            shared,
+        })
+    }
+
+    /// **Returns** every device method call received so far, in order
+    pub fn calls(&self) -> Vec<String> {
+        self.shared.state.lock().unwrap().calls.clone()
+    }
+
+    /// **Returns** the prints currently stored for a user
+    pub fn enrolled(&self, username: &str) -> Vec<String> {
+        self.shared
+            .state
+            .lock()
+            .unwrap()
+            .enrolled
+            .get(username)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// **Returns** whether the device is left claimed
+    pub fn is_claimed(&self) -> bool {
+        self.shared.state.lock().unwrap().claimed_by.is_some()
+    }
+
+    /// Claims the device on behalf of another client.
+    pub fn claim_elsewhere(&self, username: &str) {
+        self.shared.state.lock().unwrap().claimed_by = Some(username.to_string());
+    }
+}
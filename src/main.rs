@@ -2,9 +2,16 @@
 
 mod accounts_dbus;
 mod app;
+mod cli;
 mod config;
 mod fprint_dbus;
+#[cfg(test)]
+mod fprint_mock;
 mod i18n;
+mod polkit_dbus;
+mod systemd_dbus;
+
+use clap::Parser;
 
 const WINDOW_MIN_WIDTH: f32 = 360.0;
 const WINDOW_MIN_HEIGHT: f32 = 600.0;
@@ -16,6 +23,11 @@ fn main() -> cosmic::iced::Result {
     // Enable localizations to be applied.
     i18n::init(&requested_languages);
 
+    // Run a command-line command instead of the GUI when one is given.
+    if let Some(command) = cli::Cli::parse().command {
+        std::process::exit(cli::run(command).into());
+    }
+
     // Settings for configuring the application window and iced runtime.
     let settings = cosmic::app::Settings::default().size_limits(
         cosmic::iced::Limits::NONE
@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use std::collections::HashMap;
+
+use zbus::proxy;
+use zbus::zvariant::Value;
+
+/// Subject kind and details, e.g. `("system-bus-name", {"name": ":1.42"})`
+pub type Subject<'a> = (&'a str, HashMap<&'a str, Value<'a>>);
+
+#[proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+pub trait Authority {
+    /// **Returns** whether the subject is authorized, whether it would be
+    /// after authenticating, and details
+    fn check_authorization(
+        &self,
+        subject: &Subject<'_>,
+        action_id: &str,
+        details: HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
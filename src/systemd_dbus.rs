@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use zbus::proxy;
+use zbus::zvariant::OwnedObjectPath;
+
+#[proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1"
+)]
+pub trait Manager {
+    /// **Returns** the unit, loading it from disk if needed
+    fn load_unit(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+
+    /// Starts a unit, polkit may ask to authenticate first.
+    /// **Returns** the queued job
+    #[zbus(allow_interactive_auth)]
+    fn start_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+
+    /// Lets a unit which hit its start limit be started again
+    #[zbus(allow_interactive_auth)]
+    fn reset_failed_unit(&self, name: &str) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.systemd1.Unit",
+    default_service = "org.freedesktop.systemd1"
+)]
+pub trait Unit {
+    /// e.g. `loaded`, `not-found` or `masked`
+    #[zbus(property)]
+    fn load_state(&self) -> zbus::Result<String>;
+
+    /// e.g. `active`, `inactive` or `failed`
+    #[zbus(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+}